@@ -0,0 +1,118 @@
+//! Compares `step()` on boxed `Transition`s with `step_static()` on
+//! `StaticTransition`s, reporting time and heap allocations per step.
+//!
+//! Run with `cargo bench --bench step`. Only the step effect is measured;
+//! results are not applied, so history does not grow.
+
+use mindset::effects::{
+    StateMachine, StaticTransition, Transition, TransitionError, TransitionResult,
+};
+use mindset::state_enum;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use stillwater::effect::Effect;
+use stillwater::prelude::*;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const STEPS: usize = 1_000_000;
+
+state_enum! {
+    enum Job {
+        Queued,
+        Running,
+    }
+}
+
+#[derive(Clone)]
+enum JobAction {
+    Start,
+}
+
+impl Effect for JobAction {
+    type Output = TransitionResult<Job>;
+    type Error = TransitionError;
+    type Env = ();
+
+    async fn run(self, _env: &()) -> Result<TransitionResult<Job>, TransitionError> {
+        match self {
+            Self::Start => Ok(TransitionResult::Success(Job::Running)),
+        }
+    }
+}
+
+/// Run `step` `STEPS` times, returning nanoseconds and allocations per step
+fn measure<F, Fut>(runtime: &tokio::runtime::Runtime, mut step: F) -> (f64, f64)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    runtime.block_on(async {
+        for _ in 0..STEPS / 10 {
+            step().await;
+        }
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let started = Instant::now();
+        for _ in 0..STEPS {
+            step().await;
+        }
+        let elapsed = started.elapsed();
+        let allocated = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        (
+            elapsed.as_nanos() as f64 / STEPS as f64,
+            allocated as f64 / STEPS as f64,
+        )
+    })
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let mut boxed = StateMachine::<Job, ()>::new(Job::Queued);
+    boxed.add_transition(Transition::new(Job::Queued, Job::Running, || {
+        pure(TransitionResult::Success(Job::Running)).boxed()
+    }));
+    let (boxed_ns, boxed_allocs) = measure(&runtime, || async {
+        black_box(boxed.step().run(&()).await.unwrap());
+    });
+
+    let machine = StateMachine::<Job, ()>::new(Job::Queued);
+    let transitions = [StaticTransition::new(
+        Job::Queued,
+        Job::Running,
+        JobAction::Start,
+    )];
+    let (static_ns, static_allocs) = measure(&runtime, || async {
+        black_box(machine.step_static(&transitions).run(&()).await.unwrap());
+    });
+
+    println!("{:<28} {:>10} {:>14}", "path", "ns/step", "allocs/step");
+    println!(
+        "{:<28} {:>10.1} {:>14.2}",
+        "step() / Transition", boxed_ns, boxed_allocs
+    );
+    println!(
+        "{:<28} {:>10.1} {:>14.2}",
+        "step_static() / enum action", static_ns, static_allocs
+    );
+}
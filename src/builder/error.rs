@@ -1,5 +1,6 @@
 //! Build errors for state machine and transition builders.
 
+use std::panic::Location;
 use thiserror::Error;
 
 /// Errors that can occur when building state machines and transitions.
@@ -19,4 +20,17 @@ pub enum BuildError {
 
     #[error("Transition action not specified. Call .action(effect) or .succeeds()")]
     MissingAction,
+
+    #[error("Transition from final state '{from}' to '{to}' is forbidden. Allow it with .allow_from_final(from, to)")]
+    TransitionFromFinalState {
+        from: String,
+        to: String,
+        location: Option<&'static Location<'static>>,
+    },
+
+    #[error("Invalid transition defined at {location}: {source}")]
+    InvalidTransition {
+        location: &'static Location<'static>,
+        source: Box<BuildError>,
+    },
 }
@@ -2,14 +2,40 @@
 
 use crate::builder::error::BuildError;
 use crate::builder::transition::TransitionBuilder;
+use crate::checkpoint::IdGenerator;
 use crate::core::State;
-use crate::effects::{StateMachine, Transition};
+use crate::effects::{CapabilityKey, StateHook, StateMachine, Transition, TransitionError};
+use std::collections::BTreeSet;
 use std::marker::PhantomData;
+use std::panic::Location;
+use std::sync::Arc;
+use stillwater::effect::BoxedEffect;
+
+/// Whether a builder accepts transitions out of final states.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinalTransitionPolicy {
+    /// Accept them like any other transition
+    #[default]
+    Allow,
+    /// Reject them with [`BuildError::TransitionFromFinalState`] unless
+    /// the edge was allowed with
+    /// [`allow_from_final`](StateMachineBuilder::allow_from_final)
+    Forbid,
+}
 
 /// Builder for constructing state machines with a fluent API.
 pub struct StateMachineBuilder<S: State + 'static, Env: Clone + Send + Sync + 'static> {
     initial: Option<S>,
     transitions: Vec<Transition<S, Env>>,
+    final_transitions: FinalTransitionPolicy,
+    /// Allowed `(from, to)` name pairs out of final states, when
+    /// transitions from final states are forbidden
+    reopen_edges: BTreeSet<(String, String)>,
+    required_capabilities: Vec<CapabilityKey>,
+    entry_hooks: Vec<(S, StateHook<S, Env>)>,
+    exit_hooks: Vec<(S, StateHook<S, Env>)>,
+    history_bound: Option<usize>,
+    id_generator: Option<Arc<dyn IdGenerator>>,
     _phantom: PhantomData<Env>,
 }
 
@@ -19,6 +45,13 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachineBuilder
         Self {
             initial: None,
             transitions: Vec::new(),
+            final_transitions: FinalTransitionPolicy::default(),
+            reopen_edges: BTreeSet::new(),
+            required_capabilities: Vec::new(),
+            entry_hooks: Vec::new(),
+            exit_hooks: Vec::new(),
+            history_bound: None,
+            id_generator: None,
             _phantom: PhantomData,
         }
     }
@@ -30,22 +63,119 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachineBuilder
     }
 
     /// Add a transition using a builder.
-    /// Returns an error if the builder fails validation.
+    /// Returns an error if the builder fails validation, tagged with the
+    /// location the transition builder was created at.
     pub fn transition(mut self, builder: TransitionBuilder<S, Env>) -> Result<Self, BuildError> {
-        let transition = builder.build()?;
+        let location = builder.location();
+        let transition = builder
+            .build()
+            .map_err(|source| BuildError::InvalidTransition {
+                location,
+                source: Box::new(source),
+            })?;
         self.transitions.push(transition);
         Ok(self)
     }
 
+    /// Add a transition configured by `configure` on a fresh builder:
+    /// `.transition_with(|t| t.from(A).to(B).succeeds())?`.
+    /// Returns an error if the builder fails validation, tagged with the
+    /// location of the call.
+    #[track_caller]
+    pub fn transition_with<F>(self, configure: F) -> Result<Self, BuildError>
+    where
+        F: FnOnce(TransitionBuilder<S, Env>) -> TransitionBuilder<S, Env>,
+    {
+        self.transition(configure(TransitionBuilder::new()))
+    }
+
     /// Add a pre-built transition.
-    pub fn add_transition(mut self, transition: Transition<S, Env>) -> Self {
+    /// Records the caller's source location if the transition has none.
+    #[track_caller]
+    pub fn add_transition(mut self, mut transition: Transition<S, Env>) -> Self {
+        transition.location.get_or_insert(Location::caller());
         self.transitions.push(transition);
         self
     }
 
     /// Add multiple transitions at once.
+    /// Records the caller's source location on transitions that have none.
+    #[track_caller]
     pub fn transitions(mut self, transitions: Vec<Transition<S, Env>>) -> Self {
-        self.transitions.extend(transitions);
+        let location = Location::caller();
+        self.transitions
+            .extend(transitions.into_iter().map(|mut transition| {
+                transition.location.get_or_insert(location);
+                transition
+            }));
+        self
+    }
+
+    /// Set whether transitions may start from a final state, replacing
+    /// the default [`FinalTransitionPolicy::Allow`].
+    ///
+    /// Final states usually end a workflow, so an edge out of one is often
+    /// a definition mistake. Hosts that build many machines can forbid
+    /// them everywhere by passing a shared policy, and allow intentional
+    /// re-open edges with [`allow_from_final`](Self::allow_from_final).
+    pub fn final_transition_policy(mut self, policy: FinalTransitionPolicy) -> Self {
+        self.final_transitions = policy;
+        self
+    }
+
+    /// Reject transitions that start from a final state; shorthand for
+    /// `.final_transition_policy(FinalTransitionPolicy::Forbid)`.
+    pub fn forbid_transitions_from_final(self) -> Self {
+        self.final_transition_policy(FinalTransitionPolicy::Forbid)
+    }
+
+    /// Allow the transition from final state `from` to `to` even though
+    /// transitions from final states are forbidden. Implies
+    /// [`forbid_transitions_from_final`](Self::forbid_transitions_from_final).
+    pub fn allow_from_final(mut self, from: S, to: S) -> Self {
+        self.reopen_edges
+            .insert((from.name().to_string(), to.name().to_string()));
+        self.forbid_transitions_from_final()
+    }
+
+    /// Declare that the machine's actions need capability `T`, checked
+    /// with `StateMachine::check_capabilities` before driving it.
+    pub fn requires<T: ?Sized + 'static>(mut self) -> Self {
+        self.required_capabilities.push(CapabilityKey::of::<T>());
+        self
+    }
+
+    /// Run `hook` whenever a step moves the machine into `state`
+    /// (see `StateMachine::on_entry`).
+    pub fn on_entry<F>(mut self, state: S, hook: F) -> Self
+    where
+        F: Fn(&S) -> BoxedEffect<(), TransitionError, Env> + Send + Sync + 'static,
+    {
+        self.entry_hooks.push((state, Arc::new(hook)));
+        self
+    }
+
+    /// Run `hook` whenever a step moves the machine out of `state`
+    /// (see `StateMachine::on_exit`).
+    pub fn on_exit<F>(mut self, state: S, hook: F) -> Self
+    where
+        F: Fn(&S) -> BoxedEffect<(), TransitionError, Env> + Send + Sync + 'static,
+    {
+        self.exit_hooks.push((state, Arc::new(hook)));
+        self
+    }
+
+    /// Keep only the latest `N` transitions in the machine's history
+    /// (see `StateMachine::bound_history`).
+    pub fn bound_history<const N: usize>(mut self) -> Self {
+        self.history_bound = Some(N);
+        self
+    }
+
+    /// Generate the machine's own id and its checkpoint and trace ids
+    /// with `ids` (see `StateMachine::set_id_generator`).
+    pub fn id_generator(mut self, ids: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Some(Arc::new(ids));
         self
     }
 
@@ -58,10 +188,41 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachineBuilder
             return Err(BuildError::NoTransitions);
         }
 
+        if self.final_transitions == FinalTransitionPolicy::Forbid {
+            let forbidden = self.transitions.iter().find(|t| {
+                t.from.is_final()
+                    && !self
+                        .reopen_edges
+                        .contains(&(t.from.name().to_string(), t.to.name().to_string()))
+            });
+            if let Some(transition) = forbidden {
+                return Err(BuildError::TransitionFromFinalState {
+                    from: transition.from.name().to_string(),
+                    to: transition.to.name().to_string(),
+                    location: transition.location,
+                });
+            }
+        }
+
         let mut machine = StateMachine::new(initial);
         for transition in self.transitions {
             machine.add_transition(transition);
         }
+        for key in self.required_capabilities {
+            machine.require_capability(key);
+        }
+        for (state, hook) in self.entry_hooks {
+            machine.on_entry(&state, move |s: &S| hook(s));
+        }
+        for (state, hook) in self.exit_hooks {
+            machine.on_exit(&state, move |s: &S| hook(s));
+        }
+        if let Some(capacity) = self.history_bound {
+            machine.set_history_bound(capacity);
+        }
+        if let Some(ids) = self.id_generator {
+            machine.install_id_generator(ids);
+        }
 
         Ok(machine)
     }
@@ -122,12 +283,97 @@ mod tests {
         assert!(matches!(result, Err(BuildError::NoTransitions)));
     }
 
+    #[test]
+    fn invalid_transition_reports_definition_site() {
+        let line = line!() + 3;
+        let result = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .transition(TransitionBuilder::new().from(TestState::Initial));
+
+        match result {
+            Err(BuildError::InvalidTransition { location, source }) => {
+                assert_eq!((location.file(), location.line()), (file!(), line));
+                assert!(matches!(*source, BuildError::MissingToState));
+            }
+            _ => panic!("expected InvalidTransition"),
+        }
+    }
+
+    #[test]
+    fn closures_configure_fresh_transition_builders() {
+        let machine = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .transition_with(|t| {
+                t.from(TestState::Initial)
+                    .to(TestState::Processing)
+                    .succeeds()
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(machine.transitions().len(), 1);
+
+        let line = line!() + 3;
+        let result = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .transition_with(|t| t.to(TestState::Complete).succeeds());
+
+        match result {
+            Err(BuildError::InvalidTransition { location, source }) => {
+                assert_eq!((location.file(), location.line()), (file!(), line));
+                assert!(matches!(*source, BuildError::MissingFromState));
+            }
+            _ => panic!("expected InvalidTransition"),
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_registers_state_hooks() {
+        let entered = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&entered);
+        let mut machine = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .add_transition(crate::builder::simple_transition(
+                TestState::Initial,
+                TestState::Complete,
+            ))
+            .on_entry(TestState::Complete, move |_| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                stillwater::prelude::pure(()).boxed()
+            })
+            .build()
+            .unwrap();
+
+        let (from, result, attempt) = machine.step().run(&()).await.unwrap();
+        assert_eq!(entered.load(std::sync::atomic::Ordering::SeqCst), 1);
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.current_state(), &TestState::Complete);
+    }
+
+    #[test]
+    fn added_transitions_record_caller_location() {
+        let machine = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .add_transition(crate::builder::simple_transition(
+                TestState::Initial,
+                TestState::Complete,
+            ))
+            .build()
+            .unwrap();
+
+        let location = machine.transitions()[0].location.unwrap();
+        assert_eq!(location.file(), file!());
+    }
+
     #[test]
     fn fluent_api_builds_machine() {
         let transition1: Transition<TestState, ()> = Transition {
             from: TestState::Initial,
             to: TestState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(TestState::Processing)).boxed()),
         };
 
@@ -135,6 +381,9 @@ mod tests {
             from: TestState::Processing,
             to: TestState::Complete,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(TestState::Complete)).boxed()),
         };
 
@@ -156,12 +405,18 @@ mod tests {
                 from: TestState::Initial,
                 to: TestState::Processing,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| pure(TransitionResult::Success(TestState::Processing)).boxed()),
             },
             Transition {
                 from: TestState::Processing,
                 to: TestState::Complete,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| pure(TransitionResult::Success(TestState::Complete)).boxed()),
             },
         ];
@@ -173,4 +428,91 @@ mod tests {
 
         assert!(machine.is_ok());
     }
+
+    #[test]
+    fn transitions_from_final_states_can_be_forbidden() {
+        let builder = || {
+            StateMachineBuilder::<TestState, ()>::new()
+                .initial(TestState::Initial)
+                .add_transition(crate::builder::simple_transition(
+                    TestState::Initial,
+                    TestState::Failed,
+                ))
+                .add_transition(crate::builder::simple_transition(
+                    TestState::Failed,
+                    TestState::Initial,
+                ))
+        };
+
+        assert!(builder().build().is_ok());
+        assert!(matches!(
+            builder().forbid_transitions_from_final().build(),
+            Err(BuildError::TransitionFromFinalState { ref from, ref to, location: Some(_) })
+                if from == "Failed" && to == "Initial"
+        ));
+        assert!(builder()
+            .forbid_transitions_from_final()
+            .allow_from_final(TestState::Failed, TestState::Initial)
+            .build()
+            .is_ok());
+
+        let policy = FinalTransitionPolicy::Forbid;
+        assert!(builder().final_transition_policy(policy).build().is_err());
+        assert!(builder()
+            .forbid_transitions_from_final()
+            .final_transition_policy(FinalTransitionPolicy::Allow)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn bounded_machines_keep_the_latest_transitions() {
+        let mut machine = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .add_transition(crate::builder::simple_transition(
+                TestState::Initial,
+                TestState::Processing,
+            ))
+            .bound_history::<2>()
+            .build()
+            .unwrap();
+
+        for (from, to) in [
+            (TestState::Initial, TestState::Processing),
+            (TestState::Processing, TestState::Initial),
+            (TestState::Initial, TestState::Processing),
+            (TestState::Processing, TestState::Complete),
+        ] {
+            machine.apply_result(from, crate::effects::StepResult::Transitioned(to), 1);
+        }
+
+        let sequences: Vec<_> = machine
+            .history()
+            .transitions()
+            .iter()
+            .map(|t| (t.to.clone(), t.sequence))
+            .collect();
+        assert_eq!(
+            sequences,
+            vec![(TestState::Processing, 3), (TestState::Complete, 4)]
+        );
+        assert!(machine.history().events().len() <= 2);
+        assert_eq!(machine.checkpoint().metadata.stats.transitions, 4);
+    }
+
+    #[test]
+    fn built_machines_draw_their_ids_from_the_id_generator() {
+        let machine = StateMachineBuilder::<TestState, ()>::new()
+            .initial(TestState::Initial)
+            .add_transition(crate::builder::simple_transition(
+                TestState::Initial,
+                TestState::Processing,
+            ))
+            .id_generator(crate::checkpoint::SequentialIds::new("job"))
+            .build()
+            .unwrap();
+
+        assert_eq!(machine.id(), "job-1");
+        assert_eq!(machine.checkpoint().id, "job-2");
+    }
 }
@@ -9,7 +9,7 @@ pub mod macros;
 pub mod transition;
 
 pub use error::BuildError;
-pub use machine::StateMachineBuilder;
+pub use machine::{FinalTransitionPolicy, StateMachineBuilder};
 pub use transition::TransitionBuilder;
 
 use crate::core::State;
@@ -34,6 +34,7 @@ use stillwater::prelude::*;
 ///
 /// let transition = simple_transition::<MyState, ()>(MyState::Start, MyState::End);
 /// ```
+#[track_caller]
 pub fn simple_transition<S, Env>(from: S, to: S) -> Transition<S, Env>
 where
     S: State + 'static,
@@ -72,6 +73,7 @@ where
 ///     |s| !s.is_final()
 /// );
 /// ```
+#[track_caller]
 pub fn guarded_transition<S, Env, F>(from: S, to: S, guard: F) -> Transition<S, Env>
 where
     S: State + 'static,
@@ -1,8 +1,10 @@
 //! Builder for constructing state transitions.
 
 use crate::builder::error::BuildError;
-use crate::core::{Guard, State};
+use crate::core::{Guard, GuardExpr, State};
 use crate::effects::{Transition, TransitionError, TransitionResult};
+use std::collections::BTreeMap;
+use std::panic::Location;
 use std::sync::Arc;
 use stillwater::effect::BoxedEffect;
 use stillwater::prelude::*;
@@ -17,16 +19,24 @@ pub struct TransitionBuilder<S: State, Env> {
     to: Option<S>,
     guard: Option<Guard<S>>,
     action: Option<ActionFactory<S, Env>>,
+    location: &'static Location<'static>,
+    metadata: BTreeMap<String, String>,
+    flag: Option<String>,
 }
 
 impl<S: State + 'static, Env> TransitionBuilder<S, Env> {
     /// Create a new transition builder.
+    /// The caller's source location is recorded on the built transition.
+    #[track_caller]
     pub fn new() -> Self {
         Self {
             from: None,
             to: None,
             guard: None,
             action: None,
+            location: Location::caller(),
+            metadata: BTreeMap::new(),
+            flag: None,
         }
     }
 
@@ -57,6 +67,26 @@ impl<S: State + 'static, Env> TransitionBuilder<S, Env> {
         self
     }
 
+    /// Add a guard from a serializable expression (optional).
+    pub fn when_expr(mut self, expr: GuardExpr) -> Self {
+        self.guard = Some(expr.into_guard());
+        self
+    }
+
+    /// Attach a metadata entry, e.g. owner team or docs URL (optional).
+    /// Setting an existing key replaces its value.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Roll the transition out behind a feature flag (optional).
+    /// See `StateMachine::step_with_flags`.
+    pub fn behind_flag(mut self, flag: impl Into<String>) -> Self {
+        self.flag = Some(flag.into());
+        self
+    }
+
     /// Set the action effect (required).
     pub fn action<E>(mut self, effect: E) -> Self
     where
@@ -79,6 +109,11 @@ impl<S: State + 'static, Env> TransitionBuilder<S, Env> {
         self.action(move || pure(TransitionResult::Success(to.clone())).boxed())
     }
 
+    /// Get the source location the builder was created at.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
     /// Build the transition.
     pub fn build(self) -> Result<Transition<S, Env>, BuildError> {
         let from = self.from.ok_or(BuildError::MissingFromState)?;
@@ -89,12 +124,16 @@ impl<S: State + 'static, Env> TransitionBuilder<S, Env> {
             from,
             to,
             guard: self.guard,
+            location: Some(self.location),
+            metadata: self.metadata,
+            flag: self.flag,
             action,
         })
     }
 }
 
 impl<S: State + 'static, Env> Default for TransitionBuilder<S, Env> {
+    #[track_caller]
     fn default() -> Self {
         Self::new()
     }
@@ -0,0 +1,363 @@
+//! Catalog of named, versioned machine definitions.
+//!
+//! Services hosting many workflow types keep their definitions in a
+//! [`DefinitionCatalog`]: each [`Definition`] has a name, a version and
+//! free-form tags, and is looked up by `name@version` (or by name alone for
+//! the latest version). Definitions are plain data built from
+//! [`TransitionSpec`]s, so the catalog can be loaded from configuration and
+//! instantiated against the [`ActionRegistry`] of the running binary.
+//!
+//! Machines remember the definition version they were created from, so two
+//! versions can run side by side: [`DefinitionCatalog::instantiate`] with a
+//! bare name starts new instances on the latest version, while
+//! [`DefinitionCatalog::resume`] continues existing instances on the
+//! version they started with. Feeding each version's runs to its own
+//! [`StatsCollector`](crate::stats::StatsCollector) splits metrics by
+//! version.
+
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::core::State;
+use crate::effects::{ActionRegistry, DefinitionError, StateMachine, TransitionSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Label set on instantiated machines to the `name@version` of their
+/// definition.
+pub const DEFINITION_LABEL: &str = "definition";
+
+/// Errors from catalog registration and lookup
+#[derive(Debug, thiserror::Error)]
+pub enum CatalogError {
+    #[error("Definition '{0}' is already registered")]
+    AlreadyRegistered(DefinitionRef),
+
+    #[error("No definition matches '{reference}'")]
+    NotFound { reference: String },
+
+    #[error("Invalid definition reference '{reference}'. Expected name or name@version")]
+    InvalidReference { reference: String },
+
+    #[error("Checkpoint has no '{DEFINITION_LABEL}' label naming its definition")]
+    UnknownDefinition,
+
+    #[error(transparent)]
+    Definition(#[from] DefinitionError),
+
+    #[error(transparent)]
+    Checkpoint(#[from] CheckpointError),
+}
+
+/// Name and version identifying a definition.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DefinitionRef {
+    /// Definition name
+    pub name: String,
+    /// Definition version
+    pub version: u32,
+}
+
+impl fmt::Display for DefinitionRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+/// Serializable machine definition.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "S: State")]
+pub struct Definition<S: State> {
+    /// Definition name
+    pub name: String,
+    /// Definition version
+    pub version: u32,
+    /// Free-form tags, e.g. owning team or domain
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// State new machines start in
+    pub initial: S,
+    /// Transitions, with actions referenced by name
+    pub transitions: Vec<TransitionSpec<S>>,
+}
+
+impl<S: State> Definition<S> {
+    /// Create an untagged definition
+    pub fn new(name: impl Into<String>, version: u32, initial: S) -> Self {
+        Self {
+            name: name.into(),
+            version,
+            tags: BTreeSet::new(),
+            initial,
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Add a tag
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    /// Add a transition
+    pub fn transition(mut self, spec: TransitionSpec<S>) -> Self {
+        self.transitions.push(spec);
+        self
+    }
+
+    /// Name and version of the definition
+    pub fn reference(&self) -> DefinitionRef {
+        DefinitionRef {
+            name: self.name.clone(),
+            version: self.version,
+        }
+    }
+}
+
+/// Named, versioned definitions of one state type.
+pub struct DefinitionCatalog<S: State> {
+    definitions: BTreeMap<DefinitionRef, Definition<S>>,
+}
+
+impl<S: State> Default for DefinitionCatalog<S> {
+    fn default() -> Self {
+        Self {
+            definitions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S: State + 'static> DefinitionCatalog<S> {
+    /// Create an empty catalog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a definition. Registered versions are immutable, so
+    /// registering the same `name@version` twice fails.
+    pub fn register(&mut self, definition: Definition<S>) -> Result<&mut Self, CatalogError> {
+        let reference = definition.reference();
+        if self.definitions.contains_key(&reference) {
+            return Err(CatalogError::AlreadyRegistered(reference));
+        }
+        self.definitions.insert(reference, definition);
+        Ok(self)
+    }
+
+    /// Look up `name@version`, or the latest version for a bare `name`
+    pub fn get(&self, reference: &str) -> Result<&Definition<S>, CatalogError> {
+        let found = match reference.split_once('@') {
+            None => self
+                .definitions
+                .range(Self::versions_of(reference))
+                .next_back()
+                .map(|(_, definition)| definition),
+            Some((name, version)) => {
+                let version = version
+                    .parse()
+                    .map_err(|_| CatalogError::InvalidReference {
+                        reference: reference.to_string(),
+                    })?;
+                self.definitions.get(&DefinitionRef {
+                    name: name.to_string(),
+                    version,
+                })
+            }
+        };
+        found.ok_or_else(|| CatalogError::NotFound {
+            reference: reference.to_string(),
+        })
+    }
+
+    /// All definitions, ordered by name then version
+    pub fn definitions(&self) -> impl Iterator<Item = &Definition<S>> {
+        self.definitions.values()
+    }
+
+    /// Definitions carrying `tag`, ordered by name then version
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Definition<S>> + 'a {
+        self.definitions().filter(move |d| d.tags.contains(tag))
+    }
+
+    /// Create a machine from the definition `reference` refers to,
+    /// resolving its actions against `actions`.
+    ///
+    /// The machine is labelled with [`DEFINITION_LABEL`] so its definition
+    /// can be found again when it is resumed.
+    pub fn instantiate<Env: Clone + Send + Sync + 'static>(
+        &self,
+        reference: &str,
+        actions: &ActionRegistry<S, Env>,
+    ) -> Result<StateMachine<S, Env>, CatalogError> {
+        let definition = self.get(reference)?;
+        let mut machine = StateMachine::new(definition.initial.clone());
+        for transition in actions.resolve(&definition.transitions)? {
+            machine.add_transition(transition);
+        }
+        machine.set_label(DEFINITION_LABEL, definition.reference().to_string());
+        Ok(machine)
+    }
+
+    /// Restore a machine from a checkpoint, resolving the actions of the
+    /// exact definition version the machine was instantiated from.
+    pub fn resume<Env: Clone + Send + Sync + 'static>(
+        &self,
+        checkpoint: Checkpoint<S>,
+        actions: &ActionRegistry<S, Env>,
+    ) -> Result<StateMachine<S, Env>, CatalogError> {
+        let reference = checkpoint
+            .metadata
+            .labels
+            .get(DEFINITION_LABEL)
+            .ok_or(CatalogError::UnknownDefinition)?;
+        let definition = self.get(reference)?;
+        let transitions = actions.resolve(&definition.transitions)?;
+        Ok(StateMachine::from_checkpoint(checkpoint, transitions)?)
+    }
+
+    fn versions_of(name: &str) -> std::ops::RangeInclusive<DefinitionRef> {
+        let at = |version| DefinitionRef {
+            name: name.to_string(),
+            version,
+        };
+        at(0)..=at(u32::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StepResult, TransitionResult};
+    use stillwater::prelude::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Onboarding {
+        Invited,
+        Verified,
+        Active,
+    }
+
+    impl State for Onboarding {
+        fn name(&self) -> &str {
+            match self {
+                Self::Invited => "Invited",
+                Self::Verified => "Verified",
+                Self::Active => "Active",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Active)
+        }
+    }
+
+    fn catalog() -> DefinitionCatalog<Onboarding> {
+        let mut catalog = DefinitionCatalog::new();
+        catalog
+            .register(
+                Definition::new("onboarding", 1, Onboarding::Invited)
+                    .tag("accounts")
+                    .transition(TransitionSpec::new(
+                        Onboarding::Invited,
+                        Onboarding::Active,
+                        "activate",
+                    )),
+            )
+            .unwrap()
+            .register(
+                Definition::new("onboarding", 2, Onboarding::Invited)
+                    .transition(TransitionSpec::new(
+                        Onboarding::Invited,
+                        Onboarding::Verified,
+                        "verify",
+                    ))
+                    .transition(TransitionSpec::new(
+                        Onboarding::Verified,
+                        Onboarding::Active,
+                        "activate",
+                    )),
+            )
+            .unwrap();
+        catalog
+    }
+
+    #[test]
+    fn lookup_by_version_or_latest() {
+        let catalog = catalog();
+
+        assert_eq!(catalog.get("onboarding@1").unwrap().transitions.len(), 1);
+        assert_eq!(catalog.get("onboarding").unwrap().version, 2);
+        assert!(matches!(
+            catalog.get("onboarding@3"),
+            Err(CatalogError::NotFound { ref reference }) if reference == "onboarding@3"
+        ));
+        assert!(matches!(
+            catalog.get("onboarding@latest"),
+            Err(CatalogError::InvalidReference { .. })
+        ));
+        assert_eq!(
+            catalog
+                .tagged("accounts")
+                .map(Definition::reference)
+                .collect::<Vec<_>>(),
+            vec![DefinitionRef {
+                name: "onboarding".to_string(),
+                version: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn versions_are_immutable() {
+        let mut catalog = catalog();
+        let result = catalog.register(Definition::new("onboarding", 2, Onboarding::Invited));
+
+        assert!(matches!(result, Err(CatalogError::AlreadyRegistered(ref r)) if r.version == 2));
+    }
+
+    #[tokio::test]
+    async fn instantiated_machines_run_registered_actions() {
+        let mut actions = ActionRegistry::<Onboarding, ()>::new();
+        actions.register("activate", || {
+            pure(TransitionResult::Success(Onboarding::Active)).boxed()
+        });
+
+        let machine = catalog().instantiate("onboarding@1", &actions).unwrap();
+        assert_eq!(machine.labels()[DEFINITION_LABEL], "onboarding@1");
+        let (_, result, _) = machine.step().run(&()).await.unwrap();
+        assert_eq!(result, StepResult::Transitioned(Onboarding::Active));
+
+        assert!(matches!(
+            catalog().instantiate("onboarding", &actions),
+            Err(CatalogError::Definition(
+                DefinitionError::UnknownActions { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn running_instances_stay_on_their_version() {
+        let mut actions = ActionRegistry::<Onboarding, ()>::new();
+        actions
+            .register("activate", || {
+                pure(TransitionResult::Success(Onboarding::Active)).boxed()
+            })
+            .register("verify", || {
+                pure(TransitionResult::Success(Onboarding::Verified)).boxed()
+            });
+        let catalog = catalog();
+
+        let blue = catalog.instantiate("onboarding@1", &actions).unwrap();
+        let green = catalog.instantiate("onboarding", &actions).unwrap();
+        assert_eq!(green.labels()[DEFINITION_LABEL], "onboarding@2");
+
+        let resumed = catalog.resume(blue.checkpoint(), &actions).unwrap();
+        assert_eq!(resumed.transitions().len(), 1);
+        assert!(matches!(
+            catalog.resume(
+                StateMachine::<_, ()>::new(Onboarding::Invited).checkpoint(),
+                &actions
+            ),
+            Err(CatalogError::UnknownDefinition)
+        ));
+    }
+}
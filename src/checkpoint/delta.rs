@@ -0,0 +1,205 @@
+//! Incremental checkpoints.
+//!
+//! Saving a full checkpoint after every step rewrites the whole history
+//! each time, which gets expensive for chatty machines. A
+//! [`CheckpointDelta`] holds only what changed since an earlier checkpoint:
+//! the new transitions, events and data snapshots, the current state and
+//! the metadata.
+//! Storage can persist deltas and write a full snapshot every so often;
+//! [`restore`] rebuilds the latest checkpoint from a snapshot and the
+//! deltas written after it.
+
+use super::{Checkpoint, CheckpointError, MachineMetadata};
+use crate::core::{DataSnapshot, HistoryEvent, State, StateHistory, StateTransition};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Changes between two checkpoints of the same machine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CheckpointDelta<S: State> {
+    /// Id of the checkpoint the delta applies to
+    pub base: String,
+
+    /// Id of the checkpoint the delta produces
+    pub id: String,
+
+    /// When the produced checkpoint was created
+    pub timestamp: DateTime<Utc>,
+
+    /// Current state of the machine
+    pub current_state: S,
+
+    /// Transitions recorded after the base checkpoint
+    pub transitions: Vec<StateTransition<S>>,
+
+    /// History events recorded after the base checkpoint
+    pub events: Vec<HistoryEvent>,
+
+    /// Data snapshots recorded after the base checkpoint
+    #[serde(default)]
+    pub snapshots: Vec<DataSnapshot>,
+
+    /// Machine metadata, in full since it is small and changes every step
+    pub metadata: MachineMetadata,
+}
+
+impl<S: State> CheckpointDelta<S> {
+    /// Whether the delta adds no transitions, events or snapshots
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty() && self.events.is_empty() && self.snapshots.is_empty()
+    }
+}
+
+impl<S: State> Checkpoint<S> {
+    /// Changes from `base`, an earlier checkpoint of the same machine (pure).
+    ///
+    /// Fails with [`CheckpointError::ValidationFailed`] if this checkpoint's
+    /// history does not extend the history of `base`.
+    pub fn delta_since(&self, base: &Checkpoint<S>) -> Result<CheckpointDelta<S>, CheckpointError> {
+        let (old, new) = (base.history.transitions(), self.history.transitions());
+        let extends = old.len() <= new.len()
+            && base.history.events().len() <= self.history.events().len()
+            && base.initial_state == self.initial_state
+            && old.last().is_none_or(|last| {
+                let same = &new[old.len() - 1];
+                same.sequence == last.sequence && same.timestamp == last.timestamp
+            });
+        if !extends {
+            return Err(CheckpointError::ValidationFailed(format!(
+                "checkpoint {} does not extend checkpoint {}",
+                self.id, base.id
+            )));
+        }
+
+        Ok(CheckpointDelta {
+            base: base.id.clone(),
+            id: self.id.clone(),
+            timestamp: self.timestamp,
+            current_state: self.current_state.clone(),
+            transitions: new[old.len()..].to_vec(),
+            events: self.history.events()[base.history.events().len()..].to_vec(),
+            snapshots: self
+                .history
+                .snapshots()
+                .iter()
+                .filter(|s| !base.history.snapshots().contains(s))
+                .cloned()
+                .collect(),
+            metadata: self.metadata.clone(),
+        })
+    }
+
+    /// The checkpoint `delta` produces from this one (pure).
+    ///
+    /// Fails with [`CheckpointError::ValidationFailed`] if the delta was
+    /// computed against a different checkpoint.
+    pub fn apply_delta(&self, delta: CheckpointDelta<S>) -> Result<Checkpoint<S>, CheckpointError> {
+        if delta.base != self.id {
+            return Err(CheckpointError::ValidationFailed(format!(
+                "delta {} applies to checkpoint {}, not {}",
+                delta.id, delta.base, self.id
+            )));
+        }
+
+        let mut transitions = self.history.transitions().to_vec();
+        transitions.extend(delta.transitions);
+        let mut events = self.history.events().to_vec();
+        events.extend(delta.events);
+        let mut history = StateHistory::from_parts(transitions, events)
+            .with_snapshots(self.history.snapshots().to_vec());
+        for snapshot in delta.snapshots {
+            history = history.record_snapshot(snapshot);
+        }
+        Ok(Checkpoint {
+            id: delta.id,
+            timestamp: delta.timestamp,
+            current_state: delta.current_state,
+            history,
+            metadata: delta.metadata,
+            ..self.clone()
+        })
+    }
+}
+
+/// Rebuild the latest checkpoint from a full `snapshot` and the deltas
+/// written after it, oldest first (pure).
+pub fn restore<S: State>(
+    snapshot: Checkpoint<S>,
+    deltas: impl IntoIterator<Item = CheckpointDelta<S>>,
+) -> Result<Checkpoint<S>, CheckpointError> {
+    deltas
+        .into_iter()
+        .try_fold(snapshot, |checkpoint, delta| checkpoint.apply_delta(delta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Counter {
+        Even,
+        Odd,
+    }
+
+    impl State for Counter {
+        fn name(&self) -> &str {
+            match self {
+                Self::Even => "Even",
+                Self::Odd => "Odd",
+            }
+        }
+    }
+
+    fn tick(machine: &mut StateMachine<Counter, ()>) {
+        let from = machine.current_state().clone();
+        let to = match from {
+            Counter::Even => Counter::Odd,
+            Counter::Odd => Counter::Even,
+        };
+        machine.apply_result(from, StepResult::Transitioned(to), 1);
+    }
+
+    #[test]
+    fn snapshot_and_deltas_restore_the_latest_checkpoint() {
+        let mut machine = StateMachine::<Counter, ()>::new(Counter::Even);
+        tick(&mut machine);
+        let snapshot = machine.checkpoint();
+
+        let mut previous = snapshot.clone();
+        let mut deltas = Vec::new();
+        for _ in 0..3 {
+            tick(&mut machine);
+            let checkpoint = machine.checkpoint();
+            let delta = checkpoint.delta_since(&previous).unwrap();
+            assert_eq!(delta.transitions.len(), 1);
+            deltas.push(delta);
+            previous = checkpoint;
+        }
+
+        let restored = restore(snapshot.clone(), deltas).unwrap();
+        assert_eq!(restored.id, previous.id);
+        assert_eq!(restored.current_state, Counter::Even);
+        assert_eq!(restored.history.transitions().len(), 4);
+        assert_eq!(restored.metadata.stats.transitions, 4);
+        assert_eq!(restored.history.transitions()[3].sequence, 4);
+
+        let stale = previous.delta_since(&snapshot).unwrap();
+        assert!(matches!(
+            restored.apply_delta(stale),
+            Err(CheckpointError::ValidationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn unrelated_checkpoints_have_no_delta() {
+        let mut machine = StateMachine::<Counter, ()>::new(Counter::Even);
+        tick(&mut machine);
+        let other = StateMachine::<Counter, ()>::new(Counter::Odd).checkpoint();
+
+        assert!(machine.checkpoint().delta_since(&other).is_err());
+        assert!(other.delta_since(&machine.checkpoint()).is_err());
+    }
+}
@@ -17,6 +17,16 @@ pub enum CheckpointError {
     #[error("Unsupported checkpoint version {found}, supported: {supported}")]
     UnsupportedVersion { found: u32, supported: u32 },
 
+    /// State type no longer matches the schema recorded in the checkpoint
+    #[error(
+        "State schema mismatch for '{type_name}': checkpoint uses {missing:?}, which were removed or changed (new: {added:?})"
+    )]
+    StateSchemaMismatch {
+        type_name: String,
+        missing: Vec<String>,
+        added: Vec<String>,
+    },
+
     /// Checkpoint data failed validation
     #[error("Checkpoint validation failed: {0}")]
     ValidationFailed(String),
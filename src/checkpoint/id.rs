@@ -0,0 +1,120 @@
+//! Machine, checkpoint and trace identifiers.
+//!
+//! Machine ids, checkpoint ids and the trace ids of OTLP exports are
+//! random UUIDs by default. Stores that list machines or checkpoints by id
+//! benefit from ids that sort by creation time, and tests from ids that are
+//! the same on every run. An [`IdGenerator`] set on a machine with
+//! `set_id_generator`, or on a builder with `id_generator`, provides them.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Source of machine, checkpoint and trace ids.
+pub trait IdGenerator: Debug + Send + Sync {
+    /// A new, unique id
+    fn generate(&self) -> String;
+}
+
+/// Random UUIDv4 ids, the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidIds;
+
+impl IdGenerator for UuidIds {
+    fn generate(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// ULIDs: a millisecond timestamp and 80 random bits in Crockford base32,
+/// so ids sort by creation time (to the millisecond).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UlidIds;
+
+impl IdGenerator for UlidIds {
+    fn generate(&self) -> String {
+        const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let value = ((millis & ((1 << 48) - 1)) << 80) | random_bits();
+        (0..26)
+            .rev()
+            .map(|digit| ALPHABET[((value >> (digit * 5)) & 0x1f) as usize] as char)
+            .collect()
+    }
+}
+
+/// 80 random bits, taken from the 122 random bits of a UUIDv4 around its
+/// version and variant bits
+fn random_bits() -> u128 {
+    let uuid = u128::from_be_bytes(*Uuid::new_v4().as_bytes());
+    let high = uuid >> 80;
+    let middle = (uuid >> 64) & 0xfff;
+    let low = uuid & 0xf_ffff;
+    (high << 32) | (middle << 20) | low
+}
+
+/// Deterministic ids `<prefix>-1`, `<prefix>-2`, ... for tests.
+#[derive(Debug)]
+pub struct SequentialIds {
+    prefix: String,
+    next: AtomicU64,
+}
+
+impl SequentialIds {
+    /// Count from 1, prefixing ids with `prefix`
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn generate(&self) -> String {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{n}", self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ulids_sort_by_creation_time() {
+        let first = UlidIds.generate();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = UlidIds.generate();
+
+        assert_eq!(first.len(), 26);
+        assert!(first < second);
+    }
+
+    #[test]
+    fn ulid_random_parts_use_only_random_bits() {
+        let (mut ones, mut zeros) = (0u128, 0u128);
+        for _ in 0..64 {
+            let bits = random_bits();
+            ones |= bits;
+            zeros |= !bits;
+        }
+        let all = (1u128 << 80) - 1;
+
+        assert_eq!(ones, all);
+        assert_eq!(zeros & all, all);
+    }
+
+    #[test]
+    fn sequential_ids_are_reproducible() {
+        let ids = SequentialIds::new("cp");
+
+        assert_eq!(
+            [ids.generate(), ids.generate()],
+            ["cp-1".to_string(), "cp-2".to_string()]
+        );
+    }
+}
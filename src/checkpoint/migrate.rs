@@ -0,0 +1,172 @@
+//! Evolving data-carrying states across checkpoint versions.
+//!
+//! When a state enum changes shape (a variant is renamed, a variant gains
+//! a field, a payload is restructured), old JSON checkpoints no longer
+//! deserialize. Rather than writing a custom `Deserialize` impl, describe
+//! the change as a [`StateMigration`] and load with
+//! `StateMachine::from_json_migrated`: every serialized state in the
+//! checkpoint (initial, current and history) is rewritten before typed
+//! deserialization.
+//!
+//! Migrations work on serde's default, externally tagged enum
+//! representation: unit variants serialize as `"Name"`, data variants as
+//! `{"Name": payload}`. Binary checkpoints are not self-describing and
+//! cannot be migrated this way.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+type PayloadTransform = Arc<dyn Fn(Value) -> Value + Send + Sync>;
+
+/// Rewrites of serialized states applied when loading a JSON checkpoint.
+///
+/// For each state, renames apply first, then field defaults, then the
+/// variant's transform, all keyed by the (new) variant name.
+#[derive(Clone, Default)]
+pub struct StateMigration {
+    renames: BTreeMap<String, String>,
+    defaults: BTreeMap<String, Map<String, Value>>,
+    transforms: BTreeMap<String, PayloadTransform>,
+}
+
+impl fmt::Debug for StateMigration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateMigration")
+            .field("renames", &self.renames)
+            .field("defaults", &self.defaults)
+            .field("transforms", &self.transforms.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl StateMigration {
+    /// A migration that changes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Variant `old` is now called `new`
+    pub fn rename_variant(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.renames.insert(old.into(), new.into());
+        self
+    }
+
+    /// Fill in `field` with `value` where a `variant` payload lacks it
+    pub fn default_field(
+        mut self,
+        variant: impl Into<String>,
+        field: impl Into<String>,
+        value: Value,
+    ) -> Self {
+        self.defaults
+            .entry(variant.into())
+            .or_default()
+            .insert(field.into(), value);
+        self
+    }
+
+    /// Rewrite the payload of `variant` with `f`.
+    ///
+    /// Unit variants have the payload `null`; returning anything else turns
+    /// them into data variants.
+    pub fn transform<F>(mut self, variant: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        self.transforms.insert(variant.into(), Arc::new(f));
+        self
+    }
+
+    /// Migrate one serialized state (pure)
+    pub fn migrate_state(&self, state: Value) -> Value {
+        let (variant, payload) = match state {
+            Value::String(variant) => (variant, Value::Null),
+            Value::Object(map) if map.len() == 1 => {
+                let (variant, payload) = map.into_iter().next().expect("one entry");
+                (variant, payload)
+            }
+            other => return other,
+        };
+        let variant = self.renames.get(&variant).cloned().unwrap_or(variant);
+
+        let mut payload = payload;
+        if let (Some(defaults), Value::Object(fields)) = (self.defaults.get(&variant), &mut payload)
+        {
+            for (field, value) in defaults {
+                fields.entry(field.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        if let Some(transform) = self.transforms.get(&variant) {
+            payload = transform(payload);
+        }
+
+        match payload {
+            Value::Null => Value::String(variant),
+            payload => Value::Object(Map::from_iter([(variant, payload)])),
+        }
+    }
+
+    /// Migrate every state of a JSON checkpoint, and the variant names of
+    /// its recorded state schema, fields included (pure)
+    pub fn migrate_checkpoint(&self, mut checkpoint: Value) -> Value {
+        for key in ["initial_state", "current_state"] {
+            if let Some(state) = checkpoint.get_mut(key) {
+                *state = self.migrate_state(state.take());
+            }
+        }
+        if let Some(Value::Array(transitions)) = checkpoint.pointer_mut("/history/transitions") {
+            for transition in transitions {
+                for key in ["from", "to"] {
+                    if let Some(state) = transition.get_mut(key) {
+                        *state = self.migrate_state(state.take());
+                    }
+                }
+            }
+        }
+        if let Some(Value::Array(members)) = checkpoint.pointer_mut("/state_schema/members") {
+            for member in members {
+                if let Some(new) = member.as_str().and_then(|old| self.renames.get(old)) {
+                    *member = Value::String(new.clone());
+                }
+            }
+        }
+        if let Some(Value::Object(fields)) = checkpoint.pointer_mut("/state_schema/fields") {
+            // Transformed payloads no longer have the recorded fields
+            *fields = std::mem::take(fields)
+                .into_iter()
+                .map(|(variant, names)| match self.renames.get(&variant) {
+                    Some(new) => (new.clone(), names),
+                    None => (variant, names),
+                })
+                .filter(|(variant, _)| !self.transforms.contains_key(variant))
+                .collect();
+        }
+        checkpoint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renames_defaults_and_transforms_apply_in_order() {
+        let migration = StateMigration::new()
+            .rename_variant("Submitted", "InReview")
+            .default_field("InReview", "reviewer", json!(null))
+            .transform("Closed", |_| json!({ "resolution": "done" }));
+
+        assert_eq!(
+            migration.migrate_state(json!({ "Submitted": { "id": 7 } })),
+            json!({ "InReview": { "id": 7, "reviewer": null } })
+        );
+        assert_eq!(
+            migration.migrate_state(json!("Closed")),
+            json!({ "Closed": { "resolution": "done" } })
+        );
+        assert_eq!(migration.migrate_state(json!("Draft")), json!("Draft"));
+    }
+}
@@ -3,17 +3,41 @@
 //! This module provides serialization and deserialization capabilities for state machines,
 //! enabling long-running workflows to survive process restarts and infrastructure failures.
 
-use crate::core::{State, StateHistory};
+use crate::core::{Scrubber, State, StateHistory};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+pub mod delta;
 pub mod error;
+pub mod id;
+pub mod migrate;
+pub mod redact;
+pub mod schema;
+mod v1;
 
+pub use delta::{restore, CheckpointDelta};
 pub use error::CheckpointError;
+pub use id::{IdGenerator, SequentialIds, UlidIds, UuidIds};
+pub use migrate::StateMigration;
+pub use redact::StateRedaction;
+pub use schema::{SchemaKind, StateSchema};
+pub(crate) use v1::CheckpointV1;
 
 /// Version identifier for checkpoint format
-pub const CHECKPOINT_VERSION: u32 = 1;
+pub const CHECKPOINT_VERSION: u32 = 2;
+
+/// Encoding options for binary checkpoints.
+///
+/// The same fixed-width encoding `bincode::serialize` uses, so the leading
+/// `version` of every binary checkpoint is a little-endian `u32`.
+pub(crate) fn binary_options() -> impl bincode::Options + Copy {
+    use bincode::Options;
+
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
 
 /// Metadata tracked by state machine
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -27,8 +51,127 @@ pub struct MachineMetadata {
     /// Current attempt count for active transition
     pub current_attempt: usize,
 
+    /// When attempts at the current transition started, i.e. when the
+    /// machine entered its current state
+    #[serde(default)]
+    pub current_transition_started_at: Option<DateTime<Utc>>,
+
     /// Total attempts per transition (transition name -> count)
     pub total_attempts: HashMap<String, usize>,
+
+    /// Set when the machine was positioned in a state without history
+    #[serde(default)]
+    pub synthetic_start: Option<SyntheticStart>,
+
+    /// Set while the machine is paused
+    #[serde(default)]
+    pub paused: Option<PauseInfo>,
+
+    /// Id identifying the machine across checkpoints; `None` only in
+    /// checkpoints written before machines had ids
+    #[serde(default)]
+    pub machine_id: Option<String>,
+
+    /// Tenant or namespace the machine belongs to
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Free-form labels for filtering and grouping machines
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+
+    /// External signals delivered but not yet consumed, oldest first
+    #[serde(default)]
+    pub pending_signals: Vec<Signal>,
+
+    /// Feedback of the last retry of the current transition
+    #[serde(default)]
+    pub retry_feedback: Option<String>,
+
+    /// Run statistics maintained as the machine executes
+    #[serde(default)]
+    pub stats: MachineStats,
+
+    /// Times the machine was resumed from a checkpoint
+    #[serde(default)]
+    pub resume_count: usize,
+
+    /// When the whole run expires unless it reached a final state
+    #[serde(default)]
+    pub deadline: Option<DateTime<Utc>>,
+
+    /// Timers scheduled with an external timer service, not fired yet
+    #[serde(default)]
+    pub timers: Vec<PendingTimer>,
+
+    /// When the running process resumed the machine from a checkpoint.
+    /// Not persisted, so `None` for machines created fresh in this process
+    #[serde(skip)]
+    pub resumed_at: Option<DateTime<Utc>>,
+}
+
+/// Run statistics kept in checkpoint metadata, so a checkpoint shows how a
+/// run went without replaying its history.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineStats {
+    /// Transitions recorded, forced ones included
+    pub transitions: usize,
+
+    /// Steps whose action asked for a retry
+    pub retries: usize,
+
+    /// Steps whose action aborted into an error state
+    pub aborts: usize,
+
+    /// Times each state was entered by a transition or an abort (state name
+    /// -> count). The initial state is not counted.
+    pub state_entries: BTreeMap<String, usize>,
+}
+
+/// Why and since when a machine is paused.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PauseInfo {
+    /// Reason given when pausing
+    pub reason: String,
+
+    /// When the machine was paused
+    pub since: DateTime<Utc>,
+}
+
+/// External signal delivered to a machine.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    /// Signal name, e.g. `approval`
+    pub name: String,
+
+    /// Payload sent with the signal
+    pub payload: serde_json::Value,
+
+    /// When the signal was delivered
+    pub received_at: DateTime<Utc>,
+}
+
+/// Timer scheduled with an external timer service.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingTimer {
+    /// Timer name, delivered as a signal of the same name when it fires
+    pub name: String,
+
+    /// Handle the timer service returned when scheduling
+    pub handle: String,
+
+    /// When the timer is due
+    pub due_at: DateTime<Utc>,
+}
+
+/// Record of a machine started directly in a state via `resume_at`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyntheticStart {
+    /// Name of the state the machine was positioned in
+    pub state: String,
+
+    /// When the synthetic start happened
+    pub timestamp: DateTime<Utc>,
 }
 
 impl Default for MachineMetadata {
@@ -38,7 +181,20 @@ impl Default for MachineMetadata {
             created_at: now,
             updated_at: now,
             current_attempt: 0,
+            current_transition_started_at: Some(now),
             total_attempts: HashMap::new(),
+            synthetic_start: None,
+            paused: None,
+            machine_id: Some(UuidIds.generate()),
+            namespace: None,
+            labels: BTreeMap::new(),
+            pending_signals: Vec::new(),
+            retry_feedback: None,
+            stats: MachineStats::default(),
+            resume_count: 0,
+            deadline: None,
+            timers: Vec::new(),
+            resumed_at: None,
         }
     }
 }
@@ -51,6 +207,12 @@ pub struct Checkpoint<S: State> {
     /// Checkpoint format version
     pub version: u32,
 
+    /// Schema fingerprint of the state type, verified on load.
+    /// Kept directly after `version` so binary readers can check it
+    /// before decoding any states.
+    #[serde(default)]
+    pub state_schema: Option<StateSchema>,
+
     /// Unique checkpoint identifier
     pub id: String,
 
@@ -69,3 +231,45 @@ pub struct Checkpoint<S: State> {
     /// Machine metadata
     pub metadata: MachineMetadata,
 }
+
+/// Redact sensitive data from the history and metadata of an existing
+/// checkpoint.
+///
+/// The initial and current states are kept so the checkpoint can still be
+/// resumed.
+pub fn scrub_existing<S: State>(
+    checkpoint: Checkpoint<S>,
+    scrubber: &Scrubber<S>,
+) -> Checkpoint<S> {
+    Checkpoint {
+        history: scrubber.scrub_history(&checkpoint.history),
+        metadata: scrub_metadata(checkpoint.metadata, scrubber),
+        ..checkpoint
+    }
+}
+
+/// Redact the free-text fields of checkpoint metadata: the pause reason,
+/// retry feedback and pending signal payloads (pure)
+pub(crate) fn scrub_metadata<S: State>(
+    metadata: MachineMetadata,
+    scrubber: &Scrubber<S>,
+) -> MachineMetadata {
+    MachineMetadata {
+        paused: metadata.paused.map(|pause| PauseInfo {
+            reason: scrubber.scrub_text(&pause.reason),
+            ..pause
+        }),
+        retry_feedback: metadata
+            .retry_feedback
+            .map(|feedback| scrubber.scrub_text(&feedback)),
+        pending_signals: metadata
+            .pending_signals
+            .into_iter()
+            .map(|signal| Signal {
+                payload: scrubber.scrub_json(&signal.payload),
+                ..signal
+            })
+            .collect(),
+        ..metadata
+    }
+}
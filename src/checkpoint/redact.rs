@@ -0,0 +1,181 @@
+//! Redaction of state data that must not be persisted.
+//!
+//! Extended state sometimes holds values that must never reach a
+//! checkpoint, such as temporary credentials. A [`StateRedaction`] maps
+//! every state written into a checkpoint (initial, current and history) to
+//! a persistable form, for example by clearing a field or replacing it with
+//! a token. At resume time its restore hook rebuilds the live initial and
+//! current states, e.g. by fetching fresh credentials for the token.
+
+use super::Checkpoint;
+use crate::core::{Scrubber, State};
+use std::sync::Arc;
+
+type PersistFn<S> = Arc<dyn Fn(&S) -> S + Send + Sync>;
+type RestoreFn<S> = Arc<dyn Fn(S) -> S + Send + Sync>;
+
+/// Persist and restore hooks for checkpointed states.
+///
+/// Both hooks default to the identity.
+pub struct StateRedaction<S: State> {
+    persist: Option<PersistFn<S>>,
+    restore: Option<RestoreFn<S>>,
+}
+
+impl<S: State> Clone for StateRedaction<S> {
+    fn clone(&self) -> Self {
+        Self {
+            persist: self.persist.clone(),
+            restore: self.restore.clone(),
+        }
+    }
+}
+
+impl<S: State> Default for StateRedaction<S> {
+    fn default() -> Self {
+        Self {
+            persist: None,
+            restore: None,
+        }
+    }
+}
+
+impl<S: State + 'static> StateRedaction<S> {
+    /// Create a redaction that changes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map states to the form written into checkpoints
+    pub fn persist<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&S) -> S + Send + Sync + 'static,
+    {
+        self.persist = Some(Arc::new(f));
+        self
+    }
+
+    /// Rebuild the initial and current states of a loaded checkpoint
+    pub fn restore<F>(mut self, f: F) -> Self
+    where
+        F: Fn(S) -> S + Send + Sync + 'static,
+    {
+        self.restore = Some(Arc::new(f));
+        self
+    }
+
+    /// Apply the persist hook to every state of a checkpoint (pure)
+    pub fn redact_checkpoint(&self, checkpoint: Checkpoint<S>) -> Checkpoint<S> {
+        let Some(persist) = &self.persist else {
+            return checkpoint;
+        };
+        let scrubber = Scrubber::new().states({
+            let persist = Arc::clone(persist);
+            move |state: &S| persist(state)
+        });
+        Checkpoint {
+            initial_state: persist(&checkpoint.initial_state),
+            current_state: persist(&checkpoint.current_state),
+            history: scrubber.scrub_history(&checkpoint.history),
+            ..checkpoint
+        }
+    }
+
+    /// Apply the restore hook to the initial and current states (pure).
+    ///
+    /// History keeps its persisted form.
+    pub fn restore_checkpoint(&self, checkpoint: Checkpoint<S>) -> Checkpoint<S> {
+        let Some(restore) = &self.restore else {
+            return checkpoint;
+        };
+        Checkpoint {
+            initial_state: restore(checkpoint.initial_state),
+            current_state: restore(checkpoint.current_state),
+            ..checkpoint
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Import {
+        Connecting,
+        Syncing { token: Option<String> },
+        Done,
+    }
+
+    impl State for Import {
+        fn name(&self) -> &str {
+            match self {
+                Self::Connecting => "Connecting",
+                Self::Syncing { .. } => "Syncing",
+                Self::Done => "Done",
+            }
+        }
+    }
+
+    fn redaction() -> StateRedaction<Import> {
+        StateRedaction::new()
+            .persist(|state: &Import| match state {
+                Import::Syncing { .. } => Import::Syncing { token: None },
+                other => other.clone(),
+            })
+            .restore(|state| match state {
+                Import::Syncing { token: None } => Import::Syncing {
+                    token: Some("fresh".to_string()),
+                },
+                other => other,
+            })
+    }
+
+    fn syncing(token: &str) -> Import {
+        Import::Syncing {
+            token: Some(token.to_string()),
+        }
+    }
+
+    #[test]
+    fn secrets_never_reach_the_checkpoint() {
+        let mut machine = StateMachine::<Import, ()>::new(Import::Connecting);
+        machine.set_state_redaction(redaction());
+        machine.apply_result(
+            Import::Connecting,
+            StepResult::Transitioned(syncing("secret")),
+            1,
+        );
+
+        let json = machine.to_json().unwrap();
+        assert!(!json.contains("secret"));
+        assert_eq!(machine.current_state(), &syncing("secret"));
+    }
+
+    #[test]
+    fn restore_hook_rebuilds_current_state_on_resume() {
+        let mut machine = StateMachine::<Import, ()>::new(Import::Connecting);
+        machine.set_state_redaction(redaction());
+        machine.apply_result(
+            Import::Connecting,
+            StepResult::Transitioned(syncing("secret")),
+            1,
+        );
+
+        let restored = StateMachine::<Import, ()>::from_binary_with_redaction(
+            &machine.to_binary().unwrap(),
+            vec![],
+            redaction(),
+        )
+        .unwrap();
+
+        assert_eq!(restored.current_state(), &syncing("fresh"));
+        assert_eq!(
+            restored.history().transitions()[0].to,
+            Import::Syncing { token: None }
+        );
+        assert!(!restored.to_json().unwrap().contains("fresh"));
+    }
+}
@@ -0,0 +1,687 @@
+//! Schema fingerprints of state types.
+//!
+//! Checkpoints record the variant names, and the field names of struct
+//! variants, of the state type they were written with. On load the recorded
+//! schema is compared with the current type for the variants the
+//! checkpoint's states actually use, so a removed or renamed variant
+//! produces a precise `StateSchemaMismatch` instead of an opaque serde
+//! error, while changes to variants the checkpoint never used are ignored.
+
+use crate::checkpoint::CheckpointError;
+use crate::core::State;
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Whether a schema describes an enum or a struct.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SchemaKind {
+    /// Members are variant names
+    #[default]
+    Enum,
+    /// Members are field names
+    Struct,
+}
+
+/// Fingerprint of a state type's serde schema.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSchema {
+    /// Serde name of the state type
+    pub type_name: String,
+    /// Whether the type is an enum or a struct
+    #[serde(default)]
+    pub kind: SchemaKind,
+    /// Variant names for enums, field names for structs
+    pub members: Vec<String>,
+    /// Field names of the struct variants of an enum, by variant name
+    #[serde(default)]
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+impl StateSchema {
+    /// Capture the schema of a state type.
+    ///
+    /// Returns `None` for types whose schema cannot be discovered without
+    /// data, such as internally tagged or untagged enums.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use mindset::checkpoint::StateSchema;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    /// enum Phase {
+    ///     Start,
+    ///     Running { progress: u8 },
+    ///     Done,
+    /// }
+    /// # impl mindset::core::State for Phase {
+    /// #     fn name(&self) -> &str { "Phase" }
+    /// # }
+    ///
+    /// let schema = StateSchema::of::<Phase>().unwrap();
+    /// assert_eq!(schema.type_name, "Phase");
+    /// assert_eq!(schema.members, vec!["Start", "Running", "Done"]);
+    /// assert_eq!(schema.fields["Running"], vec!["progress"]);
+    /// ```
+    pub fn of<S: State>() -> Option<Self> {
+        let mut captured = None;
+        let _ = S::deserialize(SchemaTracer {
+            captured: &mut captured,
+        });
+        let mut schema = captured?;
+
+        if schema.kind == SchemaKind::Enum {
+            for (index, variant) in schema.members.iter().enumerate() {
+                let mut fields = None;
+                let _ = S::deserialize(FieldTracer {
+                    index: index as u32,
+                    fields: &mut fields,
+                });
+                if let Some(fields) = fields {
+                    schema.fields.insert(variant.clone(), fields);
+                }
+            }
+        }
+        Some(schema)
+    }
+
+    /// Verify that data written with this schema can be read as `current`
+    /// by a self-describing format such as JSON.
+    ///
+    /// `used` names the variants the data contains. Those must still exist
+    /// and keep the fields recorded for them; other variants may change
+    /// freely. Struct types use all their fields, so `used` is ignored for
+    /// them. Adding variants or fields is compatible.
+    pub fn verify_against(
+        &self,
+        current: &StateSchema,
+        used: &[String],
+    ) -> Result<(), CheckpointError> {
+        let mut missing = Vec::new();
+        let mut added = Vec::new();
+
+        match self.kind {
+            SchemaKind::Struct => {
+                missing.extend(absent(&self.members, &current.members));
+                added.extend(absent(&current.members, &self.members));
+            }
+            SchemaKind::Enum => {
+                for variant in used {
+                    if !current.members.contains(variant) {
+                        missing.push(variant.clone());
+                        continue;
+                    }
+                    let (recorded, now) = (self.fields_of(variant), current.fields_of(variant));
+                    missing.extend(absent(recorded, now).map(|f| format!("{variant}.{f}")));
+                    added.extend(absent(now, recorded).map(|f| format!("{variant}.{f}")));
+                }
+                added.extend(absent(&current.members, &self.members));
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(CheckpointError::StateSchemaMismatch {
+            type_name: current.type_name.clone(),
+            missing,
+            added,
+        })
+    }
+
+    /// Verify compatibility for positional formats such as binary checkpoints.
+    ///
+    /// Binary encodings identify variants by index and fields by position,
+    /// so each variant in `used` must keep its position in `current`, and
+    /// its fields must be unchanged. Unused variants may change, and new
+    /// variants may be appended. Struct types must keep all their fields.
+    pub fn verify_positional(
+        &self,
+        current: &StateSchema,
+        used: &[String],
+    ) -> Result<(), CheckpointError> {
+        let mut missing = Vec::new();
+        let mut added = Vec::new();
+
+        match self.kind {
+            SchemaKind::Struct => {
+                if self.members != current.members {
+                    missing.extend(moved(&self.members, &current.members));
+                    added.extend(absent(&current.members, &self.members));
+                }
+            }
+            SchemaKind::Enum => {
+                for variant in used {
+                    let position =
+                        |schema: &StateSchema| schema.members.iter().position(|m| m == variant);
+                    if position(self) != position(current) {
+                        missing.push(variant.clone());
+                        continue;
+                    }
+                    let (recorded, now) = (self.fields_of(variant), current.fields_of(variant));
+                    if recorded != now {
+                        let before = missing.len();
+                        missing.extend(moved(recorded, now).map(|f| format!("{variant}.{f}")));
+                        if missing.len() == before {
+                            // Only fields were appended, which shifts what follows
+                            missing.push(variant.clone());
+                        }
+                        added.extend(absent(now, recorded).map(|f| format!("{variant}.{f}")));
+                    }
+                }
+                added.extend(absent(&current.members, &self.members));
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(CheckpointError::StateSchemaMismatch {
+            type_name: current.type_name.clone(),
+            missing,
+            added,
+        })
+    }
+
+    fn fields_of(&self, variant: &str) -> &[String] {
+        self.fields.get(variant).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Names in `names` that `other` lacks
+fn absent<'a>(names: &'a [String], other: &'a [String]) -> impl Iterator<Item = String> + 'a {
+    names.iter().filter(|n| !other.contains(n)).cloned()
+}
+
+/// Names in `names` that `other` does not hold at the same position
+fn moved<'a>(names: &'a [String], other: &'a [String]) -> impl Iterator<Item = String> + 'a {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, n)| other.get(*i) != Some(n))
+        .map(|(_, n)| n.clone())
+}
+
+/// Variant names of the states in a JSON checkpoint, in order of first use.
+///
+/// Externally tagged enums serialize as `"Variant"` or `{"Variant": ...}`,
+/// which is the only representation a schema is captured for.
+pub(crate) fn variants_in_json(checkpoint: &Value) -> Vec<String> {
+    let transitions = checkpoint
+        .pointer("/history/transitions")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    let states = [
+        checkpoint.get("initial_state"),
+        checkpoint.get("current_state"),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(
+        transitions
+            .iter()
+            .flat_map(|t| [t.get("from"), t.get("to")])
+            .flatten(),
+    );
+
+    let mut used: Vec<String> = Vec::new();
+    for state in states {
+        let variant = match state {
+            Value::String(variant) => Some(variant),
+            Value::Object(map) if map.len() == 1 => map.keys().next(),
+            _ => None,
+        };
+        if let Some(variant) = variant.filter(|v| !used.contains(v)) {
+            used.push(variant.clone());
+        }
+    }
+    used
+}
+
+/// Index of the enum variant `value` serializes as, or `None` if it does
+/// not serialize as an enum variant.
+pub(crate) fn variant_index<T: Serialize + ?Sized>(value: &T) -> Option<u32> {
+    match value.serialize(VariantTracer) {
+        Err(VariantTrace(index)) => index,
+        Ok(()) => None,
+    }
+}
+
+/// Deserializer that records the schema requested by a `Deserialize` impl
+/// and then aborts without producing a value.
+struct SchemaTracer<'a> {
+    captured: &'a mut Option<StateSchema>,
+}
+
+impl SchemaTracer<'_> {
+    fn capture(self, name: &str, kind: SchemaKind, members: &[&str]) -> TraceStop {
+        *self.captured = Some(StateSchema {
+            type_name: name.to_string(),
+            kind,
+            members: members.iter().map(|m| m.to_string()).collect(),
+            fields: BTreeMap::new(),
+        });
+        TraceStop
+    }
+}
+
+#[derive(Debug)]
+struct TraceStop;
+
+impl fmt::Display for TraceStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema tracing stopped")
+    }
+}
+
+impl std::error::Error for TraceStop {}
+
+impl de::Error for TraceStop {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        TraceStop
+    }
+}
+
+impl<'de> de::Deserializer<'de> for SchemaTracer<'_> {
+    type Error = TraceStop;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceStop> {
+        Err(TraceStop)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, TraceStop> {
+        Err(self.capture(name, SchemaKind::Enum, variants))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, TraceStop> {
+        Err(self.capture(name, SchemaKind::Struct, fields))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map identifier ignored_any
+    }
+}
+
+/// Deserializer that selects the variant at `index` and records its field
+/// names if it is a struct variant.
+struct FieldTracer<'a> {
+    index: u32,
+    fields: &'a mut Option<Vec<String>>,
+}
+
+impl<'de> de::Deserializer<'de> for FieldTracer<'_> {
+    type Error = TraceStop;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceStop> {
+        Err(TraceStop)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceStop> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for FieldTracer<'_> {
+    type Error = TraceStop;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), TraceStop> {
+        let variant = seed.deserialize(self.index.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for FieldTracer<'_> {
+    type Error = TraceStop;
+
+    fn unit_variant(self) -> Result<(), TraceStop> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, TraceStop> {
+        Err(TraceStop)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, TraceStop> {
+        Err(TraceStop)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, TraceStop> {
+        *self.fields = Some(fields.iter().map(|f| f.to_string()).collect());
+        Err(TraceStop)
+    }
+}
+
+/// Serializer that stops at the first enum variant, reporting its index
+/// through the error without serializing the payload.
+struct VariantTracer;
+
+#[derive(Debug)]
+struct VariantTrace(Option<u32>);
+
+impl fmt::Display for VariantTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "variant tracing stopped")
+    }
+}
+
+impl std::error::Error for VariantTrace {}
+
+impl ser::Error for VariantTrace {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        VariantTrace(None)
+    }
+}
+
+macro_rules! not_a_variant {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<(), VariantTrace> {
+                Err(VariantTrace(None))
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for VariantTracer {
+    type Ok = ();
+    type Error = VariantTrace;
+    type SerializeSeq = Impossible<(), VariantTrace>;
+    type SerializeTuple = Impossible<(), VariantTrace>;
+    type SerializeTupleStruct = Impossible<(), VariantTrace>;
+    type SerializeTupleVariant = Impossible<(), VariantTrace>;
+    type SerializeMap = Impossible<(), VariantTrace>;
+    type SerializeStruct = Impossible<(), VariantTrace>;
+    type SerializeStructVariant = Impossible<(), VariantTrace>;
+
+    not_a_variant! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<(), VariantTrace> {
+        Err(VariantTrace(Some(index)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), VariantTrace> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), VariantTrace> {
+        Err(VariantTrace(Some(index)))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, VariantTrace> {
+        Err(VariantTrace(Some(index)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, VariantTrace> {
+        Err(VariantTrace(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, VariantTrace> {
+        Err(VariantTrace(Some(index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Initial,
+        Running { progress: u8 },
+        Complete,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Initial => "Initial",
+                Self::Running { .. } => "Running",
+                Self::Complete => "Complete",
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(tag = "kind")]
+    enum TaggedState {
+        A,
+    }
+
+    impl State for TaggedState {
+        fn name(&self) -> &str {
+            "A"
+        }
+    }
+
+    fn schema(members: &[&str]) -> StateSchema {
+        StateSchema {
+            type_name: "TestState".to_string(),
+            kind: SchemaKind::Enum,
+            members: members.iter().map(|m| m.to_string()).collect(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    fn used(variants: &[&str]) -> Vec<String> {
+        variants.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn captures_variants_and_their_fields() {
+        let mut expected = schema(&["Initial", "Running", "Complete"]);
+        expected
+            .fields
+            .insert("Running".to_string(), vec!["progress".to_string()]);
+
+        assert_eq!(StateSchema::of::<TestState>(), Some(expected));
+    }
+
+    #[test]
+    fn untraceable_types_have_no_schema() {
+        assert_eq!(StateSchema::of::<TaggedState>(), None);
+    }
+
+    #[test]
+    fn added_variants_are_compatible() {
+        let recorded = schema(&["Initial", "Complete"]);
+        let current = schema(&["Initial", "Running", "Complete"]);
+
+        assert!(recorded
+            .verify_against(&current, &used(&["Initial", "Complete"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn positional_check_rejects_reordering_of_used_variants() {
+        let recorded = schema(&["Initial", "Complete"]);
+        let reordered = schema(&["Initial", "Running", "Complete"]);
+
+        assert!(recorded
+            .verify_positional(
+                &schema(&["Initial", "Complete", "Running"]),
+                &used(&["Complete"])
+            )
+            .is_ok());
+        assert!(recorded
+            .verify_positional(&reordered, &used(&["Initial"]))
+            .is_ok());
+        assert!(recorded
+            .verify_positional(&reordered, &used(&["Complete"]))
+            .is_err());
+    }
+
+    #[test]
+    fn removed_variants_are_reported_only_when_used() {
+        let recorded = schema(&["Initial", "Paused", "Complete"]);
+        let current = schema(&["Initial", "Running", "Complete"]);
+
+        assert!(recorded
+            .verify_against(&current, &used(&["Initial", "Complete"]))
+            .is_ok());
+        match recorded.verify_against(&current, &used(&["Initial", "Paused"])) {
+            Err(CheckpointError::StateSchemaMismatch { missing, added, .. }) => {
+                assert_eq!(missing, vec!["Paused"]);
+                assert_eq!(added, vec!["Running"]);
+            }
+            other => panic!("Expected StateSchemaMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn removed_fields_of_used_variants_are_reported() {
+        let mut recorded = schema(&["Initial", "Running"]);
+        recorded
+            .fields
+            .insert("Running".to_string(), used(&["progress", "eta"]));
+        let current = StateSchema::of::<TestState>().unwrap();
+
+        assert!(recorded
+            .verify_against(&current, &used(&["Initial"]))
+            .is_ok());
+        match recorded.verify_against(&current, &used(&["Running"])) {
+            Err(CheckpointError::StateSchemaMismatch { missing, .. }) => {
+                assert_eq!(missing, vec!["Running.eta"]);
+            }
+            other => panic!("Expected StateSchemaMismatch, got {:?}", other),
+        }
+        assert!(recorded
+            .verify_positional(&current, &used(&["Running"]))
+            .is_err());
+    }
+
+    #[test]
+    fn variant_indices_and_json_variants_are_traced() {
+        assert_eq!(variant_index(&TestState::Running { progress: 3 }), Some(1));
+        assert_eq!(
+            variant_index(&std::sync::Arc::new(TestState::Complete)),
+            Some(2)
+        );
+        assert_eq!(variant_index(&3u8), None);
+
+        let checkpoint = serde_json::json!({
+            "initial_state": "Initial",
+            "current_state": { "Running": { "progress": 3 } },
+            "history": { "transitions": [{ "from": "Initial", "to": "Complete" }] }
+        });
+        assert_eq!(
+            variants_in_json(&checkpoint),
+            vec!["Initial", "Running", "Complete"]
+        );
+    }
+}
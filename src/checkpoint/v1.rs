@@ -0,0 +1,85 @@
+//! Decoder for version 1 binary checkpoints.
+//!
+//! Binary checkpoints are positional, so fields added to [`Checkpoint`]
+//! since version 1 cannot be defaulted the way JSON checkpoints default
+//! them. Version 1 checkpoints are decoded with their original layout here
+//! and then converted.
+
+use crate::checkpoint::{Checkpoint, MachineMetadata};
+use crate::core::{State, StateHistory, StateTransition};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Deserialize)]
+#[serde(bound = "")]
+pub(crate) struct CheckpointV1<S: State> {
+    version: u32,
+    id: String,
+    timestamp: DateTime<Utc>,
+    initial_state: S,
+    current_state: S,
+    history: HistoryV1<S>,
+    metadata: MetadataV1,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "")]
+struct HistoryV1<S: State> {
+    transitions: Vec<TransitionV1<S>>,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "")]
+struct TransitionV1<S: State> {
+    from: S,
+    to: S,
+    timestamp: DateTime<Utc>,
+    attempt: usize,
+}
+
+#[derive(Deserialize)]
+struct MetadataV1 {
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    current_attempt: usize,
+    total_attempts: HashMap<String, usize>,
+}
+
+impl<S: State> From<CheckpointV1<S>> for Checkpoint<S> {
+    fn from(v1: CheckpointV1<S>) -> Self {
+        let transitions = v1
+            .history
+            .transitions
+            .into_iter()
+            .map(|t| StateTransition {
+                from: t.from,
+                to: t.to,
+                timestamp: t.timestamp,
+                attempt: t.attempt,
+                sequence: 0,
+                forced: None,
+                correlation: BTreeMap::new(),
+            })
+            .collect();
+
+        Checkpoint {
+            version: v1.version,
+            state_schema: None,
+            id: v1.id,
+            timestamp: v1.timestamp,
+            initial_state: v1.initial_state,
+            current_state: v1.current_state,
+            history: StateHistory::from_parts(transitions, Vec::new()),
+            metadata: MachineMetadata {
+                created_at: v1.metadata.created_at,
+                updated_at: v1.metadata.updated_at,
+                current_attempt: v1.metadata.current_attempt,
+                current_transition_started_at: None,
+                total_attempts: v1.metadata.total_attempts,
+                machine_id: None,
+                ..MachineMetadata::default()
+            },
+        }
+    }
+}
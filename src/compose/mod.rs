@@ -0,0 +1,667 @@
+//! Composition of state machine definitions.
+//!
+//! Three combinators build a new machine definition from existing ones:
+//!
+//! - [`product`] runs two machines side by side. Its states are pairs and
+//!   each transition advances one component while the other stays put,
+//!   which layers independent concerns (e.g. document flow × locking)
+//!   without writing the product enum by hand.
+//! - [`regions`] runs two machines as orthogonal regions of one machine:
+//!   a step advances both regions at once, running their actions
+//!   concurrently, and falls back to moving one region when only one can.
+//! - [`merge`] unions the edge sets of two definitions over the same state
+//!   type, rejecting edges defined by both.
+//!
+//! Only definitions are composed: the resulting machine starts fresh in
+//! the combined initial state with empty history.
+
+use crate::core::{Guard, State};
+use crate::effects::{StateMachine, Transition, TransitionResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use stillwater::effect::{from_async, par2};
+use stillwater::prelude::*;
+use thiserror::Error;
+
+/// Errors that can occur when composing machine definitions.
+#[derive(Debug, Error, PartialEq)]
+pub enum ComposeError {
+    #[error("Cannot merge machines with different initial states '{left}' and '{right}'")]
+    InitialStateMismatch { left: String, right: String },
+
+    #[error("Transitions defined by both machines: {}", format_edges(.edges))]
+    ConflictingTransitions { edges: Vec<(String, String)> },
+}
+
+fn format_edges(edges: &[(String, String)]) -> String {
+    edges
+        .iter()
+        .map(|(from, to)| format!("'{from}' -> '{to}'"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// State of a [`product`] machine: one state from each component.
+///
+/// The product is final when both components are final, and an error state
+/// when either component is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "", from = "(A, B)", into = "(A, B)")]
+pub struct ProductState<A: State, B: State> {
+    /// State of the left machine
+    pub left: A,
+    /// State of the right machine
+    pub right: B,
+    name: String,
+}
+
+impl<A: State, B: State> ProductState<A, B> {
+    /// Pair a left and a right state.
+    pub fn new(left: A, right: B) -> Self {
+        let name = format!("({}, {})", left.name(), right.name());
+        Self { left, right, name }
+    }
+}
+
+impl<A: State, B: State> From<(A, B)> for ProductState<A, B> {
+    fn from((left, right): (A, B)) -> Self {
+        Self::new(left, right)
+    }
+}
+
+impl<A: State, B: State> From<ProductState<A, B>> for (A, B) {
+    fn from(state: ProductState<A, B>) -> Self {
+        (state.left, state.right)
+    }
+}
+
+impl<A: State, B: State> State for ProductState<A, B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_final(&self) -> bool {
+        self.left.is_final() && self.right.is_final()
+    }
+
+    fn is_error(&self) -> bool {
+        self.left.is_error() || self.right.is_error()
+    }
+}
+
+/// States mentioned by a definition, in first-seen order.
+fn states_of<S: State + 'static, Env: Clone + Send + Sync + 'static>(
+    machine: &StateMachine<S, Env>,
+) -> Vec<S> {
+    let mut states = vec![machine.initial_state().clone()];
+    for transition in machine.transitions() {
+        for state in [&transition.from, &transition.to] {
+            if !states.contains(state) {
+                states.push(state.clone());
+            }
+        }
+    }
+    states
+}
+
+/// Lift a component transition into the product. `wrap` pairs a component
+/// state with the fixed state of the other component; `project` extracts
+/// the component from a product state for guard checks.
+fn lift<C, P, Env>(
+    transition: &Transition<C, Env>,
+    wrap: impl Fn(C) -> P + Clone + Send + Sync + 'static,
+    project: impl Fn(&P) -> &C + Send + Sync + 'static,
+) -> Transition<P, Env>
+where
+    C: State + 'static,
+    P: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    let guard = transition.guard.clone().map(|guard| {
+        let label = guard.label().map(str::to_string);
+        let lifted = Guard::new(move |state: &P| guard.check(project(state)));
+        match label {
+            Some(label) => lifted.with_label(label),
+            None => lifted,
+        }
+    });
+    let action = Arc::clone(&transition.action);
+
+    Transition {
+        from: wrap(transition.from.clone()),
+        to: wrap(transition.to.clone()),
+        guard,
+        location: transition.location,
+        metadata: transition.metadata.clone(),
+        flag: transition.flag.clone(),
+        action: Arc::new(move || {
+            let wrap = wrap.clone();
+            action()
+                .map(move |result| match result {
+                    TransitionResult::Success(state) => TransitionResult::Success(wrap(state)),
+                    TransitionResult::Retry {
+                        feedback,
+                        current_state,
+                    } => TransitionResult::Retry {
+                        feedback,
+                        current_state: wrap(current_state),
+                    },
+                    TransitionResult::Abort {
+                        reason,
+                        error_state,
+                    } => TransitionResult::Abort {
+                        reason,
+                        error_state: wrap(error_state),
+                    },
+                })
+                .boxed()
+        }),
+    }
+}
+
+/// Build the product of two machine definitions.
+///
+/// For every state `b` of the right machine, each left transition
+/// `a -> a'` becomes `(a, b) -> (a', b)`, and symmetrically for right
+/// transitions. Guards and actions of the components are reused, so the
+/// product keeps their behaviour.
+pub fn product<A, B, Env>(
+    left: &StateMachine<A, Env>,
+    right: &StateMachine<B, Env>,
+) -> StateMachine<ProductState<A, B>, Env>
+where
+    A: State + 'static,
+    B: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    let mut machine = StateMachine::new(ProductState::new(
+        left.initial_state().clone(),
+        right.initial_state().clone(),
+    ));
+
+    for transition in left.transitions() {
+        for other in states_of(right) {
+            machine.add_transition(lift(
+                transition,
+                move |state| ProductState::new(state, other.clone()),
+                |state: &ProductState<A, B>| &state.left,
+            ));
+        }
+    }
+
+    for transition in right.transitions() {
+        for other in states_of(left) {
+            machine.add_transition(lift(
+                transition,
+                move |state| ProductState::new(other.clone(), state),
+                |state: &ProductState<A, B>| &state.right,
+            ));
+        }
+    }
+
+    machine
+}
+
+/// Combine the outcomes of two region actions into the outcome of the
+/// joint step (pure).
+///
+/// A joint step moves both regions or neither, so history only records
+/// the joint transition's target. A region that asks for a retry makes the
+/// whole step retry with that region's feedback, and both actions run
+/// again on the next step. An abort in either region aborts the step,
+/// keeping the other region's outcome as its part of the error state.
+fn settle<A: State, B: State>(
+    from: &ProductState<A, B>,
+    left: TransitionResult<A>,
+    right: TransitionResult<B>,
+) -> TransitionResult<ProductState<A, B>> {
+    use TransitionResult::*;
+
+    match (left, right) {
+        (Success(a), Success(b)) => Success(ProductState::new(a, b)),
+        (
+            Retry {
+                feedback: left,
+                current_state: a,
+            },
+            Retry {
+                feedback: right,
+                current_state: b,
+            },
+        ) => Retry {
+            feedback: format!("{left}; {right}"),
+            current_state: ProductState::new(a, b),
+        },
+        (
+            Success(_),
+            Retry {
+                feedback,
+                current_state: b,
+            },
+        ) => Retry {
+            feedback,
+            current_state: ProductState::new(from.left.clone(), b),
+        },
+        (
+            Retry {
+                feedback,
+                current_state: a,
+            },
+            Success(_),
+        ) => Retry {
+            feedback,
+            current_state: ProductState::new(a, from.right.clone()),
+        },
+        (
+            Abort {
+                reason: left,
+                error_state: a,
+            },
+            Abort {
+                reason: right,
+                error_state: b,
+            },
+        ) => Abort {
+            reason: format!("{left}; {right}"),
+            error_state: ProductState::new(a, b),
+        },
+        (
+            Abort {
+                reason,
+                error_state: a,
+            },
+            Success(b)
+            | Retry {
+                current_state: b, ..
+            },
+        ) => Abort {
+            reason,
+            error_state: ProductState::new(a, b),
+        },
+        (
+            Success(a)
+            | Retry {
+                current_state: a, ..
+            },
+            Abort {
+                reason,
+                error_state: b,
+            },
+        ) => Abort {
+            reason,
+            error_state: ProductState::new(a, b),
+        },
+    }
+}
+
+/// Joint transition advancing both regions, or `None` when the two
+/// transitions are behind different feature flags.
+fn joint<A, B, Env>(
+    left: &Transition<A, Env>,
+    right: &Transition<B, Env>,
+) -> Option<Transition<ProductState<A, B>, Env>>
+where
+    A: State + 'static,
+    B: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    let flag = match (&left.flag, &right.flag) {
+        (Some(l), Some(r)) if l != r => return None,
+        (l, r) => l.clone().or(r.clone()),
+    };
+
+    let guard = match (left.guard.clone(), right.guard.clone()) {
+        (None, None) => None,
+        (l, r) => {
+            let label = [
+                l.as_ref().and_then(|g| g.label()),
+                r.as_ref().and_then(|g| g.label()),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" and ");
+            let guard = Guard::new(move |state: &ProductState<A, B>| {
+                l.as_ref().is_none_or(|g| g.check(&state.left))
+                    && r.as_ref().is_none_or(|g| g.check(&state.right))
+            });
+            Some(if label.is_empty() {
+                guard
+            } else {
+                guard.with_label(label)
+            })
+        }
+    };
+
+    let mut metadata: BTreeMap<_, _> = left.metadata.clone();
+    metadata.extend(right.metadata.clone());
+    let (left_action, right_action) = (Arc::clone(&left.action), Arc::clone(&right.action));
+
+    let from = ProductState::new(left.from.clone(), right.from.clone());
+    Some(Transition {
+        from: from.clone(),
+        to: ProductState::new(left.to.clone(), right.to.clone()),
+        guard,
+        location: left.location,
+        metadata,
+        flag,
+        action: Arc::new(move || {
+            let (left, right) = (left_action(), right_action());
+            let from = from.clone();
+            from_async(move |env: &Env| {
+                let env = env.clone();
+                async move {
+                    let (left, right) = par2(left, right, &env).await;
+                    Ok(settle(&from, left?, right?))
+                }
+            })
+            .boxed()
+        }),
+    })
+}
+
+/// Build a machine running two definitions as orthogonal regions.
+///
+/// The states are those of the [`product`], so the machine is final only
+/// once both regions are. Each pair of transitions `a -> a'` and `b -> b'`
+/// becomes a joint transition `(a, b) -> (a', b')` that runs both actions
+/// concurrently; joint transitions come first, so a step advances both
+/// regions whenever both can move. The product's single-region transitions
+/// follow and cover steps where only one region can.
+///
+/// An action error in either region fails the whole step, and a retry in
+/// either region retries it. Transitions behind two different feature
+/// flags are not joined.
+///
+/// Every pair of region transitions is joined up front, so the machine
+/// holds `|L| × |R|` joint transitions plus the product's single-region
+/// ones, which grow with `|L| × |states of R| + |R| × |states of L|`.
+/// Regions suit small definitions; step costs grow with the transition
+/// count, since selection scans transitions in order.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::builder::simple_transition;
+/// use mindset::compose::regions;
+/// use mindset::core::State;
+/// use mindset::effects::StateMachine;
+/// use mindset::state_enum;
+///
+/// state_enum! {
+///     enum Link {
+///         Offline,
+///         Online,
+///     }
+///     final: [Online]
+/// }
+///
+/// state_enum! {
+///     enum Battery {
+///         Charging,
+///         Full,
+///     }
+///     final: [Full]
+/// }
+///
+/// let mut link = StateMachine::<Link, ()>::new(Link::Offline);
+/// link.add_transition(simple_transition(Link::Offline, Link::Online));
+/// let mut battery = StateMachine::<Battery, ()>::new(Battery::Charging);
+/// battery.add_transition(simple_transition(Battery::Charging, Battery::Full));
+///
+/// let device = regions(&link, &battery);
+/// assert_eq!(device.transitions()[0].to.name(), "(Online, Full)");
+/// ```
+pub fn regions<A, B, Env>(
+    left: &StateMachine<A, Env>,
+    right: &StateMachine<B, Env>,
+) -> StateMachine<ProductState<A, B>, Env>
+where
+    A: State + 'static,
+    B: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    let singles = product(left, right);
+    let mut machine = StateMachine::new(singles.initial_state().clone());
+    for l in left.transitions() {
+        for r in right.transitions() {
+            if let Some(transition) = joint(l, r) {
+                machine.add_transition(transition);
+            }
+        }
+    }
+    for transition in singles.transitions() {
+        machine.add_transition(transition.clone());
+    }
+    machine
+}
+
+/// Merge two definitions over the same state type.
+///
+/// The result has the left machine's transitions followed by the right
+/// machine's. Both machines must share their initial state, and no edge
+/// (`from`, `to`) may be defined by both.
+pub fn merge<S, Env>(
+    left: &StateMachine<S, Env>,
+    right: &StateMachine<S, Env>,
+) -> Result<StateMachine<S, Env>, ComposeError>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    if left.initial_state() != right.initial_state() {
+        return Err(ComposeError::InitialStateMismatch {
+            left: left.initial_state().name().to_string(),
+            right: right.initial_state().name().to_string(),
+        });
+    }
+
+    let edges: Vec<(String, String)> = right
+        .transitions()
+        .iter()
+        .filter(|r| {
+            left.transitions()
+                .iter()
+                .any(|l| l.from == r.from && l.to == r.to)
+        })
+        .map(|t| (t.from.name().to_string(), t.to.name().to_string()))
+        .collect();
+    if !edges.is_empty() {
+        return Err(ComposeError::ConflictingTransitions { edges });
+    }
+
+    let mut machine = StateMachine::new(left.initial_state().clone());
+    for transition in left.transitions().iter().chain(right.transitions()) {
+        machine.add_transition(transition.clone());
+    }
+    Ok(machine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{guarded_transition, simple_transition};
+    use crate::effects::StepResult;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Doc {
+        Draft,
+        Published,
+    }
+
+    impl State for Doc {
+        fn name(&self) -> &str {
+            match self {
+                Self::Draft => "Draft",
+                Self::Published => "Published",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Published)
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Lock {
+        Unlocked,
+        Locked,
+    }
+
+    impl State for Lock {
+        fn name(&self) -> &str {
+            match self {
+                Self::Unlocked => "Unlocked",
+                Self::Locked => "Locked",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Locked)
+        }
+    }
+
+    fn doc_machine() -> StateMachine<Doc, ()> {
+        let mut machine = StateMachine::new(Doc::Draft);
+        machine.add_transition(simple_transition(Doc::Draft, Doc::Published));
+        machine
+    }
+
+    fn lock_machine() -> StateMachine<Lock, ()> {
+        let mut machine = StateMachine::new(Lock::Unlocked);
+        machine.add_transition(simple_transition(Lock::Unlocked, Lock::Locked));
+        machine
+    }
+
+    #[test]
+    fn product_advances_one_component_per_transition() {
+        let machine = product(&doc_machine(), &lock_machine());
+
+        assert_eq!(machine.current_state().name(), "(Draft, Unlocked)");
+        let edges: Vec<_> = machine
+            .transitions()
+            .iter()
+            .map(|t| (t.from.name().to_string(), t.to.name().to_string()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("(Draft, Unlocked)".into(), "(Published, Unlocked)".into()),
+                ("(Draft, Locked)".into(), "(Published, Locked)".into()),
+                ("(Draft, Unlocked)".into(), "(Draft, Locked)".into()),
+                ("(Published, Unlocked)".into(), "(Published, Locked)".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn product_runs_component_actions_to_completion() {
+        let mut machine = product(&doc_machine(), &lock_machine());
+
+        while !machine.is_final() {
+            let (from, result, attempt) = machine.step().run(&()).await.unwrap();
+            assert!(matches!(result, StepResult::Transitioned(_)));
+            machine.apply_result(from, result, attempt);
+        }
+
+        assert_eq!(
+            machine.current_state(),
+            &ProductState::new(Doc::Published, Lock::Locked)
+        );
+        let restored = StateMachine::<ProductState<Doc, Lock>, ()>::from_json(
+            &machine.to_json().unwrap(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(restored.current_state().name(), "(Published, Locked)");
+    }
+
+    #[test]
+    fn product_lifts_guards_onto_their_component() {
+        let mut docs = StateMachine::<Doc, ()>::new(Doc::Draft);
+        docs.add_transition(guarded_transition(Doc::Draft, Doc::Published, |_| false));
+        let machine = product(&docs, &lock_machine());
+
+        assert!(!machine.transitions()[0].can_execute(machine.current_state()));
+    }
+
+    #[tokio::test]
+    async fn regions_advance_together_then_alone() {
+        let mut docs = doc_machine();
+        docs.add_transition(simple_transition(Doc::Published, Doc::Draft));
+        let mut machine = regions(&docs, &lock_machine());
+
+        let (from, result, attempt) = machine.step().run(&()).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(
+            machine.current_state(),
+            &ProductState::new(Doc::Published, Lock::Locked)
+        );
+        assert!(machine.is_final());
+
+        let (from, result, attempt) = machine.step().run(&()).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(
+            machine.current_state(),
+            &ProductState::new(Doc::Draft, Lock::Locked)
+        );
+    }
+
+    #[test]
+    fn settle_retries_the_joint_step_when_a_region_retries() {
+        let from = ProductState::new(Doc::Draft, Lock::Unlocked);
+        let retry = || TransitionResult::Retry {
+            feedback: "busy".to_string(),
+            current_state: Lock::Unlocked,
+        };
+
+        assert_eq!(
+            settle(&from, TransitionResult::Success(Doc::Published), retry()),
+            TransitionResult::Retry {
+                feedback: "busy".to_string(),
+                current_state: from.clone(),
+            }
+        );
+        assert_eq!(
+            settle(
+                &from,
+                TransitionResult::Abort {
+                    reason: "rejected".to_string(),
+                    error_state: Doc::Draft,
+                },
+                retry()
+            ),
+            TransitionResult::Abort {
+                reason: "rejected".to_string(),
+                error_state: ProductState::new(Doc::Draft, Lock::Unlocked),
+            }
+        );
+    }
+
+    #[test]
+    fn merge_unions_edges() {
+        let mut extra = StateMachine::<Doc, ()>::new(Doc::Draft);
+        extra.add_transition(simple_transition(Doc::Published, Doc::Draft));
+
+        let merged = merge(&doc_machine(), &extra).unwrap();
+
+        assert_eq!(merged.transitions().len(), 2);
+    }
+
+    #[test]
+    fn merge_rejects_duplicate_edges_and_mismatched_initials() {
+        assert_eq!(
+            merge(&doc_machine(), &doc_machine()).err(),
+            Some(ComposeError::ConflictingTransitions {
+                edges: vec![("Draft".into(), "Published".into())]
+            })
+        );
+
+        let other = StateMachine::<Doc, ()>::new(Doc::Published);
+        assert!(matches!(
+            merge(&doc_machine(), &other),
+            Err(ComposeError::InitialStateMismatch { .. })
+        ));
+    }
+}
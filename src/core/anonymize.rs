@@ -0,0 +1,291 @@
+//! Anonymized history export.
+//!
+//! An anonymized history keeps the shape of a run (state names, order,
+//! timing, attempts) but replaces state payloads and identities with keyed
+//! hashes (HMAC-SHA256) and drops free-text reasons. Equal payloads hash to
+//! equal identifiers, so a trace still shows when a run returned to the
+//! same state, without revealing business data. Without the key, the
+//! identifiers can neither be reversed nor recomputed for guessed payloads.
+//! The result is meant for sharing traces outside the organisation, e.g.
+//! with vendors while debugging.
+
+use super::history::{HistoryEvent, StateHistory};
+use super::state::State;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+/// A state reduced to its name and a hashed identifier of its payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymizedState {
+    /// `State::name()` of the original state
+    pub name: String,
+    /// Keyed hash of the state's serialized payload
+    pub id: String,
+}
+
+/// Anonymized transition record.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnonymizedTransition {
+    /// The state being transitioned from
+    pub from: AnonymizedState,
+    /// The state being transitioned to
+    pub to: AnonymizedState,
+    /// When the transition occurred
+    pub timestamp: DateTime<Utc>,
+    /// The attempt number for this transition
+    pub attempt: usize,
+    /// Hashed operator identity if the transition was forced
+    pub forced_by: Option<String>,
+    /// Correlation keys with hashed values
+    pub correlation: BTreeMap<String, String>,
+}
+
+/// Anonymized administrative event: its kind and when it happened.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnonymizedEvent {
+    /// Event variant name, e.g. `Paused`
+    pub kind: String,
+    /// When the event happened
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Shareable, anonymized copy of a [`StateHistory`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnonymizedHistory {
+    /// Transitions, oldest first
+    pub transitions: Vec<AnonymizedTransition>,
+    /// Administrative events, oldest first
+    pub events: Vec<AnonymizedEvent>,
+}
+
+/// HMAC-SHA256 of the value under `key`, hex encoded
+fn hash(key: &str, value: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(value);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn anonymize_state<S: State>(state: &S, key: &str) -> Result<AnonymizedState, serde_json::Error> {
+    let payload = serde_json::to_vec(state)?;
+    Ok(AnonymizedState {
+        name: state.name().to_string(),
+        id: hash(key, &payload),
+    })
+}
+
+impl<S: State> StateHistory<S> {
+    /// Export the history with payloads replaced by hashes keyed with
+    /// `key` (pure).
+    ///
+    /// Keep the key secret, and use a fresh one per recipient; anyone
+    /// holding it can confirm guessed payloads of states with few possible
+    /// values. Pause and force reasons are dropped entirely.
+    ///
+    /// Fails if a state cannot be serialized, since its payload could then
+    /// not be told apart from others.
+    pub fn anonymized(&self, key: &str) -> Result<AnonymizedHistory, serde_json::Error> {
+        Ok(AnonymizedHistory {
+            transitions: self
+                .transitions()
+                .iter()
+                .map(|t| {
+                    Ok(AnonymizedTransition {
+                        from: anonymize_state(&t.from, key)?,
+                        to: anonymize_state(&t.to, key)?,
+                        timestamp: t.timestamp,
+                        attempt: t.attempt,
+                        forced_by: t
+                            .forced
+                            .as_ref()
+                            .map(|forced| hash(key, forced.operator.as_bytes())),
+                        correlation: t
+                            .correlation
+                            .iter()
+                            .map(|(name, value)| (name.clone(), hash(key, value.as_bytes())))
+                            .collect(),
+                    })
+                })
+                .collect::<Result<_, serde_json::Error>>()?,
+            events: self
+                .events()
+                .iter()
+                .map(|event| {
+                    let (kind, timestamp) = match event {
+                        HistoryEvent::Paused { timestamp, .. } => ("Paused", timestamp),
+                        HistoryEvent::Resumed { timestamp } => ("Resumed", timestamp),
+                        HistoryEvent::InvariantViolated { timestamp, .. } => {
+                            ("InvariantViolated", timestamp)
+                        }
+                        HistoryEvent::TransitionAdded { timestamp, .. } => {
+                            ("TransitionAdded", timestamp)
+                        }
+                        HistoryEvent::TransitionRemoved { timestamp, .. } => {
+                            ("TransitionRemoved", timestamp)
+                        }
+                        HistoryEvent::LockAcquired { timestamp, .. } => ("LockAcquired", timestamp),
+                        HistoryEvent::LockReleased { timestamp, .. } => ("LockReleased", timestamp),
+                    };
+                    AnonymizedEvent {
+                        kind: kind.to_string(),
+                        timestamp: *timestamp,
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ForcedTransition, StateTransition};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Customer { email: String },
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Customer { .. } => "Customer",
+                Self::Done => "Done",
+            }
+        }
+    }
+
+    fn transition(from: TestState, to: TestState) -> StateTransition<TestState> {
+        StateTransition {
+            from,
+            to,
+            timestamp: Utc::now(),
+            attempt: 1,
+            sequence: 0,
+            forced: Some(ForcedTransition {
+                reason: "customer called".to_string(),
+                operator: "jane".to_string(),
+            }),
+            correlation: BTreeMap::from([("request_id".to_string(), "r-1".to_string())]),
+        }
+    }
+
+    fn customer(email: &str) -> TestState {
+        TestState::Customer {
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn export_contains_no_payloads() {
+        let history = StateHistory::new()
+            .record(transition(customer("a@example.com"), TestState::Done))
+            .record_event(HistoryEvent::Paused {
+                reason: "a@example.com on hold".to_string(),
+                timestamp: Utc::now(),
+            });
+
+        let export = serde_json::to_string(&history.anonymized("salt").unwrap()).unwrap();
+
+        assert!(!export.contains("example.com"));
+        assert!(!export.contains("jane"));
+        assert!(!export.contains("r-1"));
+        assert!(export.contains("Customer"));
+        assert!(export.contains("request_id"));
+    }
+
+    #[test]
+    fn identifiers_are_stable_per_payload_and_salt() {
+        let history = StateHistory::new()
+            .record(transition(customer("a@example.com"), TestState::Done))
+            .record(transition(
+                customer("a@example.com"),
+                customer("b@example.com"),
+            ));
+
+        let export = history.anonymized("salt").unwrap();
+        let ids: Vec<&str> = export
+            .transitions
+            .iter()
+            .flat_map(|t| [t.from.id.as_str(), t.to.id.as_str()])
+            .collect();
+
+        assert_eq!(ids[0], ids[2]);
+        assert_ne!(ids[2], ids[3]);
+        assert_ne!(
+            history.anonymized("other").unwrap().transitions[0].from.id,
+            ids[0]
+        );
+    }
+
+    #[test]
+    fn different_keys_give_unrelated_identifiers() {
+        let history = StateHistory::new().record(transition(TestState::Done, TestState::Done));
+
+        let a = history.anonymized("key-a").unwrap().transitions[0]
+            .to
+            .id
+            .clone();
+        let b = history.anonymized("key-b").unwrap().transitions[0]
+            .to
+            .id
+            .clone();
+
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        // About half the bits of unrelated digests differ
+        let differing: u32 = a
+            .as_bytes()
+            .chunks(2)
+            .zip(b.as_bytes().chunks(2))
+            .map(|(x, y)| {
+                let x = u8::from_str_radix(std::str::from_utf8(x).unwrap(), 16).unwrap();
+                let y = u8::from_str_radix(std::str::from_utf8(y).unwrap(), 16).unwrap();
+                (x ^ y).count_ones()
+            })
+            .sum();
+        assert!(
+            (64..=192).contains(&differing),
+            "{differing} of 256 bits differ"
+        );
+    }
+
+    /// State whose payload cannot be serialized
+    #[derive(Clone, PartialEq, Debug, Deserialize)]
+    struct Opaque;
+
+    impl Serialize for Opaque {
+        fn serialize<Ser: serde::Serializer>(&self, _: Ser) -> Result<Ser::Ok, Ser::Error> {
+            Err(serde::ser::Error::custom("opaque payload"))
+        }
+    }
+
+    impl State for Opaque {
+        fn name(&self) -> &str {
+            "Opaque"
+        }
+    }
+
+    #[test]
+    fn unserializable_payloads_fail_the_export() {
+        let history = StateHistory::new().record(StateTransition {
+            from: Opaque,
+            to: Opaque,
+            timestamp: Utc::now(),
+            attempt: 1,
+            sequence: 0,
+            forced: None,
+            correlation: BTreeMap::new(),
+        });
+
+        assert!(history.anonymized("key").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Fixed-capacity history for constrained environments.
+
+use crate::core::history::{StateHistory, StateTransition};
+use crate::core::State;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// History keeping only the latest `N` transitions in a ring buffer.
+///
+/// The buffer is stored inline, so recording never allocates beyond what
+/// the transitions themselves hold. Queries mirror [`StateHistory`] but
+/// only see the retained window; [`dropped`](Self::dropped) counts the
+/// transitions evicted to make room.
+///
+/// Machines keep the same window of their own history with
+/// `StateMachine::bound_history` or `StateMachineBuilder::bound_history`.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::core::{BoundedHistory, StateTransition};
+/// use mindset::state_enum;
+/// use chrono::Utc;
+///
+/// state_enum! {
+///     enum Led {
+///         Off,
+///         On,
+///     }
+/// }
+///
+/// let mut history = BoundedHistory::<Led, 2>::new();
+/// for (from, to) in [(Led::Off, Led::On), (Led::On, Led::Off), (Led::Off, Led::On)] {
+///     history.push(StateTransition {
+///         from,
+///         to,
+///         timestamp: Utc::now(),
+///         attempt: 1,
+///         correlation: Default::default(),
+///         sequence: 0,
+///         forced: None,
+///     });
+/// }
+///
+/// assert_eq!(history.len(), 2);
+/// assert_eq!(history.dropped(), 1);
+/// assert_eq!(history.get_path().collect::<Vec<_>>(), vec![&Led::On, &Led::Off, &Led::On]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BoundedHistory<S: State, const N: usize> {
+    slots: [Option<StateTransition<S>>; N],
+    /// Slot of the oldest retained transition
+    head: usize,
+    len: usize,
+    dropped: u64,
+    last_sequence: u64,
+}
+
+impl<S: State, const N: usize> Default for BoundedHistory<S, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: State, const N: usize> BoundedHistory<S, N> {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+            dropped: 0,
+            last_sequence: 0,
+        }
+    }
+
+    /// Maximum number of transitions retained
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of transitions retained
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no transition is retained
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of transitions evicted to make room for newer ones
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Record a transition in place, evicting the oldest one when full.
+    ///
+    /// The transition's `sequence` is set to follow the last recorded one,
+    /// evicted or not.
+    pub fn push(&mut self, mut transition: StateTransition<S>) {
+        self.last_sequence += 1;
+        transition.sequence = self.last_sequence;
+        if N == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.len == N {
+            self.slots[self.head] = Some(transition);
+            self.head = (self.head + 1) % N;
+            self.dropped += 1;
+        } else {
+            self.slots[(self.head + self.len) % N] = Some(transition);
+            self.len += 1;
+        }
+    }
+
+    /// Record a transition, returning a new history (pure)
+    pub fn record(&self, transition: StateTransition<S>) -> Self {
+        let mut history = self.clone();
+        history.push(transition);
+        history
+    }
+
+    /// Retained transitions, oldest first
+    pub fn transitions(&self) -> impl DoubleEndedIterator<Item = &StateTransition<S>> + '_ {
+        (0..self.len).filter_map(move |i| self.slots[(self.head + i) % N.max(1)].as_ref())
+    }
+
+    /// Path of states through the retained window: the source of the
+    /// oldest retained transition, then the target of each
+    pub fn get_path(&self) -> impl Iterator<Item = &S> + '_ {
+        self.transitions()
+            .next()
+            .map(|first| &first.from)
+            .into_iter()
+            .chain(self.transitions().map(|t| &t.to))
+    }
+
+    /// Duration from the oldest to the newest retained transition
+    pub fn duration(&self) -> Option<Duration> {
+        let first = self.transitions().next()?;
+        let last = self.transitions().next_back()?;
+        last.timestamp
+            .signed_duration_since(first.timestamp)
+            .to_std()
+            .ok()
+    }
+
+    /// Visits to states named `name` along the retained path (O(N))
+    pub fn visit_count(&self, name: &str) -> usize {
+        self.get_path().filter(|s| s.name() == name).count()
+    }
+
+    /// When a state named `name` was last entered within the retained
+    /// window (O(N))
+    pub fn last_entered_at(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.transitions()
+            .rev()
+            .find(|t| t.to.name() == name)
+            .map(|t| t.timestamp)
+    }
+
+    /// Time spent in states named `name` between retained transitions (O(N))
+    pub fn time_in_state(&self, name: &str) -> Duration {
+        self.transitions()
+            .zip(self.transitions().skip(1))
+            .filter(|(_, leaving)| leaving.from.name() == name)
+            .map(|(entering, leaving)| {
+                (leaving.timestamp - entering.timestamp)
+                    .to_std()
+                    .unwrap_or_default()
+            })
+            .sum()
+    }
+}
+
+impl<S: State, const N: usize> From<&StateHistory<S>> for BoundedHistory<S, N> {
+    /// Keep the latest `N` transitions of `history`, with their sequences
+    fn from(history: &StateHistory<S>) -> Self {
+        let mut bounded = Self::new();
+        for transition in history.transitions() {
+            bounded.last_sequence = transition.sequence.saturating_sub(1);
+            bounded.push(transition.clone());
+        }
+        bounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_enum;
+    use chrono::TimeDelta;
+
+    state_enum! {
+        enum Pump {
+            Idle,
+            Running,
+            Fault,
+        }
+    }
+
+    fn transition(from: Pump, to: Pump, at: DateTime<Utc>) -> StateTransition<Pump> {
+        StateTransition {
+            from,
+            to,
+            timestamp: at,
+            attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
+        }
+    }
+
+    #[test]
+    fn ring_buffer_keeps_latest_transitions() {
+        let start = Utc::now();
+        let mut history = BoundedHistory::<Pump, 2>::new();
+        history.push(transition(Pump::Idle, Pump::Running, start));
+        history.push(transition(
+            Pump::Running,
+            Pump::Fault,
+            start + TimeDelta::seconds(3),
+        ));
+        let history = history.record(transition(
+            Pump::Fault,
+            Pump::Idle,
+            start + TimeDelta::seconds(5),
+        ));
+
+        let sequences: Vec<_> = history.transitions().map(|t| t.sequence).collect();
+        assert_eq!(sequences, vec![2, 3]);
+        assert_eq!(history.dropped(), 1);
+        assert_eq!(history.visit_count("Idle"), 1);
+        assert_eq!(history.time_in_state("Fault"), Duration::from_secs(2));
+        assert_eq!(history.duration(), Some(Duration::from_secs(2)));
+        assert_eq!(
+            history.last_entered_at("Idle"),
+            Some(start + TimeDelta::seconds(5))
+        );
+    }
+
+    #[test]
+    fn converts_from_unbounded_history() {
+        let start = Utc::now();
+        let full = StateHistory::new()
+            .record(transition(Pump::Idle, Pump::Running, start))
+            .record(transition(Pump::Running, Pump::Idle, start));
+
+        let bounded = BoundedHistory::<Pump, 4>::from(&full);
+        assert_eq!(bounded.len(), 2);
+        assert_eq!(bounded.get_path().collect::<Vec<_>>(), full.get_path());
+
+        let empty = BoundedHistory::<Pump, 0>::from(&full);
+        assert!(empty.is_empty());
+        assert_eq!(empty.dropped(), 2);
+    }
+}
@@ -0,0 +1,110 @@
+//! Business calendars for time limits.
+//!
+//! Limits like "at most 3 business days in Review" should not run on
+//! weekends or holidays. A [`Calendar`] decides which days count; time
+//! measured against it only accumulates on business days. Days are UTC
+//! calendar days, so a calendar for another time zone shifts its dates
+//! accordingly.
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc, Weekday};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Decides which days count as business days.
+///
+/// Only [`is_business_day`](Self::is_business_day) is required; the other
+/// methods derive from it.
+pub trait Calendar: Debug + Send + Sync {
+    /// Whether `date` is a business day (pure)
+    fn is_business_day(&self, date: NaiveDate) -> bool;
+
+    /// Time between `start` and `end` that falls on business days (pure)
+    fn business_time_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        let mut total = chrono::Duration::zero();
+        let mut cursor = start;
+        while cursor < end {
+            let next_day = cursor.date_naive() + Days::new(1);
+            let until = next_day.and_time(Default::default()).and_utc().min(end);
+            if self.is_business_day(cursor.date_naive()) {
+                total += until - cursor;
+            }
+            cursor = until;
+        }
+        total.to_std().unwrap_or_default()
+    }
+
+    /// The same time of day `days` business days after `start` (pure).
+    ///
+    /// Never returns for a calendar without business days.
+    fn add_business_days(&self, start: DateTime<Utc>, days: u32) -> DateTime<Utc> {
+        let mut at = start;
+        let mut remaining = days;
+        while remaining > 0 {
+            at = at + Days::new(1);
+            if self.is_business_day(at.date_naive()) {
+                remaining -= 1;
+            }
+        }
+        at
+    }
+}
+
+/// Monday to Friday, minus configured holidays.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WeekdayCalendar {
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl WeekdayCalendar {
+    /// A calendar without holidays
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exclude `date` from business days
+    pub fn holiday(mut self, date: NaiveDate) -> Self {
+        self.holidays.insert(date);
+        self
+    }
+}
+
+impl Calendar for WeekdayCalendar {
+    fn is_business_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // March 2025: the 7th is a Friday, the 10th a Monday
+        Utc.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn weekends_and_holidays_do_not_count() {
+        let calendar = WeekdayCalendar::new();
+        assert_eq!(
+            calendar.business_time_between(at(7, 18), at(10, 6)),
+            Duration::from_secs(12 * 3600)
+        );
+
+        let calendar = calendar.holiday(NaiveDate::from_ymd_opt(2025, 3, 10).unwrap());
+        assert_eq!(
+            calendar.business_time_between(at(7, 18), at(11, 6)),
+            Duration::from_secs(12 * 3600)
+        );
+    }
+
+    #[test]
+    fn business_days_skip_the_weekend() {
+        assert_eq!(
+            WeekdayCalendar::new().add_business_days(at(7, 9), 3),
+            at(12, 9)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Localization-ready display names.
+//!
+//! User-facing applications need to render workflow status in multiple
+//! languages without string-matching `State::name()`. A `DisplayName` pairs
+//! a stable localization key with an English fallback and named arguments,
+//! and a `Localizer` turns it into text.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Localization key with fallback text and named arguments.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::core::DisplayName;
+/// use std::collections::HashMap;
+///
+/// let name = DisplayName::new("order.shipped", "Shipped").with_arg("carrier", "UPS");
+///
+/// let mut catalog = HashMap::new();
+/// catalog.insert("order.shipped".to_string(), "Versandt mit {carrier}".to_string());
+///
+/// assert_eq!(name.render(&catalog), "Versandt mit UPS");
+/// assert_eq!(name.render(&HashMap::new()), "Shipped");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayName {
+    /// Stable key used to look up translations
+    pub key: String,
+    /// Text used when no translation is available
+    pub fallback: String,
+    /// Named arguments available to the translation
+    pub args: BTreeMap<String, String>,
+}
+
+impl DisplayName {
+    /// Create a display name from a key and fallback text.
+    pub fn new(key: impl Into<String>, fallback: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            fallback: fallback.into(),
+            args: BTreeMap::new(),
+        }
+    }
+
+    /// Add a named argument for the translation.
+    pub fn with_arg(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.args.insert(name.into(), value.into());
+        self
+    }
+
+    /// Render using a localizer, falling back to the default text.
+    pub fn render<L: Localizer + ?Sized>(&self, localizer: &L) -> String {
+        localizer
+            .localize(self)
+            .unwrap_or_else(|| self.fallback.clone())
+    }
+}
+
+/// Source of translations for display names.
+///
+/// Implement this to integrate fluent, ICU, or an in-house catalog.
+/// Returning `None` makes the caller use the fallback text.
+pub trait Localizer {
+    /// Translate a display name, or return `None` if no translation exists.
+    fn localize(&self, name: &DisplayName) -> Option<String>;
+}
+
+/// Simple catalog: keys map to templates with `{arg}` placeholders.
+impl Localizer for HashMap<String, String> {
+    fn localize(&self, name: &DisplayName) -> Option<String> {
+        self.get(&name.key).map(|template| {
+            name.args
+                .iter()
+                .fold(template.clone(), |text, (arg, value)| {
+                    text.replace(&format!("{{{}}}", arg), value)
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Uppercase;
+
+    impl Localizer for Uppercase {
+        fn localize(&self, name: &DisplayName) -> Option<String> {
+            Some(name.fallback.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn render_uses_fallback_without_translation() {
+        let name = DisplayName::new("state.Draft", "Draft");
+        assert_eq!(name.render(&HashMap::new()), "Draft");
+    }
+
+    #[test]
+    fn render_substitutes_arguments() {
+        let name = DisplayName::new("retry", "Retrying").with_arg("count", "3");
+        let mut catalog = HashMap::new();
+        catalog.insert("retry".to_string(), "Attempt {count}".to_string());
+
+        assert_eq!(name.render(&catalog), "Attempt 3");
+    }
+
+    #[test]
+    fn custom_localizer_is_used() {
+        let name = DisplayName::new("state.Draft", "Draft");
+        assert_eq!(name.render(&Uppercase), "DRAFT");
+    }
+}
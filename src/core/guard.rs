@@ -48,6 +48,7 @@ use std::sync::Arc;
 /// ```
 pub struct Guard<S: State> {
     predicate: Arc<dyn Fn(&S) -> bool + Send + Sync>,
+    label: Option<String>,
     _phantom: PhantomData<S>,
 }
 
@@ -55,6 +56,7 @@ impl<S: State> Clone for Guard<S> {
     fn clone(&self) -> Self {
         Guard {
             predicate: Arc::clone(&self.predicate),
+            label: self.label.clone(),
             _phantom: PhantomData,
         }
     }
@@ -95,10 +97,50 @@ impl<S: State> Guard<S> {
     {
         Guard {
             predicate: Arc::new(predicate),
+            label: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Attach a human-readable label to the guard.
+    ///
+    /// Labels are used when explaining why a transition is unavailable,
+    /// so frontends can render an actionable message.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use mindset::core::{Guard, State};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    /// enum Door {
+    ///     Open,
+    ///     Closed,
+    /// }
+    ///
+    /// impl State for Door {
+    ///     fn name(&self) -> &str {
+    ///         match self {
+    ///             Self::Open => "Open",
+    ///             Self::Closed => "Closed",
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let guard = Guard::new(|s: &Door| matches!(s, Door::Closed)).with_label("door must be closed");
+    /// assert_eq!(guard.label(), Some("door must be closed"));
+    /// ```
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Get the guard's label, if one was set.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     /// Check if the guard allows transition from this state.
     ///
     /// This is a pure function that evaluates the predicate without
@@ -206,6 +248,15 @@ mod tests {
         assert_eq!(result1, result2);
     }
 
+    #[test]
+    fn guard_label_is_preserved_on_clone() {
+        let guard = Guard::new(|s: &TestState| !s.is_final()).with_label("not finished");
+        let cloned = guard.clone();
+
+        assert_eq!(cloned.label(), Some("not finished"));
+        assert_eq!(Guard::new(|_: &TestState| true).label(), None);
+    }
+
     #[test]
     fn guard_can_use_complex_predicates() {
         let guard =
@@ -0,0 +1,326 @@
+//! Serializable guard expressions.
+//!
+//! Closures cannot be stored in configuration files, so machines defined
+//! as data express their guards with [`GuardExpr`]: comparisons over fields
+//! of the serialized state combined with boolean operators. Expressions
+//! convert into ordinary [`Guard`]s, so closure guards remain available for
+//! anything the DSL cannot express.
+
+use super::guard::Guard;
+use super::state::State;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Declarative guard predicate over the fields of a state.
+///
+/// Fields are addressed by dot-separated paths into the state's JSON
+/// serialization. For an enum state, the first segment is the variant name,
+/// e.g. `Running.progress` for `Running { progress: u8 }`. The empty path
+/// addresses the whole value, so `eq("", json!("Idle"))` matches a unit
+/// variant and is displayed as `$`. Comparisons against a missing field are
+/// false.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::core::{GuardExpr, State};
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// enum Job {
+///     Running { progress: u8 },
+///     Done,
+/// }
+///
+/// impl State for Job {
+///     fn name(&self) -> &str {
+///         match self {
+///             Self::Running { .. } => "Running",
+///             Self::Done => "Done",
+///         }
+///     }
+/// }
+///
+/// let expr: GuardExpr =
+///     serde_json::from_str(r#"{"op": "ge", "field": "Running.progress", "value": 50}"#).unwrap();
+/// assert!(expr.eval(&Job::Running { progress: 80 }));
+/// assert!(!expr.eval(&Job::Done));
+///
+/// let guard = GuardExpr::ge("Running.progress", json!(50)).into_guard::<Job>();
+/// assert_eq!(guard.label(), Some("Running.progress >= 50"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GuardExpr {
+    /// Field equals value
+    Eq { field: String, value: Value },
+    /// Field differs from value
+    Ne { field: String, value: Value },
+    /// Field is less than value
+    Lt { field: String, value: Value },
+    /// Field is less than or equal to value
+    Le { field: String, value: Value },
+    /// Field is greater than value
+    Gt { field: String, value: Value },
+    /// Field is greater than or equal to value
+    Ge { field: String, value: Value },
+    /// Field is present
+    Exists { field: String },
+    /// All sub-expressions hold (true when empty)
+    All { of: Vec<GuardExpr> },
+    /// At least one sub-expression holds (false when empty)
+    Any { of: Vec<GuardExpr> },
+    /// Sub-expression does not hold
+    Not { expr: Box<GuardExpr> },
+}
+
+impl GuardExpr {
+    /// `field == value`
+    pub fn eq(field: impl Into<String>, value: Value) -> Self {
+        Self::Eq {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// `field != value`
+    pub fn ne(field: impl Into<String>, value: Value) -> Self {
+        Self::Ne {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// `field < value`
+    pub fn lt(field: impl Into<String>, value: Value) -> Self {
+        Self::Lt {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// `field <= value`
+    pub fn le(field: impl Into<String>, value: Value) -> Self {
+        Self::Le {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// `field > value`
+    pub fn gt(field: impl Into<String>, value: Value) -> Self {
+        Self::Gt {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// `field >= value`
+    pub fn ge(field: impl Into<String>, value: Value) -> Self {
+        Self::Ge {
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// Field is present
+    pub fn exists(field: impl Into<String>) -> Self {
+        Self::Exists {
+            field: field.into(),
+        }
+    }
+
+    /// Conjunction of expressions
+    pub fn all(of: Vec<GuardExpr>) -> Self {
+        Self::All { of }
+    }
+
+    /// Disjunction of expressions
+    pub fn any(of: Vec<GuardExpr>) -> Self {
+        Self::Any { of }
+    }
+
+    /// Negation of an expression
+    pub fn negate(expr: GuardExpr) -> Self {
+        Self::Not {
+            expr: Box::new(expr),
+        }
+    }
+
+    /// Evaluate the expression against a state (pure).
+    ///
+    /// A state that fails to serialize satisfies no comparison.
+    pub fn eval<S: State>(&self, state: &S) -> bool {
+        let root = serde_json::to_value(state).unwrap_or(Value::Null);
+        self.eval_value(&root)
+    }
+
+    /// Convert into a [`Guard`] labelled with the expression's text.
+    pub fn into_guard<S: State + 'static>(self) -> Guard<S> {
+        let label = self.to_string();
+        Guard::new(move |state: &S| self.eval(state)).with_label(label)
+    }
+
+    fn eval_value(&self, root: &Value) -> bool {
+        let compare = |field: &str, value: &Value, accept: fn(Ordering) -> bool| {
+            lookup(root, field)
+                .and_then(|actual| compare_values(actual, value))
+                .is_some_and(accept)
+        };
+
+        match self {
+            Self::Eq { field, value } => compare(field, value, Ordering::is_eq),
+            Self::Ne { field, value } => lookup(root, field)
+                .is_some_and(|actual| compare_values(actual, value) != Some(Ordering::Equal)),
+            Self::Lt { field, value } => compare(field, value, Ordering::is_lt),
+            Self::Le { field, value } => compare(field, value, Ordering::is_le),
+            Self::Gt { field, value } => compare(field, value, Ordering::is_gt),
+            Self::Ge { field, value } => compare(field, value, Ordering::is_ge),
+            Self::Exists { field } => lookup(root, field).is_some(),
+            Self::All { of } => of.iter().all(|e| e.eval_value(root)),
+            Self::Any { of } => of.iter().any(|e| e.eval_value(root)),
+            Self::Not { expr } => !expr.eval_value(root),
+        }
+    }
+}
+
+/// Resolve a dot-separated path; the empty path is the value itself.
+fn lookup<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(root);
+    }
+    path.split('.')
+        .try_fold(root, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Order two JSON values of the same kind. Numbers compare numerically,
+/// strings lexicographically; other kinds are only ever equal or unequal.
+fn compare_values(actual: &Value, expected: &Value) -> Option<Ordering> {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if actual == expected => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+impl fmt::Display for GuardExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, of: &[GuardExpr], op: &str| {
+            write!(f, "(")?;
+            for (i, expr) in of.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {op} ")?;
+                }
+                write!(f, "{expr}")?;
+            }
+            write!(f, ")")
+        };
+
+        // The empty path (the whole state) is rendered as `$`
+        let path = |field: &str| {
+            if field.is_empty() {
+                "$".to_string()
+            } else {
+                field.to_string()
+            }
+        };
+
+        match self {
+            Self::Eq { field, value } => write!(f, "{} == {value}", path(field)),
+            Self::Ne { field, value } => write!(f, "{} != {value}", path(field)),
+            Self::Lt { field, value } => write!(f, "{} < {value}", path(field)),
+            Self::Le { field, value } => write!(f, "{} <= {value}", path(field)),
+            Self::Gt { field, value } => write!(f, "{} > {value}", path(field)),
+            Self::Ge { field, value } => write!(f, "{} >= {value}", path(field)),
+            Self::Exists { field } => write!(f, "exists({})", path(field)),
+            Self::All { of } if of.is_empty() => write!(f, "true"),
+            Self::Any { of } if of.is_empty() => write!(f, "false"),
+            Self::All { of } => join(f, of, "&&"),
+            Self::Any { of } => join(f, of, "||"),
+            Self::Not { expr } => write!(f, "!({expr})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Idle,
+        Running { progress: u8, owner: String },
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Idle => "Idle",
+                Self::Running { .. } => "Running",
+            }
+        }
+    }
+
+    fn running(progress: u8) -> TestState {
+        TestState::Running {
+            progress,
+            owner: "ops".to_string(),
+        }
+    }
+
+    #[test]
+    fn comparisons_over_nested_fields() {
+        assert!(GuardExpr::ge("Running.progress", json!(50)).eval(&running(50)));
+        assert!(!GuardExpr::gt("Running.progress", json!(50)).eval(&running(50)));
+        assert!(GuardExpr::lt("Running.progress", json!(50.5)).eval(&running(50)));
+        assert!(GuardExpr::eq("Running.owner", json!("ops")).eval(&running(1)));
+        assert!(GuardExpr::eq("", json!("Idle")).eval(&TestState::Idle));
+    }
+
+    #[test]
+    fn missing_fields_fail_comparisons() {
+        assert!(!GuardExpr::ge("Running.progress", json!(0)).eval(&TestState::Idle));
+        assert!(!GuardExpr::ne("Running.progress", json!(0)).eval(&TestState::Idle));
+        assert!(!GuardExpr::exists("Running").eval(&TestState::Idle));
+    }
+
+    #[test]
+    fn combinators_compose() {
+        let expr = GuardExpr::all(vec![
+            GuardExpr::exists("Running"),
+            GuardExpr::negate(GuardExpr::lt("Running.progress", json!(10))),
+        ]);
+
+        assert!(expr.eval(&running(10)));
+        assert!(!expr.eval(&running(9)));
+        assert!(GuardExpr::any(vec![GuardExpr::eq("", json!("Idle")), expr]).eval(&TestState::Idle));
+    }
+
+    #[test]
+    fn round_trips_through_json_and_labels_guard() {
+        let expr = GuardExpr::any(vec![
+            GuardExpr::eq("", json!("Idle")),
+            GuardExpr::ge("Running.progress", json!(90)),
+        ]);
+        let json = serde_json::to_string(&expr).unwrap();
+        let parsed: GuardExpr = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, expr);
+
+        let guard = parsed.into_guard::<TestState>();
+        assert_eq!(
+            guard.label(),
+            Some(r#"($ == "Idle" || Running.progress >= 90)"#)
+        );
+        assert!(guard.check(&running(95)));
+        assert!(!guard.check(&running(5)));
+    }
+}
@@ -3,9 +3,11 @@
 //! Provides immutable tracking of state machine transitions over time,
 //! following functional programming principles.
 
+use super::snapshot::DataSnapshot;
 use super::state::State;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 
 /// Record of a single state transition.
@@ -40,6 +42,9 @@ use std::time::Duration;
 ///     to: TaskState::Running,
 ///     timestamp: Utc::now(),
 ///     attempt: 1,
+///     correlation: Default::default(),
+///     sequence: 0,
+///     forced: None,
 /// };
 /// ```
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -53,6 +58,73 @@ pub struct StateTransition<S: State> {
     pub timestamp: DateTime<Utc>,
     /// The attempt number for this transition (for retry logic)
     pub attempt: usize,
+    /// Position in the history, starting at 1 and strictly increasing even
+    /// when timestamps are not. Assigned by [`StateHistory::record`].
+    #[serde(default)]
+    pub sequence: u64,
+    /// Set when an operator forced this transition, bypassing the graph
+    #[serde(default)]
+    pub forced: Option<ForcedTransition>,
+    /// Caller-supplied correlation data (request id, user id, ...) for
+    /// joining history with external logs
+    #[serde(default)]
+    pub correlation: BTreeMap<String, String>,
+}
+
+/// Audit record of an operator-forced transition.
+///
+/// Forced transitions bypass guards and the transition graph, so history
+/// keeps who forced them and why.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForcedTransition {
+    /// Why the transition was forced
+    pub reason: String,
+    /// Identity of the operator who forced it
+    pub operator: String,
+}
+
+/// Administrative event recorded alongside transitions.
+///
+/// These events do not change the machine's state, so they are kept
+/// separate from transitions and do not appear in `get_path()`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HistoryEvent {
+    /// The machine was paused
+    Paused {
+        reason: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// The machine was resumed after a pause
+    Resumed { timestamp: DateTime<Utc> },
+    /// The machine completed in a state violating an invariant
+    InvariantViolated {
+        invariant: String,
+        state: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// A transition was added to the live machine
+    TransitionAdded {
+        from: String,
+        to: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// A transition was removed from the live machine
+    TransitionRemoved {
+        from: String,
+        to: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// A transition's action acquired an external lock
+    LockAcquired {
+        key: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// An external lock was released, after the action finished or was
+    /// cancelled
+    LockReleased {
+        key: String,
+        timestamp: DateTime<Utc>,
+    },
 }
 
 /// Ordered history of state transitions.
@@ -91,6 +163,9 @@ pub struct StateTransition<S: State> {
 ///     to: WorkState::Middle,
 ///     timestamp: Utc::now(),
 ///     attempt: 1,
+///     correlation: Default::default(),
+///     sequence: 0,
+///     forced: None,
 /// };
 ///
 /// let history = history.record(transition1);
@@ -100,6 +175,9 @@ pub struct StateTransition<S: State> {
 ///     to: WorkState::End,
 ///     timestamp: Utc::now(),
 ///     attempt: 1,
+///     correlation: Default::default(),
+///     sequence: 0,
+///     forced: None,
 /// };
 ///
 /// let history = history.record(transition2);
@@ -108,9 +186,68 @@ pub struct StateTransition<S: State> {
 /// assert_eq!(path.len(), 3); // Start -> Middle -> End
 /// ```
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(bound = "")]
+#[serde(bound = "", from = "StoredHistory<S>")]
 pub struct StateHistory<S: State> {
     transitions: Vec<StateTransition<S>>,
+    #[serde(default)]
+    events: Vec<HistoryEvent>,
+    /// Data snapshots of sampled transitions, in sequence order
+    #[serde(default)]
+    snapshots: Vec<DataSnapshot>,
+    /// Per-state aggregates, rebuilt on load rather than serialized
+    #[serde(skip)]
+    index: HashMap<String, StateVisits>,
+    /// Sequence of the last recorded transition, retained or not
+    #[serde(skip)]
+    last_sequence: u64,
+}
+
+/// Serialized form of a [`StateHistory`].
+#[derive(Deserialize)]
+#[serde(bound = "")]
+struct StoredHistory<S: State> {
+    transitions: Vec<StateTransition<S>>,
+    #[serde(default)]
+    events: Vec<HistoryEvent>,
+    #[serde(default)]
+    snapshots: Vec<DataSnapshot>,
+}
+
+impl<S: State> From<StoredHistory<S>> for StateHistory<S> {
+    fn from(stored: StoredHistory<S>) -> Self {
+        Self::from_parts(stored.transitions, stored.events).with_snapshots(stored.snapshots)
+    }
+}
+
+/// Aggregates of the visits to one state name.
+#[derive(Clone, Debug, Default)]
+struct StateVisits {
+    visits: usize,
+    last_entered_at: Option<DateTime<Utc>>,
+    time_in_state: Duration,
+}
+
+/// Fold `transition` into `index`, given the timestamp of the previous
+/// transition (when the source state was entered).
+fn index_transition<S: State>(
+    index: &mut HashMap<String, StateVisits>,
+    transition: &StateTransition<S>,
+    previous: Option<&StateTransition<S>>,
+) {
+    let from = index.entry(transition.from.name().to_string()).or_default();
+    match previous {
+        // Clock jumps backwards count as no time spent
+        Some(previous) => {
+            from.time_in_state += (transition.timestamp - previous.timestamp)
+                .to_std()
+                .unwrap_or_default()
+        }
+        // The first source state is visited without being entered
+        None => from.visits += 1,
+    }
+    let to = index.entry(transition.to.name().to_string()).or_default();
+    to.visits += 1;
+    to.last_entered_at = Some(transition.timestamp);
 }
 
 impl<S: State> Default for StateHistory<S> {
@@ -120,6 +257,40 @@ impl<S: State> Default for StateHistory<S> {
 }
 
 impl<S: State> StateHistory<S> {
+    /// Assemble a history from already ordered entries.
+    pub(crate) fn from_parts(
+        transitions: Vec<StateTransition<S>>,
+        events: Vec<HistoryEvent>,
+    ) -> Self {
+        let mut transitions = transitions;
+        let mut sequence = 0;
+        for transition in &mut transitions {
+            // Checkpoints written before sequences existed hold zeros
+            sequence = transition.sequence.max(sequence + 1);
+            transition.sequence = sequence;
+        }
+        let mut index = HashMap::new();
+        let mut previous = None;
+        for transition in &transitions {
+            index_transition(&mut index, transition, previous);
+            previous = Some(transition);
+        }
+        Self {
+            transitions,
+            events,
+            snapshots: Vec::new(),
+            index,
+            last_sequence: sequence,
+        }
+    }
+
+    /// Replace the data snapshots, e.g. when assembling a history with
+    /// [`from_parts`](Self::from_parts).
+    pub(crate) fn with_snapshots(mut self, snapshots: Vec<DataSnapshot>) -> Self {
+        self.snapshots = snapshots;
+        self
+    }
+
     /// Create a new empty history.
     ///
     /// # Example
@@ -141,13 +312,18 @@ impl<S: State> StateHistory<S> {
     pub fn new() -> Self {
         Self {
             transitions: Vec::new(),
+            events: Vec::new(),
+            snapshots: Vec::new(),
+            index: HashMap::new(),
+            last_sequence: 0,
         }
     }
 
     /// Record a transition, returning a new history.
     ///
     /// This is a pure function - it does not mutate the existing history
-    /// but returns a new one with the transition added.
+    /// but returns a new one with the transition added. The transition's
+    /// `sequence` is set to follow the last recorded one.
     ///
     /// # Example
     ///
@@ -174,6 +350,9 @@ impl<S: State> StateHistory<S> {
     ///     to: Step::B,
     ///     timestamp: Utc::now(),
     ///     attempt: 1,
+    ///     correlation: Default::default(),
+    ///     sequence: 0,
+    ///     forced: None,
     /// };
     ///
     /// let new_history = history.record(transition);
@@ -181,9 +360,116 @@ impl<S: State> StateHistory<S> {
     /// assert_eq!(history.transitions().len(), 0); // Original unchanged
     /// ```
     pub fn record(&self, transition: StateTransition<S>) -> Self {
-        let mut transitions = self.transitions.clone();
-        transitions.push(transition);
-        Self { transitions }
+        let mut history = self.clone();
+        history.push(transition);
+        history
+    }
+
+    /// Record a transition in place, setting its `sequence`
+    pub(crate) fn push(&mut self, mut transition: StateTransition<S>) {
+        self.last_sequence += 1;
+        transition.sequence = self.last_sequence;
+        index_transition(&mut self.index, &transition, self.transitions.last());
+        self.transitions.push(transition);
+    }
+
+    /// Record an administrative event, returning a new history.
+    ///
+    /// Events are kept separate from transitions, so paths and durations
+    /// are unaffected.
+    pub fn record_event(&self, event: HistoryEvent) -> Self {
+        let mut history = self.clone();
+        history.push_event(event);
+        history
+    }
+
+    /// Record an administrative event in place
+    pub(crate) fn push_event(&mut self, event: HistoryEvent) {
+        self.events.push(event);
+    }
+
+    /// Sequence of the last recorded transition, including evicted ones
+    pub(crate) fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Keep only the latest `capacity` transitions, with the snapshots of
+    /// the retained ones, and the latest `capacity` events, updating the
+    /// per-state aggregates in place
+    pub(crate) fn retain_latest(&mut self, capacity: usize) {
+        let evicted = self.transitions.len().saturating_sub(capacity);
+        for _ in 0..evicted {
+            self.evict_oldest();
+        }
+        let oldest = self.transitions.first().map_or(u64::MAX, |t| t.sequence);
+        self.snapshots.retain(|s| s.sequence >= oldest);
+        let stale = self.events.len().saturating_sub(capacity);
+        self.events.drain(..stale);
+    }
+
+    /// Drop the oldest transition, taking it out of the aggregates so they
+    /// match a history starting at the next one
+    fn evict_oldest(&mut self) {
+        let evicted = self.transitions.remove(0);
+        // The evicted transition's source was visited as the first state
+        // and its target entered
+        for name in [evicted.from.name(), evicted.to.name()] {
+            if let Some(visits) = self.index.get_mut(name) {
+                visits.visits = visits.visits.saturating_sub(1);
+            }
+        }
+        // Its successor's source becomes the first state: visited, but
+        // with no recorded start to count time from
+        if let Some(next) = self.transitions.first() {
+            let spent = (next.timestamp - evicted.timestamp)
+                .to_std()
+                .unwrap_or_default();
+            let from = self.index.entry(next.from.name().to_string()).or_default();
+            from.visits += 1;
+            from.time_in_state = from.time_in_state.saturating_sub(spent);
+        }
+        let name = evicted.to.name();
+        let entered = self
+            .transitions
+            .iter()
+            .rev()
+            .find(|t| t.to.name() == name)
+            .map(|t| t.timestamp);
+        if let Some(visits) = self.index.get_mut(name) {
+            visits.last_entered_at = entered;
+        }
+        self.index.retain(|_, visits| visits.visits > 0);
+    }
+
+    /// Get all administrative events in the order they were recorded.
+    pub fn events(&self) -> &[HistoryEvent] {
+        &self.events
+    }
+
+    /// Record a data snapshot, returning a new history.
+    ///
+    /// Like `record`, this is pure. The snapshot replaces an earlier one
+    /// for the same transition.
+    pub fn record_snapshot(&self, snapshot: DataSnapshot) -> Self {
+        let mut history = self.clone();
+        history.push_snapshot(snapshot);
+        history
+    }
+
+    /// Record a data snapshot in place, replacing one for the same sequence
+    pub(crate) fn push_snapshot(&mut self, snapshot: DataSnapshot) {
+        self.snapshots.retain(|s| s.sequence != snapshot.sequence);
+        self.snapshots.push(snapshot);
+    }
+
+    /// Get all data snapshots in the order they were recorded.
+    pub fn snapshots(&self) -> &[DataSnapshot] {
+        &self.snapshots
+    }
+
+    /// The data snapshot recorded for the transition with `sequence`
+    pub fn snapshot_for(&self, sequence: u64) -> Option<&DataSnapshot> {
+        self.snapshots.iter().find(|s| s.sequence == sequence)
     }
 
     /// Get the path of states traversed.
@@ -218,6 +504,9 @@ impl<S: State> StateHistory<S> {
     ///     to: Phase::Two,
     ///     timestamp: Utc::now(),
     ///     attempt: 1,
+    ///     correlation: Default::default(),
+    ///     sequence: 0,
+    ///     forced: None,
     /// });
     ///
     /// history = history.record(StateTransition {
@@ -225,6 +514,9 @@ impl<S: State> StateHistory<S> {
     ///     to: Phase::Three,
     ///     timestamp: Utc::now(),
     ///     attempt: 1,
+    ///     correlation: Default::default(),
+    ///     sequence: 0,
+    ///     forced: None,
     /// });
     ///
     /// let path = history.get_path();
@@ -277,6 +569,9 @@ impl<S: State> StateHistory<S> {
     ///     to: State1::B,
     ///     timestamp: start,
     ///     attempt: 1,
+    ///     correlation: Default::default(),
+    ///     sequence: 0,
+    ///     forced: None,
     /// });
     ///
     /// assert!(history.duration().is_some());
@@ -319,6 +614,9 @@ impl<S: State> StateHistory<S> {
     ///     to: MyState::Y,
     ///     timestamp: Utc::now(),
     ///     attempt: 1,
+    ///     correlation: Default::default(),
+    ///     sequence: 0,
+    ///     forced: None,
     /// });
     ///
     /// assert_eq!(history.transitions().len(), 1);
@@ -326,6 +624,29 @@ impl<S: State> StateHistory<S> {
     pub fn transitions(&self) -> &[StateTransition<S>] {
         &self.transitions
     }
+
+    /// Number of times the path visited a state named `name` (O(1)).
+    ///
+    /// The state the history starts from counts as one visit.
+    pub fn visit_count(&self, name: &str) -> usize {
+        self.index.get(name).map_or(0, |v| v.visits)
+    }
+
+    /// When a state named `name` was last entered by a transition (O(1)).
+    pub fn last_entered_at(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.index.get(name).and_then(|v| v.last_entered_at)
+    }
+
+    /// Total time spent in states named `name` before leaving them (O(1)).
+    ///
+    /// Time before the first transition, which has no recorded start, and
+    /// time in the last entered state, which has not been left, are not
+    /// included.
+    pub fn time_in_state(&self, name: &str) -> Duration {
+        self.index
+            .get(name)
+            .map_or(Duration::ZERO, |v| v.time_in_state)
+    }
 }
 
 #[cfg(test)]
@@ -377,6 +698,9 @@ mod tests {
             to: TestState::Processing,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let history = history.record(transition);
@@ -393,6 +717,9 @@ mod tests {
             to: TestState::Processing,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let new_history = history.record(transition);
@@ -410,6 +737,9 @@ mod tests {
             to: TestState::Processing,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         history = history.record(transition1);
@@ -419,6 +749,9 @@ mod tests {
             to: TestState::Complete,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         history = history.record(transition2);
@@ -440,6 +773,9 @@ mod tests {
             to: TestState::Processing,
             timestamp: start,
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let history = history.record(transition1);
@@ -451,6 +787,9 @@ mod tests {
             to: TestState::Complete,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let history = history.record(transition2);
@@ -469,6 +808,9 @@ mod tests {
             to: TestState::Processing,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         history = history.record(transition);
@@ -491,6 +833,9 @@ mod tests {
             to: TestState::Processing,
             timestamp,
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let history = StateHistory::new().record(transition);
@@ -500,6 +845,18 @@ mod tests {
         assert_eq!(duration.unwrap(), std::time::Duration::from_secs(0));
     }
 
+    #[test]
+    fn record_event_keeps_transitions_separate() {
+        let history: StateHistory<TestState> = StateHistory::new();
+        let history = history.record_event(HistoryEvent::Resumed {
+            timestamp: Utc::now(),
+        });
+
+        assert_eq!(history.events().len(), 1);
+        assert!(history.transitions().is_empty());
+        assert!(history.get_path().is_empty());
+    }
+
     #[test]
     fn attempt_field_is_tracked() {
         let transition = StateTransition {
@@ -507,8 +864,121 @@ mod tests {
             to: TestState::Processing,
             timestamp: Utc::now(),
             attempt: 3,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         assert_eq!(transition.attempt, 3);
     }
+
+    #[test]
+    fn state_queries_use_the_index() {
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        let step = |from: TestState, to: TestState, secs| StateTransition {
+            from,
+            to,
+            timestamp: at(secs),
+            attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
+        };
+        let history = StateHistory::new()
+            .record(step(TestState::Initial, TestState::Processing, 0))
+            .record(step(TestState::Processing, TestState::Initial, 10))
+            .record(step(TestState::Initial, TestState::Processing, 15))
+            .record(step(TestState::Processing, TestState::Complete, 45));
+
+        assert_eq!(history.visit_count("Initial"), 2);
+        assert_eq!(history.visit_count("Processing"), 2);
+        assert_eq!(history.visit_count("Failed"), 0);
+        assert_eq!(history.last_entered_at("Processing"), Some(at(15)));
+        assert_eq!(history.last_entered_at("Initial"), Some(at(10)));
+        assert_eq!(history.time_in_state("Processing"), Duration::from_secs(40));
+        assert_eq!(history.time_in_state("Complete"), Duration::ZERO);
+
+        let restored: StateHistory<TestState> =
+            serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert!(!serde_json::to_string(&history).unwrap().contains("index"));
+        assert_eq!(restored.visit_count("Processing"), 2);
+        assert_eq!(restored.time_in_state("Initial"), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn sequences_stay_ordered_when_the_clock_jumps_back() {
+        let now = Utc::now();
+        let step = |from: TestState, to: TestState, timestamp| StateTransition {
+            from,
+            to,
+            timestamp,
+            attempt: 1,
+            sequence: 0,
+            correlation: Default::default(),
+            forced: None,
+        };
+        let history = StateHistory::new()
+            .record(step(TestState::Initial, TestState::Processing, now))
+            .record(step(
+                TestState::Processing,
+                TestState::Complete,
+                now - chrono::Duration::seconds(30),
+            ));
+
+        let sequences: Vec<_> = history.transitions().iter().map(|t| t.sequence).collect();
+        assert_eq!(sequences, vec![1, 2]);
+
+        let mut legacy = serde_json::to_value(&history).unwrap();
+        for transition in legacy["transitions"].as_array_mut().unwrap() {
+            transition.as_object_mut().unwrap().remove("sequence");
+        }
+        let restored: StateHistory<TestState> = serde_json::from_value(legacy).unwrap();
+        assert_eq!(restored.transitions()[1].sequence, 2);
+    }
+
+    #[test]
+    fn retaining_the_latest_transitions_keeps_the_index_consistent() {
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+        let step = |from: TestState, to: TestState, secs| StateTransition {
+            from,
+            to,
+            timestamp: at(secs),
+            attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
+        };
+        let mut history = StateHistory::new();
+        for (from, to, secs) in [
+            (TestState::Initial, TestState::Processing, 0),
+            (TestState::Processing, TestState::Initial, 10),
+            (TestState::Initial, TestState::Processing, 15),
+            (TestState::Processing, TestState::Failed, 45),
+            (TestState::Failed, TestState::Complete, 50),
+        ] {
+            history.push(step(from, to, secs));
+            history.push_event(HistoryEvent::Resumed {
+                timestamp: at(secs),
+            });
+            history.retain_latest(2);
+        }
+
+        let rebuilt = StateHistory::from_parts(history.transitions().to_vec(), Vec::new());
+        for name in ["Initial", "Processing", "Complete", "Failed"] {
+            assert_eq!(history.visit_count(name), rebuilt.visit_count(name));
+            assert_eq!(history.last_entered_at(name), rebuilt.last_entered_at(name));
+            assert_eq!(history.time_in_state(name), rebuilt.time_in_state(name));
+        }
+        assert_eq!(history.visit_count("Initial"), 0);
+        assert_eq!(history.visit_count("Processing"), 1);
+        assert_eq!(history.time_in_state("Failed"), Duration::from_secs(5));
+        assert_eq!(history.events().len(), 2);
+        assert_eq!(history.last_sequence(), 5);
+
+        history.retain_latest(0);
+        history.push(step(TestState::Complete, TestState::Complete, 60));
+        assert_eq!(history.transitions()[0].sequence, 6);
+    }
 }
@@ -2,16 +2,32 @@
 //!
 //! This module contains the pure functional core of the state machine:
 //! - State definitions via the `State` trait
-//! - Guard predicates for transition control
-//! - Immutable history tracking
+//! - Guard predicates for transition control, as closures or serializable
+//!   expressions
+//! - Immutable history tracking, unbounded or in a fixed-size ring buffer,
+//!   with optional snapshots of the machine's data
 //!
 //! All logic in this module is pure (no side effects), following
 //! the "pure core, imperative shell" philosophy.
 
+mod anonymize;
+mod bounded;
+mod calendar;
+mod display;
 mod guard;
+mod guard_expr;
 mod history;
+mod scrub;
+mod snapshot;
 mod state;
 
+pub use anonymize::{AnonymizedEvent, AnonymizedHistory, AnonymizedState, AnonymizedTransition};
+pub use bounded::BoundedHistory;
+pub use calendar::{Calendar, WeekdayCalendar};
+pub use display::{DisplayName, Localizer};
 pub use guard::Guard;
-pub use history::{StateHistory, StateTransition};
-pub use state::State;
+pub use guard_expr::GuardExpr;
+pub use history::{ForcedTransition, HistoryEvent, StateHistory, StateTransition};
+pub use scrub::Scrubber;
+pub use snapshot::{DataSnapshot, SnapshotPolicy};
+pub use state::{State, StateDisplay, StateRef};
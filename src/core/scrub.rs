@@ -0,0 +1,350 @@
+//! Redaction of sensitive data in history.
+//!
+//! A [`Scrubber`] holds user-supplied functions that redact sensitive
+//! fields from states and free-text fields (pause and force reasons,
+//! correlation values, data snapshots, retry feedback and signal payloads)
+//! before they are written into history and checkpoints. History is what long-lived
+//! workflows retain and export, so scrubbing it keeps personal data out of
+//! checkpoints while the live current state stays intact for resuming.
+
+use super::history::{HistoryEvent, StateHistory, StateTransition};
+use super::snapshot::DataSnapshot;
+use super::state::State;
+use std::sync::Arc;
+
+type StateFn<S> = Arc<dyn Fn(&S) -> S + Send + Sync>;
+type TextFn = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Redaction functions applied to history entries.
+///
+/// Both functions default to the identity.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::core::{Scrubber, State, StateHistory, StateTransition};
+/// use serde::{Deserialize, Serialize};
+/// use chrono::Utc;
+///
+/// #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// enum Signup {
+///     Started { email: String },
+///     Done,
+/// }
+///
+/// impl State for Signup {
+///     fn name(&self) -> &str {
+///         match self {
+///             Self::Started { .. } => "Started",
+///             Self::Done => "Done",
+///         }
+///     }
+/// }
+///
+/// let scrubber = Scrubber::new().states(|s: &Signup| match s {
+///     Signup::Started { .. } => Signup::Started { email: "<redacted>".into() },
+///     other => other.clone(),
+/// });
+///
+/// let history = StateHistory::new().record(StateTransition {
+///     from: Signup::Started { email: "a@example.com".into() },
+///     to: Signup::Done,
+///     timestamp: Utc::now(),
+///     attempt: 1,
+///     sequence: 0,
+///     forced: None,
+///     correlation: Default::default(),
+/// });
+///
+/// let scrubbed = scrubber.scrub_history(&history);
+/// assert_eq!(
+///     scrubbed.transitions()[0].from,
+///     Signup::Started { email: "<redacted>".into() }
+/// );
+/// ```
+pub struct Scrubber<S: State> {
+    state: Option<StateFn<S>>,
+    text: Option<TextFn>,
+}
+
+impl<S: State> Clone for Scrubber<S> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            text: self.text.clone(),
+        }
+    }
+}
+
+impl<S: State> Default for Scrubber<S> {
+    fn default() -> Self {
+        Self {
+            state: None,
+            text: None,
+        }
+    }
+}
+
+impl<S: State> Scrubber<S> {
+    /// Create a scrubber that changes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redact states recorded in history
+    pub fn states<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&S) -> S + Send + Sync + 'static,
+    {
+        self.state = Some(Arc::new(f));
+        self
+    }
+
+    /// Redact free-text fields such as pause and force reasons, correlation
+    /// values and the strings in signal payloads
+    pub fn text<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.text = Some(Arc::new(f));
+        self
+    }
+
+    /// Redact a single state (pure)
+    pub fn scrub_state(&self, state: &S) -> S {
+        match &self.state {
+            Some(f) => f(state),
+            None => state.clone(),
+        }
+    }
+
+    /// Redact a free-text field (pure)
+    pub fn scrub_text(&self, text: &str) -> String {
+        match &self.text {
+            Some(f) => f(text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Redact every string in a JSON value with the text function (pure)
+    pub fn scrub_json(&self, value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+
+        match value {
+            Value::String(text) => Value::String(self.scrub_text(text)),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.scrub_json(v)).collect()),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.scrub_json(v)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    /// Redact a transition record (pure)
+    pub fn scrub_transition(&self, transition: StateTransition<S>) -> StateTransition<S> {
+        StateTransition {
+            from: self.scrub_state(&transition.from),
+            to: self.scrub_state(&transition.to),
+            forced: transition.forced.map(|mut forced| {
+                forced.reason = self.scrub_text(&forced.reason);
+                forced
+            }),
+            correlation: transition
+                .correlation
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = self.scrub_text(&value);
+                    (key, value)
+                })
+                .collect(),
+            ..transition
+        }
+    }
+
+    /// Redact an administrative event (pure)
+    ///
+    /// Pause reasons, lock keys and the display names of states violating
+    /// invariants go through the text function. State names, as in
+    /// transition changes, identify variants only and are kept.
+    pub fn scrub_event(&self, event: HistoryEvent) -> HistoryEvent {
+        match event {
+            HistoryEvent::Paused { reason, timestamp } => HistoryEvent::Paused {
+                reason: self.scrub_text(&reason),
+                timestamp,
+            },
+            HistoryEvent::Resumed { timestamp } => HistoryEvent::Resumed { timestamp },
+            HistoryEvent::InvariantViolated {
+                invariant,
+                state,
+                timestamp,
+            } => HistoryEvent::InvariantViolated {
+                invariant,
+                state: self.scrub_text(&state),
+                timestamp,
+            },
+            HistoryEvent::TransitionAdded {
+                from,
+                to,
+                timestamp,
+            } => HistoryEvent::TransitionAdded {
+                from,
+                to,
+                timestamp,
+            },
+            HistoryEvent::TransitionRemoved {
+                from,
+                to,
+                timestamp,
+            } => HistoryEvent::TransitionRemoved {
+                from,
+                to,
+                timestamp,
+            },
+            HistoryEvent::LockAcquired { key, timestamp } => HistoryEvent::LockAcquired {
+                key: self.scrub_text(&key),
+                timestamp,
+            },
+            HistoryEvent::LockReleased { key, timestamp } => HistoryEvent::LockReleased {
+                key: self.scrub_text(&key),
+                timestamp,
+            },
+        }
+    }
+
+    /// Redact the data of a snapshot with the text function (pure)
+    pub fn scrub_snapshot(&self, snapshot: DataSnapshot) -> DataSnapshot {
+        DataSnapshot {
+            data: snapshot.data.map(|data| self.scrub_text(&data)),
+            ..snapshot
+        }
+    }
+
+    /// Redact every entry of a history (pure)
+    pub fn scrub_history(&self, history: &StateHistory<S>) -> StateHistory<S> {
+        StateHistory::from_parts(
+            history
+                .transitions()
+                .iter()
+                .cloned()
+                .map(|t| self.scrub_transition(t))
+                .collect(),
+            history
+                .events()
+                .iter()
+                .cloned()
+                .map(|e| self.scrub_event(e))
+                .collect(),
+        )
+        .with_snapshots(
+            history
+                .snapshots()
+                .iter()
+                .cloned()
+                .map(|s| self.scrub_snapshot(s))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ForcedTransition;
+    use chrono::Utc;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Account { owner: String },
+        Closed,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Account { .. } => "Account",
+                Self::Closed => "Closed",
+            }
+        }
+    }
+
+    fn scrubber() -> Scrubber<TestState> {
+        Scrubber::new()
+            .states(|s: &TestState| match s {
+                TestState::Account { .. } => TestState::Account {
+                    owner: "***".to_string(),
+                },
+                other => other.clone(),
+            })
+            .text(|_| "[redacted]".to_string())
+    }
+
+    #[test]
+    fn scrubs_states_and_reasons_in_history() {
+        let history = StateHistory::new()
+            .record(StateTransition {
+                from: TestState::Account {
+                    owner: "alice".to_string(),
+                },
+                to: TestState::Closed,
+                timestamp: Utc::now(),
+                attempt: 1,
+                sequence: 0,
+                forced: Some(ForcedTransition {
+                    reason: "alice asked by phone".to_string(),
+                    operator: "ops".to_string(),
+                }),
+                correlation: Default::default(),
+            })
+            .record_event(HistoryEvent::Paused {
+                reason: "alice on holiday".to_string(),
+                timestamp: Utc::now(),
+            })
+            .record_event(HistoryEvent::LockAcquired {
+                key: "account:alice".to_string(),
+                timestamp: Utc::now(),
+            })
+            .record_event(HistoryEvent::TransitionAdded {
+                from: "Account".to_string(),
+                to: "Closed".to_string(),
+                timestamp: Utc::now(),
+            });
+
+        let scrubbed = scrubber().scrub_history(&history);
+
+        let transition = &scrubbed.transitions()[0];
+        assert_eq!(
+            transition.from,
+            TestState::Account {
+                owner: "***".to_string()
+            }
+        );
+        let forced = transition.forced.as_ref().unwrap();
+        assert_eq!(forced.reason, "[redacted]");
+        assert_eq!(forced.operator, "ops");
+        assert!(matches!(
+            &scrubbed.events()[0],
+            HistoryEvent::Paused { reason, .. } if reason == "[redacted]"
+        ));
+        assert!(matches!(
+            &scrubbed.events()[1],
+            HistoryEvent::LockAcquired { key, .. } if key == "[redacted]"
+        ));
+        assert!(matches!(
+            &scrubbed.events()[2],
+            HistoryEvent::TransitionAdded { from, .. } if from == "Account"
+        ));
+    }
+
+    #[test]
+    fn default_scrubber_is_identity() {
+        let state = TestState::Account {
+            owner: "bob".to_string(),
+        };
+        assert_eq!(Scrubber::new().scrub_state(&state), state);
+        assert_eq!(Scrubber::<TestState>::new().scrub_text("bob"), "bob");
+    }
+}
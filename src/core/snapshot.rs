@@ -0,0 +1,117 @@
+//! Snapshots of a machine's data, recorded alongside transitions.
+
+use serde::{Deserialize, Serialize};
+
+/// Serialized view of a machine's extended context when a transition was
+/// recorded.
+///
+/// Snapshots over the size limit keep their size but not their data, so
+/// debugging still shows that the data was there and how big it was.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataSnapshot {
+    /// Sequence of the transition the snapshot belongs to
+    pub sequence: u64,
+    /// The data as JSON, `None` if it exceeded the size limit
+    pub data: Option<String>,
+    /// Size of the serialized data in bytes
+    pub size: usize,
+}
+
+impl DataSnapshot {
+    /// Whether the data was dropped for exceeding the size limit (pure)
+    pub fn is_truncated(&self) -> bool {
+        self.data.is_none()
+    }
+
+    /// The data deserialized as JSON, if it was kept (pure)
+    pub fn value(&self) -> Option<serde_json::Value> {
+        serde_json::from_str(self.data.as_deref()?).ok()
+    }
+}
+
+/// When and how much data to capture in snapshots.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::core::SnapshotPolicy;
+///
+/// // Every tenth transition, at most 4 KiB each
+/// let policy = SnapshotPolicy::new().every(10).max_bytes(4096);
+/// assert!(policy.samples(1));
+/// assert!(!policy.samples(2));
+/// assert!(policy.samples(11));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    max_bytes: usize,
+    every: u64,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnapshotPolicy {
+    /// Capture every transition, up to 64 KiB of data each
+    pub fn new() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            every: 1,
+        }
+    }
+
+    /// Drop the data of snapshots larger than `max_bytes`
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Capture only every `n`th transition, starting with the first.
+    /// Zero is treated as one.
+    pub fn every(mut self, n: u64) -> Self {
+        self.every = n.max(1);
+        self
+    }
+
+    /// Whether the transition with `sequence` is sampled (pure)
+    pub fn samples(&self, sequence: u64) -> bool {
+        sequence.saturating_sub(1) % self.every == 0
+    }
+
+    /// Snapshot of `data` for the transition with `sequence`, or `None`
+    /// if that transition is not sampled or `data` does not serialize
+    /// (pure)
+    pub fn capture<T: Serialize + ?Sized>(&self, sequence: u64, data: &T) -> Option<DataSnapshot> {
+        if !self.samples(sequence) {
+            return None;
+        }
+        let json = serde_json::to_string(data).ok()?;
+        let size = json.len();
+        Some(DataSnapshot {
+            sequence,
+            data: (size <= self.max_bytes).then_some(json),
+            size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn oversized_data_keeps_only_its_size() {
+        let policy = SnapshotPolicy::new().max_bytes(16);
+
+        let small = policy.capture(1, &json!({ "n": 1 })).unwrap();
+        assert_eq!(small.value(), Some(json!({ "n": 1 })));
+
+        let large = policy.capture(2, &"x".repeat(32)).unwrap();
+        assert!(large.is_truncated());
+        assert_eq!(large.size, 34);
+    }
+}
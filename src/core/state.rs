@@ -3,8 +3,12 @@
 //! All state machine states must implement this trait, which provides
 //! pure methods for inspecting state properties without side effects.
 
+use super::display::DisplayName;
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+use std::ops::Deref;
+use std::sync::Arc;
 
 /// Trait for state machine states.
 ///
@@ -78,6 +82,160 @@ pub trait State:
     fn is_error(&self) -> bool {
         false
     }
+
+    /// Get a localization-ready display name for this state.
+    ///
+    /// The default key is `state.<name>` with `name()` as fallback text.
+    /// Override to provide custom keys or arguments for data-carrying states.
+    fn display_key(&self) -> DisplayName {
+        DisplayName::new(format!("state.{}", self.name()), self.name())
+    }
+
+    /// Get a human-readable name, including dynamic content if useful.
+    ///
+    /// `name()` identifies the kind of state and is used as a key in
+    /// alarms, introspection and exports. `display_name()` is what error
+    /// messages and diagnostics show, so data-carrying states such as
+    /// `Failed { code }` can render as `Failed(503)`.
+    ///
+    /// Default implementation borrows `name()`.
+    fn display_name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.name())
+    }
+
+    /// Get an adapter that formats the state with [`State::display_name`].
+    fn display(&self) -> StateDisplay<'_, Self>
+    where
+        Self: Sized,
+    {
+        StateDisplay(self)
+    }
+}
+
+/// `Display` adapter for any state, returned by [`State::display`].
+#[derive(Debug)]
+pub struct StateDisplay<'a, S: State>(&'a S);
+
+impl<S: State> fmt::Display for StateDisplay<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.display_name())
+    }
+}
+
+/// Delegates to the boxed state.
+impl<S: State> State for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn is_final(&self) -> bool {
+        (**self).is_final()
+    }
+
+    fn is_error(&self) -> bool {
+        (**self).is_error()
+    }
+
+    fn display_key(&self) -> DisplayName {
+        (**self).display_key()
+    }
+
+    fn display_name(&self) -> Cow<'_, str> {
+        (**self).display_name()
+    }
+}
+
+/// Delegates to the shared state.
+///
+/// Cloning an `Arc` state, as history does for every record, only bumps
+/// the reference count, so large payloads are shared rather than copied.
+/// Serialization writes the payload out for every occurrence and
+/// deserialization allocates each occurrence separately; sharing is not
+/// preserved across checkpoints.
+impl<S: State> State for Arc<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn is_final(&self) -> bool {
+        (**self).is_final()
+    }
+
+    fn is_error(&self) -> bool {
+        (**self).is_error()
+    }
+
+    fn display_key(&self) -> DisplayName {
+        (**self).display_key()
+    }
+
+    fn display_name(&self) -> Cow<'_, str> {
+        (**self).display_name()
+    }
+}
+
+/// Cheaply cloneable handle to a state payload.
+///
+/// Like `Arc<S>`, but equality first compares pointers, so comparing
+/// states that share a payload (e.g. a history record and the current
+/// state) does not walk the payload. Serialized transparently as `S`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent, bound = "")]
+pub struct StateRef<S: State>(Arc<S>);
+
+impl<S: State> StateRef<S> {
+    /// Wrap a state for sharing
+    pub fn new(state: S) -> Self {
+        Self(Arc::new(state))
+    }
+}
+
+impl<S: State> Clone for StateRef<S> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<S: State> PartialEq for StateRef<S> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl<S: State> Deref for StateRef<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: State> From<S> for StateRef<S> {
+    fn from(state: S) -> Self {
+        Self::new(state)
+    }
+}
+
+impl<S: State> State for StateRef<S> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn is_final(&self) -> bool {
+        self.0.is_final()
+    }
+
+    fn is_error(&self) -> bool {
+        self.0.is_error()
+    }
+
+    fn display_key(&self) -> DisplayName {
+        self.0.display_key()
+    }
+
+    fn display_name(&self) -> Cow<'_, str> {
+        self.0.display_name()
+    }
 }
 
 #[cfg(test)]
@@ -151,6 +309,13 @@ mod tests {
         assert!(failed.is_error());
     }
 
+    #[test]
+    fn display_key_defaults_to_state_name() {
+        let key = TestState::Processing.display_key();
+        assert_eq!(key.key, "state.Processing");
+        assert_eq!(key.fallback, "Processing");
+    }
+
     #[test]
     fn state_serializes_correctly() {
         let state = TestState::Initial;
@@ -175,4 +340,77 @@ mod tests {
         assert_eq!(state1, state2);
         assert_ne!(state1, state3);
     }
+
+    #[test]
+    fn smart_pointers_delegate_to_inner_state() {
+        let boxed = Box::new(TestState::Failed);
+        let shared = Arc::new(TestState::Complete);
+        let handle = StateRef::new(TestState::Processing);
+
+        assert_eq!(boxed.name(), "Failed");
+        assert!(boxed.is_error());
+        assert!(shared.is_final());
+        assert_eq!(handle.name(), "Processing");
+        assert_eq!(handle.display_key().key, "state.Processing");
+    }
+
+    #[test]
+    fn state_ref_serializes_as_inner_state() {
+        let handle = StateRef::new(TestState::Initial);
+        let json = serde_json::to_string(&handle).unwrap();
+        assert_eq!(json, serde_json::to_string(&TestState::Initial).unwrap());
+
+        let restored: StateRef<TestState> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, handle);
+        assert_eq!(*restored, TestState::Initial);
+    }
+
+    #[test]
+    fn state_ref_clones_share_payload() {
+        let handle = StateRef::new(TestState::Processing);
+        let cloned = handle.clone();
+        assert!(std::ptr::eq(&*handle, &*cloned));
+        assert_eq!(handle, cloned);
+        assert_ne!(handle, StateRef::new(TestState::Complete));
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Request {
+        Pending,
+        Failed { code: u16 },
+    }
+
+    impl State for Request {
+        fn name(&self) -> &str {
+            match self {
+                Self::Pending => "Pending",
+                Self::Failed { .. } => "Failed",
+            }
+        }
+
+        fn display_name(&self) -> Cow<'_, str> {
+            match self {
+                Self::Failed { code } => Cow::Owned(format!("Failed({code})")),
+                other => Cow::Borrowed(other.name()),
+            }
+        }
+    }
+
+    #[test]
+    fn display_name_defaults_to_name() {
+        assert!(matches!(
+            TestState::Processing.display_name(),
+            Cow::Borrowed("Processing")
+        ));
+        assert_eq!(TestState::Processing.display().to_string(), "Processing");
+    }
+
+    #[test]
+    fn display_renders_dynamic_content() {
+        let failed = Request::Failed { code: 503 };
+        assert_eq!(failed.name(), "Failed");
+        assert_eq!(format!("state {}", failed.display()), "state Failed(503)");
+        assert_eq!(Arc::new(failed).display_name(), "Failed(503)");
+        assert_eq!(Request::Pending.display().to_string(), "Pending");
+    }
 }
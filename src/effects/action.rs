@@ -0,0 +1,395 @@
+//! Ready-made action factories for common patterns.
+//!
+//! A [`TransitionAction`] is a factory that returns a fresh boxed effect for
+//! every step. These helpers build such factories from plain async closures
+//! and wrap existing actions with retries, timeouts and error mapping, so
+//! non-trivial actions can be written without reaching for Stillwater's
+//! combinators directly.
+//!
+//! # Example
+//!
+//! ```rust
+//! use mindset::effects::{action_from_async, action_with_timeout, TransitionResult};
+//! use mindset::state_enum;
+//! use std::time::Duration;
+//!
+//! state_enum! {
+//!     enum Job {
+//!         Queued,
+//!         Done,
+//!     }
+//! }
+//!
+//! let action = action_with_timeout(
+//!     action_from_async(|_env: ()| async { Ok(TransitionResult::Success(Job::Done)) }),
+//!     Duration::from_secs(30),
+//! );
+//! # let _ = action;
+//! ```
+
+use crate::core::State;
+use crate::effects::transition::{TransitionAction, TransitionError, TransitionResult};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use stillwater::prelude::*;
+use stillwater::{RetryExhausted, RetryPolicy, TimeoutError};
+
+/// Build an action from an async closure receiving a clone of the environment.
+pub fn action_from_async<S, Env, F, Fut>(f: F) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+    F: Fn(Env) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<TransitionResult<S>, TransitionError>> + Send + 'static,
+{
+    let f = Arc::new(f);
+    Arc::new(move || {
+        let f = Arc::clone(&f);
+        from_async(move |env: &Env| f(env.clone())).boxed()
+    })
+}
+
+/// Build an action from an async closure failing with any displayable error.
+///
+/// Errors become [`TransitionError::ActionFailed`] with the error's message,
+/// so the closure can use `?` on its own error type.
+pub fn action_try<S, Env, E, F, Fut>(f: F) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+    E: Display + Send + 'static,
+    F: Fn(Env) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<TransitionResult<S>, E>> + Send + 'static,
+{
+    action_from_async(move |env| {
+        let attempt = f(env);
+        async move {
+            attempt
+                .await
+                .map_err(|error| TransitionError::ActionFailed(error.to_string()))
+        }
+    })
+}
+
+/// Re-run `inner` immediately up to `retries` more times while it fails.
+///
+/// Only errors are retried. A [`TransitionResult::Retry`] is a successful
+/// result that the machine handles itself, so it is returned unchanged.
+/// The last error is returned once retries are exhausted.
+pub fn action_retrying<S, Env>(
+    inner: TransitionAction<S, Env>,
+    retries: u32,
+) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    Arc::new(move || {
+        let inner = Arc::clone(&inner);
+        let policy = RetryPolicy::constant(Duration::ZERO).with_max_retries(retries);
+        retry(move || inner(), policy)
+            .map(RetryExhausted::into_value)
+            .map_err(RetryExhausted::into_error)
+            .boxed()
+    })
+}
+
+/// What to do after a failed attempt, as decided by a [`RetryDecider`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Run the action again immediately
+    Retry,
+    /// Run the action again after waiting
+    Backoff(Duration),
+    /// Give up and return the error
+    Abort,
+}
+
+/// A failed attempt handed to a [`RetryDecider`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryAttempt {
+    /// Number of the attempt that failed (1-based)
+    pub attempt: u32,
+    /// Time since the first attempt started
+    pub elapsed: Duration,
+    /// Message of the error the attempt failed with
+    pub feedback: String,
+}
+
+/// Callback deciding whether a failed attempt is retried.
+pub type RetryDecider = Arc<dyn Fn(&RetryAttempt) -> RetryDecision + Send + Sync>;
+
+/// Re-run `inner` while it fails and `decider` asks for another attempt,
+/// making at most `max_attempts` attempts in total.
+///
+/// Unlike [`action_retrying`], the delay between attempts and whether to
+/// make another are decided per failure, so e.g. network errors can be
+/// retried with backoff while validation errors fail at once. The last
+/// error is returned after `max_attempts` attempts whatever the decider
+/// says; at least one attempt is always made. As there, only errors are
+/// retried. Backoff uses Stillwater's timer, which is Tokio's, so the step
+/// must run inside a Tokio runtime.
+pub fn action_retrying_with<S, Env, F>(
+    inner: TransitionAction<S, Env>,
+    max_attempts: u32,
+    decider: F,
+) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+    F: Fn(&RetryAttempt) -> RetryDecision + Send + Sync + 'static,
+{
+    let decider: RetryDecider = Arc::new(decider);
+    Arc::new(move || {
+        let inner = Arc::clone(&inner);
+        let decider = Arc::clone(&decider);
+        from_async(move |env: &Env| {
+            let env = env.clone();
+            async move {
+                let started = Instant::now();
+                let mut attempt = 1;
+                loop {
+                    let error = match inner().run(&env).await {
+                        Ok(result) => return Ok(result),
+                        Err(error) => error,
+                    };
+                    if attempt >= max_attempts {
+                        return Err(error);
+                    }
+                    let failed = RetryAttempt {
+                        attempt,
+                        elapsed: started.elapsed(),
+                        feedback: error.to_string(),
+                    };
+                    match decider(&failed) {
+                        RetryDecision::Retry => {}
+                        RetryDecision::Backoff(delay) => wait(delay, &env).await,
+                        RetryDecision::Abort => return Err(error),
+                    }
+                    attempt += 1;
+                }
+            }
+        })
+        .boxed()
+    })
+}
+
+/// Wait for `delay` on Stillwater's timer.
+async fn wait<Env: Clone + Send + Sync + 'static>(delay: Duration, env: &Env) {
+    let never = from_async(|_: &Env| std::future::pending::<Result<(), TransitionError>>());
+    let _ = with_timeout(never, delay).run(env).await;
+}
+
+/// Fail with [`TransitionError::Timeout`] if `inner` runs longer than `limit`.
+///
+/// The timer is Tokio's, so the step must run inside a Tokio runtime.
+pub fn action_with_timeout<S, Env>(
+    inner: TransitionAction<S, Env>,
+    limit: Duration,
+) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    Arc::new(move || {
+        with_timeout(inner(), limit)
+            .map_err(|error| match error {
+                TimeoutError::Timeout { duration } => TransitionError::Timeout { after: duration },
+                TimeoutError::Inner(error) => error,
+            })
+            .boxed()
+    })
+}
+
+/// Transform the errors `inner` fails with, e.g. to add context.
+pub fn action_map_err<S, Env, F>(inner: TransitionAction<S, Env>, f: F) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+    F: Fn(TransitionError) -> TransitionError + Send + Sync + 'static,
+{
+    let f = Arc::new(f);
+    Arc::new(move || {
+        let f = Arc::clone(&f);
+        inner().map_err(move |error| f(error)).boxed()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult, Transition};
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Start,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Start => "Start",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    fn flaky(failures: usize, calls: Arc<AtomicUsize>) -> TransitionAction<TestState, ()> {
+        action_from_async(move |_: ()| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call < failures {
+                    Err(TransitionError::ActionFailed(format!("call {call} failed")))
+                } else {
+                    Ok(TransitionResult::Success(TestState::Done))
+                }
+            }
+        })
+    }
+
+    async fn run(
+        action: TransitionAction<TestState, ()>,
+    ) -> Result<StepResult<TestState>, TransitionError> {
+        let mut machine = StateMachine::new(TestState::Start);
+        machine.add_transition(Transition {
+            from: TestState::Start,
+            to: TestState::Done,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action,
+        });
+        machine.step().run(&()).await.map(|(_, result, _)| result)
+    }
+
+    #[tokio::test]
+    async fn retrying_recovers_from_transient_errors() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let result = run(action_retrying(flaky(2, Arc::clone(&calls)), 2)).await;
+
+        assert_eq!(result.unwrap(), StepResult::Transitioned(TestState::Done));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retrying_returns_last_error_when_exhausted() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let result = run(action_retrying(flaky(5, Arc::clone(&calls)), 1)).await;
+
+        assert!(matches!(
+            result,
+            Err(TransitionError::ActionFailed(ref message)) if message == "call 1 failed"
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn decider_chooses_per_failure() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let attempts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&attempts);
+        let action = action_retrying_with(flaky(2, Arc::clone(&calls)), 5, move |failed| {
+            seen.lock().unwrap().push(failed.attempt);
+            if failed.attempt == 1 {
+                RetryDecision::Backoff(Duration::from_millis(1))
+            } else {
+                RetryDecision::Retry
+            }
+        });
+
+        assert_eq!(
+            run(action).await.unwrap(),
+            StepResult::Transitioned(TestState::Done)
+        );
+        assert_eq!(*attempts.lock().unwrap(), vec![1, 2]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn decider_aborts_on_permanent_errors() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let action = action_retrying_with(flaky(5, Arc::clone(&calls)), 5, |failed| {
+            if failed.feedback.contains("call 0") {
+                RetryDecision::Retry
+            } else {
+                RetryDecision::Abort
+            }
+        });
+
+        assert!(matches!(
+            run(action).await,
+            Err(TransitionError::ActionFailed(ref message)) if message == "call 1 failed"
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn decider_retries_stop_at_max_attempts() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let action = action_retrying_with(flaky(5, Arc::clone(&calls)), 3, |_| {
+            RetryDecision::Backoff(Duration::from_millis(1))
+        });
+
+        assert!(matches!(
+            run(action).await,
+            Err(TransitionError::ActionFailed(ref message)) if message == "call 2 failed"
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn slow_action_times_out() {
+        let slow = action_from_async(|_: ()| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(TransitionResult::Success(TestState::Done))
+        });
+
+        let result = run(action_with_timeout(slow, Duration::from_millis(10))).await;
+
+        assert!(matches!(
+            result,
+            Err(TransitionError::Timeout { after }) if after == Duration::from_millis(10)
+        ));
+    }
+
+    #[tokio::test]
+    async fn map_err_transforms_failures() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let action = action_map_err(flaky(1, calls), |error| {
+            TransitionError::ActionFailed(format!("charging card: {error}"))
+        });
+
+        assert!(matches!(
+            run(action).await,
+            Err(TransitionError::ActionFailed(ref message))
+                if message == "charging card: Transition action failed: call 0 failed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn action_try_converts_foreign_errors() {
+        let action = action_try(|_: ()| async {
+            let code: u16 = "not a number".parse()?;
+            Ok::<_, std::num::ParseIntError>(TransitionResult::Success(if code > 0 {
+                TestState::Done
+            } else {
+                TestState::Start
+            }))
+        });
+
+        assert!(matches!(
+            run(action).await,
+            Err(TransitionError::ActionFailed(ref message)) if message == "invalid digit found in string"
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+//! Per-state alarm thresholds.
+//!
+//! Alarms flag two common signs of trouble without a full SLA setup: a state
+//! entered suspiciously often in one run (thrashing between states), and a
+//! machine sitting in a state for too long. [`StateMachine::check_alarms`]
+//! evaluates the thresholds against the machine's history; callers run it
+//! after applying step results and route the alarms wherever they need.
+//! With a [`Calendar`] attached, time limits only count business time.
+
+use crate::core::{Calendar, State};
+use crate::effects::machine::StateMachine;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+struct Thresholds {
+    max_entries: Option<usize>,
+    max_time_in_state: Option<Duration>,
+}
+
+/// Alarm thresholds keyed by state name.
+#[derive(Clone, Debug, Default)]
+pub struct StateAlarms {
+    thresholds: HashMap<String, Thresholds>,
+    calendar: Option<Arc<dyn Calendar>>,
+}
+
+impl StateAlarms {
+    /// Create an empty alarm configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Alarm when `state` is entered more than `limit` times in a run.
+    pub fn max_entries<S: State>(mut self, state: &S, limit: usize) -> Self {
+        self.entry(state).max_entries = Some(limit);
+        self
+    }
+
+    /// Alarm when the machine stays in `state` longer than `limit`.
+    pub fn max_time_in_state<S: State>(mut self, state: &S, limit: Duration) -> Self {
+        self.entry(state).max_time_in_state = Some(limit);
+        self
+    }
+
+    /// Measure time limits in business time of `calendar`, so that e.g.
+    /// `max_time_in_state(&Review, 3 * 24h)` means three business days.
+    pub fn with_calendar(mut self, calendar: impl Calendar + 'static) -> Self {
+        self.calendar = Some(Arc::new(calendar));
+        self
+    }
+
+    fn entry<S: State>(&mut self, state: &S) -> &mut Thresholds {
+        self.thresholds.entry(state.name().to_string()).or_default()
+    }
+}
+
+/// A threshold that was exceeded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateAlarm {
+    /// The state was entered more often than allowed
+    TooManyEntries {
+        state: String,
+        entries: usize,
+        limit: usize,
+    },
+    /// The machine has been in the state longer than allowed
+    TimeInStateExceeded {
+        state: String,
+        elapsed: Duration,
+        limit: Duration,
+    },
+}
+
+impl fmt::Display for StateAlarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyEntries {
+                state,
+                entries,
+                limit,
+            } => write!(f, "state '{state}' entered {entries} times (limit {limit})"),
+            Self::TimeInStateExceeded {
+                state,
+                elapsed,
+                limit,
+            } => write!(f, "in state '{state}' for {elapsed:?} (limit {limit:?})"),
+        }
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Number of times `state` was entered in this run, counting the
+    /// initial state as entered once (pure).
+    pub fn entry_count(&self, state: &S) -> usize {
+        let initial = usize::from(self.initial_state() == state);
+        initial
+            + self
+                .history()
+                .transitions()
+                .iter()
+                .filter(|t| &t.to == state)
+                .count()
+    }
+
+    /// Evaluate alarm thresholds as of now (pure apart from reading the clock).
+    pub fn check_alarms(&self, alarms: &StateAlarms) -> Vec<StateAlarm> {
+        self.check_alarms_at(alarms, Utc::now())
+    }
+
+    /// Evaluate alarm thresholds as of `now` (pure).
+    ///
+    /// Entry limits are checked for every state with a configured limit;
+    /// the time limit only applies to the current state.
+    pub fn check_alarms_at(&self, alarms: &StateAlarms, now: DateTime<Utc>) -> Vec<StateAlarm> {
+        let mut entries: BTreeMap<&str, usize> = BTreeMap::new();
+        entries.insert(self.initial_state().name(), 1);
+        for transition in self.history().transitions() {
+            *entries.entry(transition.to.name()).or_default() += 1;
+        }
+
+        let mut found: Vec<StateAlarm> = entries
+            .into_iter()
+            .filter_map(|(state, count)| {
+                let limit = alarms.thresholds.get(state)?.max_entries?;
+                (count > limit).then(|| StateAlarm::TooManyEntries {
+                    state: state.to_string(),
+                    entries: count,
+                    limit,
+                })
+            })
+            .collect();
+
+        let current = self.current_state().name();
+        if let Some(limit) = alarms
+            .thresholds
+            .get(current)
+            .and_then(|t| t.max_time_in_state)
+        {
+            let since = self.current_transition_started_at();
+            let elapsed = match &alarms.calendar {
+                Some(calendar) => calendar.business_time_between(since, now),
+                None => (now - since).to_std().unwrap_or_default(),
+            };
+            if elapsed > limit {
+                found.push(StateAlarm::TimeInStateExceeded {
+                    state: current.to_string(),
+                    elapsed,
+                    limit,
+                });
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::StepResult;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Pending,
+        Working,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Pending => "Pending",
+                Self::Working => "Working",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    fn bounce(machine: &mut StateMachine<TestState, ()>, times: usize) {
+        for _ in 0..times {
+            machine.apply_result(
+                TestState::Pending,
+                StepResult::Transitioned(TestState::Working),
+                1,
+            );
+            machine.apply_result(
+                TestState::Working,
+                StepResult::Transitioned(TestState::Pending),
+                1,
+            );
+        }
+    }
+
+    #[test]
+    fn thrashing_state_raises_entry_alarm() {
+        let mut machine = StateMachine::<TestState, ()>::new(TestState::Pending);
+        let alarms = StateAlarms::new().max_entries(&TestState::Pending, 2);
+
+        bounce(&mut machine, 1);
+        assert_eq!(machine.entry_count(&TestState::Pending), 2);
+        assert!(machine.check_alarms(&alarms).is_empty());
+
+        bounce(&mut machine, 1);
+        assert_eq!(
+            machine.check_alarms(&alarms),
+            vec![StateAlarm::TooManyEntries {
+                state: "Pending".to_string(),
+                entries: 3,
+                limit: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn lingering_in_current_state_raises_time_alarm() {
+        let machine = StateMachine::<TestState, ()>::new(TestState::Pending);
+        let alarms =
+            StateAlarms::new().max_time_in_state(&TestState::Pending, Duration::from_secs(60));
+        let started = machine.current_transition_started_at();
+
+        assert!(machine
+            .check_alarms_at(&alarms, started + chrono::Duration::seconds(30))
+            .is_empty());
+        assert!(matches!(
+            machine
+                .check_alarms_at(&alarms, started + chrono::Duration::seconds(90))
+                .as_slice(),
+            [StateAlarm::TimeInStateExceeded { state, .. }] if state == "Pending"
+        ));
+    }
+
+    #[test]
+    fn calendar_limits_count_business_time() {
+        use crate::core::WeekdayCalendar;
+        use chrono::TimeZone;
+
+        let friday = Utc.with_ymd_and_hms(2025, 3, 7, 9, 0, 0).unwrap();
+        let mut machine = StateMachine::<TestState, ()>::new(TestState::Pending);
+        machine.apply_result_at(
+            TestState::Pending,
+            StepResult::Transitioned(TestState::Working),
+            1,
+            Default::default(),
+            friday,
+        );
+        let two_days = Duration::from_secs(2 * 24 * 3600);
+        let alarms = StateAlarms::new()
+            .max_time_in_state(&TestState::Working, two_days)
+            .with_calendar(WeekdayCalendar::new());
+
+        let monday = friday + chrono::Duration::days(3);
+        assert!(machine.check_alarms_at(&alarms, monday).is_empty());
+        assert!(matches!(
+            machine
+                .check_alarms_at(&alarms, monday + chrono::Duration::hours(25))
+                .as_slice(),
+            [StateAlarm::TimeInStateExceeded { elapsed, .. }]
+                if *elapsed == Duration::from_secs(49 * 3600)
+        ));
+    }
+}
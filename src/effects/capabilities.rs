@@ -0,0 +1,243 @@
+//! Typed capability lookup for environments.
+//!
+//! Instead of one environment type implementing a trait per dependency
+//! (`Env: PaymentGateway + Mailer + Clock`), an environment can hold a
+//! [`Capabilities`] map keyed by type, typically trait objects:
+//!
+//! ```rust
+//! use mindset::effects::Capabilities;
+//! use std::sync::Arc;
+//!
+//! trait PaymentGateway: Send + Sync {
+//!     fn charge(&self, cents: u64) -> bool;
+//! }
+//!
+//! struct AlwaysApprove;
+//!
+//! impl PaymentGateway for AlwaysApprove {
+//!     fn charge(&self, _cents: u64) -> bool {
+//!         true
+//!     }
+//! }
+//!
+//! let env = Capabilities::new().with::<dyn PaymentGateway>(Arc::new(AlwaysApprove));
+//! assert!(env.get::<dyn PaymentGateway>().unwrap().charge(100));
+//! ```
+//!
+//! Machines declare what they need with `StateMachineBuilder::requires`,
+//! and [`StateMachine::check_capabilities`] lists every missing capability
+//! at once before the machine is driven.
+
+use crate::core::State;
+use crate::effects::machine::StateMachine;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Key identifying a capability by type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CapabilityKey {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl CapabilityKey {
+    /// The key of capability type `T`
+    pub fn of<T: ?Sized + 'static>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Type name of the capability, for error messages
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Capabilities required but not provided by an environment.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("Missing capabilities: {}", .missing.join(", "))]
+pub struct MissingCapabilities {
+    /// Type names of the missing capabilities, in declaration order
+    pub missing: Vec<&'static str>,
+}
+
+/// Type map of shared dependencies. Cloning is cheap.
+#[derive(Clone, Default)]
+pub struct Capabilities {
+    entries: HashMap<TypeId, (&'static str, Arc<dyn Any + Send + Sync>)>,
+}
+
+impl fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.entries.values().map(|(name, _)| *name).collect();
+        names.sort_unstable();
+        f.debug_struct("Capabilities")
+            .field("provided", &names)
+            .finish()
+    }
+}
+
+impl Capabilities {
+    /// An empty capability map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Provide `value` as capability `T`, replacing any previous one
+    pub fn insert<T: ?Sized + Send + Sync + 'static>(&mut self, value: Arc<T>) {
+        let key = CapabilityKey::of::<T>();
+        self.entries.insert(key.id, (key.name, Arc::new(value)));
+    }
+
+    /// Provide `value` as capability `T` (builder style)
+    pub fn with<T: ?Sized + Send + Sync + 'static>(mut self, value: Arc<T>) -> Self {
+        self.insert(value);
+        self
+    }
+
+    /// Capability `T`, if provided
+    pub fn get<T: ?Sized + Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.entries
+            .get(&TypeId::of::<T>())
+            .and_then(|(_, value)| value.downcast_ref::<Arc<T>>())
+            .cloned()
+    }
+
+    /// Capability `T`, or an error naming it
+    pub fn require<T: ?Sized + Send + Sync + 'static>(
+        &self,
+    ) -> Result<Arc<T>, MissingCapabilities> {
+        self.get::<T>().ok_or_else(|| MissingCapabilities {
+            missing: vec![CapabilityKey::of::<T>().name()],
+        })
+    }
+
+    /// Whether the capability identified by `key` is provided
+    pub fn contains(&self, key: &CapabilityKey) -> bool {
+        self.entries.contains_key(&key.id)
+    }
+
+    /// Verify that every capability in `required` is provided
+    pub fn check(&self, required: &[CapabilityKey]) -> Result<(), MissingCapabilities> {
+        let missing: Vec<_> = required
+            .iter()
+            .filter(|key| !self.contains(key))
+            .map(CapabilityKey::name)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingCapabilities { missing })
+        }
+    }
+}
+
+/// Environment capability exposing a [`Capabilities`] map.
+pub trait HasCapabilities {
+    /// The capabilities actions look their dependencies up in
+    fn capabilities(&self) -> &Capabilities;
+}
+
+impl HasCapabilities for Capabilities {
+    fn capabilities(&self) -> &Capabilities {
+        self
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Verify that `env` provides every capability this machine requires.
+    ///
+    /// All missing capabilities are reported together.
+    pub fn check_capabilities(
+        &self,
+        env: &impl HasCapabilities,
+    ) -> Result<(), MissingCapabilities> {
+        env.capabilities().check(self.required_capabilities())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    trait Mailer: Send + Sync {
+        fn send(&self) -> &'static str;
+    }
+
+    struct Smtp;
+
+    impl Mailer for Smtp {
+        fn send(&self) -> &'static str {
+            "sent"
+        }
+    }
+
+    #[test]
+    fn trait_objects_are_looked_up_by_type() {
+        let caps = Capabilities::new()
+            .with::<dyn Mailer>(Arc::new(Smtp))
+            .with(Arc::new(42u32));
+
+        assert_eq!(caps.get::<dyn Mailer>().unwrap().send(), "sent");
+        assert_eq!(*caps.require::<u32>().unwrap(), 42);
+        assert!(caps.get::<String>().is_none());
+    }
+
+    #[test]
+    fn check_lists_every_missing_capability() {
+        let caps = Capabilities::new().with(Arc::new(42u32));
+        let err = caps
+            .check(&[
+                CapabilityKey::of::<dyn Mailer>(),
+                CapabilityKey::of::<u32>(),
+                CapabilityKey::of::<String>(),
+            ])
+            .unwrap_err();
+
+        assert_eq!(err.missing.len(), 2);
+        assert!(err.to_string().contains("Mailer"));
+        assert!(err.to_string().contains("String"));
+    }
+
+    #[test]
+    fn machines_check_declared_requirements() {
+        #[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+        enum Flow {
+            Start,
+            Done,
+        }
+
+        impl State for Flow {
+            fn name(&self) -> &str {
+                match self {
+                    Self::Start => "Start",
+                    Self::Done => "Done",
+                }
+            }
+
+            fn is_final(&self) -> bool {
+                matches!(self, Self::Done)
+            }
+        }
+
+        let machine = crate::builder::StateMachineBuilder::<Flow, Capabilities>::new()
+            .initial(Flow::Start)
+            .requires::<dyn Mailer>()
+            .add_transition(crate::builder::simple_transition(Flow::Start, Flow::Done))
+            .build()
+            .unwrap();
+
+        let err = machine
+            .check_capabilities(&Capabilities::new())
+            .unwrap_err();
+        assert_eq!(err.missing, vec![std::any::type_name::<dyn Mailer>()]);
+
+        let env = Capabilities::new().with::<dyn Mailer>(Arc::new(Smtp));
+        assert!(machine.check_capabilities(&env).is_ok());
+    }
+}
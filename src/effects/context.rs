@@ -0,0 +1,256 @@
+//! Step context for actions.
+//!
+//! Action factories take no arguments, so an action that needs to know
+//! which attempt it is on, or why the previous attempt asked for a retry,
+//! would otherwise have to capture that information itself. A
+//! [`StepContext`] carries it through the environment instead: drive the
+//! machine with [`StateMachine::step_with_context`] and the environment the
+//! action runs against exposes the context via [`HasStepContext`].
+//!
+//! Because the context travels with the environment, it reaches actions
+//! wrapped by [`Transition::with_lock`], [`action_retrying`] and the other
+//! wrappers unchanged.
+//!
+//! [`Transition::with_lock`]: crate::effects::Transition::with_lock
+//! [`action_retrying`]: crate::effects::action_retrying
+
+use crate::core::{HistoryEvent, State, StateTransition};
+use crate::effects::machine::{StateMachine, StepResult};
+use crate::effects::transition::{TransitionAction, TransitionError, TransitionResult};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use stillwater::effect::{from_async, Effect};
+use stillwater::prelude::*;
+
+/// Number of recent transitions included in a [`StepContext`].
+pub const RECENT_TRANSITIONS: usize = 5;
+
+/// What the machine knows about the step an action runs in.
+#[derive(Clone, Debug)]
+pub struct StepContext<S: State> {
+    /// State the step starts from
+    pub state: S,
+    /// Attempt number of this step (1-based)
+    pub attempt: usize,
+    /// Feedback of the previous attempt, if it asked for a retry
+    pub feedback: Option<String>,
+    /// Up to [`RECENT_TRANSITIONS`] latest transitions, oldest first
+    pub recent: Vec<StateTransition<S>>,
+    /// Id of the machine, see [`StateMachine::id`]
+    pub machine_id: String,
+    /// Namespace (tenant) of the machine
+    pub namespace: Option<String>,
+    /// Labels of the machine
+    pub labels: BTreeMap<String, String>,
+    /// Whether the machine was resumed from a checkpoint and has not
+    /// transitioned since, so this step may repeat interrupted work
+    pub resumed: bool,
+    /// Times the machine was resumed from a checkpoint
+    pub resume_count: usize,
+    /// Events the step reports, added to the machine's history when the
+    /// step's result is applied
+    pub events: StepEvents,
+}
+
+/// Sink for history events reported while a step runs.
+///
+/// Wrappers such as [`Transition::with_lock`] record what they did here.
+/// Clones share the sink.
+///
+/// [`Transition::with_lock`]: crate::effects::Transition::with_lock
+#[derive(Clone, Debug, Default)]
+pub struct StepEvents(Arc<Mutex<Vec<HistoryEvent>>>);
+
+impl StepEvents {
+    /// Record `event` for the machine running the step
+    pub fn record(&self, event: HistoryEvent) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+    }
+
+    /// Take the recorded events, oldest first
+    pub(crate) fn take(&self) -> Vec<HistoryEvent> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Environment capability carrying the [`StepContext`] of the running step.
+pub trait HasStepContext<S: State>: Sized {
+    /// Context of the running step, if the environment carries one
+    fn step_context(&self) -> Option<&StepContext<S>>;
+
+    /// Copy of the environment carrying `context`
+    fn with_step_context(&self, context: Arc<StepContext<S>>) -> Self;
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Context the next step would run with (pure)
+    pub fn step_context(&self) -> StepContext<S> {
+        let transitions = self.history().transitions();
+        let recent = &transitions[transitions.len().saturating_sub(RECENT_TRANSITIONS)..];
+        StepContext {
+            state: self.current_state().clone(),
+            attempt: self.attempt_count() + 1,
+            feedback: self.retry_feedback().map(str::to_string),
+            recent: recent.to_vec(),
+            machine_id: self.id().to_string(),
+            namespace: self.namespace().map(str::to_string),
+            labels: self.labels().clone(),
+            resumed: self.is_resume_pending(),
+            resume_count: self.resume_count(),
+            events: self.step_events().clone(),
+        }
+    }
+
+    /// Execute one step, passing its [`StepContext`] to the action through
+    /// the environment.
+    ///
+    /// Feature flags are not evaluated; see `step_with_flags`.
+    pub fn step_with_context(
+        &self,
+    ) -> impl Effect<Output = (S, StepResult<S>, usize), Error = TransitionError, Env = Env> + '_
+    where
+        Env: HasStepContext<S>,
+    {
+        from_async(move |env: &Env| {
+            let env = env.with_step_context(Arc::new(self.step_context()));
+            let step = self.step();
+            async move { step.run(&env).await }
+        })
+    }
+}
+
+/// Build an action from an async closure receiving a clone of the
+/// environment and the step's context.
+///
+/// Fails with [`TransitionError::ActionFailed`] when the environment
+/// carries no context, i.e. the machine was not driven with
+/// [`StateMachine::step_with_context`].
+pub fn action_with_context<S, Env, F, Fut>(f: F) -> TransitionAction<S, Env>
+where
+    S: State + 'static,
+    Env: HasStepContext<S> + Clone + Send + Sync + 'static,
+    F: Fn(Env, StepContext<S>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<TransitionResult<S>, TransitionError>> + Send + 'static,
+{
+    let f = Arc::new(f);
+    Arc::new(move || {
+        let f = Arc::clone(&f);
+        from_async(move |env: &Env| {
+            let attempt = env
+                .step_context()
+                .cloned()
+                .map(|context| f(env.clone(), context));
+            async move {
+                match attempt {
+                    Some(attempt) => attempt.await,
+                    None => Err(TransitionError::ActionFailed(
+                        "no step context; drive the machine with step_with_context".to_string(),
+                    )),
+                }
+            }
+        })
+        .boxed()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::Transition;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Upload {
+        Sending,
+        Sent,
+    }
+
+    impl State for Upload {
+        fn name(&self) -> &str {
+            match self {
+                Self::Sending => "Sending",
+                Self::Sent => "Sent",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Sent)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestEnv {
+        context: Option<Arc<StepContext<Upload>>>,
+    }
+
+    impl HasStepContext<Upload> for TestEnv {
+        fn step_context(&self) -> Option<&StepContext<Upload>> {
+            self.context.as_deref()
+        }
+
+        fn with_step_context(&self, context: Arc<StepContext<Upload>>) -> Self {
+            Self {
+                context: Some(context),
+            }
+        }
+    }
+
+    fn machine() -> StateMachine<Upload, TestEnv> {
+        let mut machine = StateMachine::new(Upload::Sending);
+        machine.add_transition(Transition {
+            from: Upload::Sending,
+            to: Upload::Sent,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: action_with_context(|_env, context: StepContext<Upload>| async move {
+                Ok(match context.feedback {
+                    Some(feedback) if context.attempt > 2 => {
+                        assert_eq!(feedback, "chunk size 2 too large");
+                        TransitionResult::Success(Upload::Sent)
+                    }
+                    _ => TransitionResult::Retry {
+                        feedback: format!("chunk size {} too large", context.attempt),
+                        current_state: Upload::Sending,
+                    },
+                })
+            }),
+        });
+        machine
+    }
+
+    #[tokio::test]
+    async fn actions_see_attempt_and_previous_feedback() {
+        let env = TestEnv::default();
+        let mut machine = machine();
+        machine.set_label("team", "storage");
+
+        let mut results = Vec::new();
+        while !machine.is_final() {
+            let (from, result, attempt) = machine.step_with_context().run(&env).await.unwrap();
+            results.push(result.clone());
+            machine.apply_result(from, result, attempt);
+        }
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2], StepResult::Transitioned(Upload::Sent));
+        let context = machine.step_context();
+        assert_eq!((context.attempt, context.feedback), (1, None));
+        assert_eq!(context.recent.len(), 1);
+        assert_eq!(context.labels["team"], "storage");
+        assert_eq!(context.machine_id, machine.id());
+        assert!(!context.machine_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn plain_step_has_no_context() {
+        let result = machine().step().run(&TestEnv::default()).await;
+
+        assert!(matches!(result, Err(TransitionError::ActionFailed(_))));
+    }
+}
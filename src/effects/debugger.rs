@@ -0,0 +1,270 @@
+//! Step-by-step debugging of a machine.
+//!
+//! A [`Debugger`] drives a machine like a host would, but stops at
+//! breakpoints on states or transitions and lets the caller inspect the
+//! machine in between. It is the programmatic core an interactive debugger
+//! (a CLI or an IDE plugin) can be built on.
+//!
+//! Breakpoints are matched by state name. A transition breakpoint stops
+//! *before* the transition's action runs; a state breakpoint stops right
+//! after the state was entered.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepResult};
+use crate::effects::transition::{CandidateCheck, TransitionError};
+use std::collections::BTreeSet;
+use stillwater::effect::Effect;
+
+/// Steps a continue runs at most before giving up, by default.
+pub const DEFAULT_STEP_LIMIT: usize = 1000;
+
+/// Why a [`Debugger`] stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop<S: State> {
+    /// The machine entered a state with a breakpoint
+    StateBreakpoint(S),
+    /// The next step would run a transition with a breakpoint
+    TransitionBreakpoint { from: S, to: S },
+    /// The machine reached a final state, or aborted into an error state
+    Final(S),
+    /// The machine is paused or waits for a signal
+    Waiting(StepResult<S>),
+    /// The step limit was reached, e.g. because a transition keeps retrying
+    StepLimit,
+}
+
+/// Drives a machine with breakpoints.
+pub struct Debugger<'m, S: State + 'static, Env: Clone + Send + Sync + 'static> {
+    machine: &'m mut StateMachine<S, Env>,
+    state_breakpoints: BTreeSet<String>,
+    transition_breakpoints: BTreeSet<(String, String)>,
+    step_limit: usize,
+    /// Whether the debugger is stopped at a transition breakpoint
+    before_transition: bool,
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Attach a debugger to the machine
+    pub fn debugger(&mut self) -> Debugger<'_, S, Env> {
+        Debugger {
+            machine: self,
+            state_breakpoints: BTreeSet::new(),
+            transition_breakpoints: BTreeSet::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            before_transition: false,
+        }
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> Debugger<'_, S, Env> {
+    /// Stop whenever a state named `state` is entered
+    pub fn break_on_state(&mut self, state: impl Into<String>) -> &mut Self {
+        self.state_breakpoints.insert(state.into());
+        self
+    }
+
+    /// Stop before running a transition from `from` to `to` (state names)
+    pub fn break_on_transition(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> &mut Self {
+        self.transition_breakpoints.insert((from.into(), to.into()));
+        self
+    }
+
+    /// Remove all breakpoints
+    pub fn clear_breakpoints(&mut self) -> &mut Self {
+        self.state_breakpoints.clear();
+        self.transition_breakpoints.clear();
+        self
+    }
+
+    /// Limit how many steps a single continue may run
+    pub fn set_step_limit(&mut self, limit: usize) -> &mut Self {
+        self.step_limit = limit;
+        self
+    }
+
+    /// The machine being debugged
+    pub fn machine(&self) -> &StateMachine<S, Env> {
+        self.machine
+    }
+
+    /// How each transition out of the current state fares right now:
+    /// state match, guard outcome and disabling flag (pure)
+    pub fn pending_guards(&self) -> Vec<CandidateCheck> {
+        self.machine.candidate_checks(None)
+    }
+
+    /// Run a single step and apply it, ignoring breakpoints
+    pub async fn step(&mut self, env: &Env) -> Result<StepResult<S>, TransitionError> {
+        self.before_transition = false;
+        let (from, result, attempt) = self.machine.step().run(env).await?;
+        self.machine.apply_result(from, result.clone(), attempt);
+        Ok(result)
+    }
+
+    /// Run steps until a breakpoint, a final state, a wait or the step limit.
+    ///
+    /// A transition breakpoint the debugger is currently stopped at does not
+    /// stop it again, so repeated calls make progress.
+    pub async fn continue_run(&mut self, env: &Env) -> Result<Stop<S>, TransitionError> {
+        self.run_until(env, |_| false).await
+    }
+
+    /// Like [`continue_run`](Self::continue_run), additionally stopping once
+    /// a state named `state` is entered.
+    pub async fn continue_until(
+        &mut self,
+        env: &Env,
+        state: &str,
+    ) -> Result<Stop<S>, TransitionError> {
+        self.run_until(env, |entered| entered.name() == state).await
+    }
+
+    async fn run_until(
+        &mut self,
+        env: &Env,
+        target: impl Fn(&S) -> bool,
+    ) -> Result<Stop<S>, TransitionError> {
+        let resuming = std::mem::take(&mut self.before_transition);
+        for steps in 0..self.step_limit {
+            let current = self.machine.current_state();
+            if current.is_final() {
+                return Ok(Stop::Final(current.clone()));
+            }
+            if let Some(next) = self.machine.preview() {
+                let edge = (next.from.name().to_string(), next.to.name().to_string());
+                if !(resuming && steps == 0) && self.transition_breakpoints.contains(&edge) {
+                    self.before_transition = true;
+                    return Ok(Stop::TransitionBreakpoint {
+                        from: next.from,
+                        to: next.to,
+                    });
+                }
+            }
+
+            match self.step(env).await? {
+                StepResult::Transitioned(entered) => {
+                    if entered.is_final() {
+                        return Ok(Stop::Final(entered));
+                    }
+                    if target(&entered) || self.state_breakpoints.contains(entered.name()) {
+                        return Ok(Stop::StateBreakpoint(entered));
+                    }
+                }
+                StepResult::Aborted { error_state, .. } => return Ok(Stop::Final(error_state)),
+                waiting @ (StepResult::Paused { .. } | StepResult::AwaitingSignal { .. }) => {
+                    return Ok(Stop::Waiting(waiting))
+                }
+                StepResult::Retry { .. } => {}
+            }
+        }
+        Ok(Stop::StepLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::core::Guard;
+    use crate::effects::{Transition, TransitionResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+    use stillwater::prelude::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Build {
+        Queued,
+        Compiling,
+        Testing,
+        Passed,
+    }
+
+    impl State for Build {
+        fn name(&self) -> &str {
+            match self {
+                Self::Queued => "Queued",
+                Self::Compiling => "Compiling",
+                Self::Testing => "Testing",
+                Self::Passed => "Passed",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Passed)
+        }
+    }
+
+    fn machine() -> StateMachine<Build, ()> {
+        let mut machine = StateMachine::new(Build::Queued);
+        machine.add_transition(simple_transition(Build::Queued, Build::Compiling));
+        machine.add_transition(simple_transition(Build::Compiling, Build::Testing));
+        machine.add_transition(simple_transition(Build::Testing, Build::Passed));
+        machine
+    }
+
+    #[tokio::test]
+    async fn stops_at_breakpoints_and_resumes() {
+        let mut machine = machine();
+        let mut debugger = machine.debugger();
+        debugger
+            .break_on_state("Compiling")
+            .break_on_transition("Compiling", "Testing");
+
+        assert_eq!(
+            debugger.continue_run(&()).await.unwrap(),
+            Stop::StateBreakpoint(Build::Compiling)
+        );
+        assert_eq!(
+            debugger.continue_run(&()).await.unwrap(),
+            Stop::TransitionBreakpoint {
+                from: Build::Compiling,
+                to: Build::Testing
+            }
+        );
+        assert_eq!(debugger.machine().current_state(), &Build::Compiling);
+        assert_eq!(
+            debugger.continue_run(&()).await.unwrap(),
+            Stop::Final(Build::Passed)
+        );
+        assert_eq!(machine.history().transitions().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn continue_until_a_state() {
+        let mut machine = machine();
+        let mut debugger = machine.debugger();
+
+        assert_eq!(
+            debugger.continue_until(&(), "Testing").await.unwrap(),
+            Stop::StateBreakpoint(Build::Testing)
+        );
+    }
+
+    #[tokio::test]
+    async fn endless_retries_hit_the_step_limit() {
+        let mut machine = StateMachine::<Build, ()>::new(Build::Queued);
+        machine.add_transition(Transition {
+            guard: Some(Guard::new(|_: &Build| true).with_label("runner free")),
+            action: Arc::new(|| {
+                pure(TransitionResult::Retry {
+                    feedback: "no runner".to_string(),
+                    current_state: Build::Queued,
+                })
+                .boxed()
+            }),
+            ..simple_transition(Build::Queued, Build::Compiling)
+        });
+        let mut debugger = machine.debugger();
+        debugger.set_step_limit(3);
+
+        assert_eq!(debugger.continue_run(&()).await.unwrap(), Stop::StepLimit);
+        assert_eq!(debugger.machine().attempt_count(), 3);
+        let guards = debugger.pending_guards();
+        assert_eq!(guards[0].guard_label.as_deref(), Some("runner free"));
+        assert_eq!(guards[0].guard_passed, Some(true));
+    }
+}
@@ -0,0 +1,261 @@
+//! Runtime diagnostics for long-running transition actions.
+//!
+//! Wrapping a transition with [`Transition::instrumented`] attaches a
+//! [`StepProbe`] to its action. The probe tracks how long the current run
+//! has been pending, how often the runtime polled it and whether it is
+//! currently awaiting. Monitoring code can read the probe from any thread
+//! while the step runs.
+//!
+//! An optional slow-step threshold invokes a callback once per run, the
+//! first time the action is observed running longer than the threshold
+//! (on a poll or at completion). The library has no timer of its own, so a
+//! monitor that needs to detect stalls without polling activity should
+//! check [`StepProbe::diagnostics`] periodically.
+
+use crate::core::State;
+use crate::effects::transition::Transition;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use stillwater::prelude::*;
+
+/// Snapshot of an instrumented action's progress.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepDiagnostics {
+    /// How long the current (or last) run has taken so far
+    pub elapsed: Duration,
+    /// Number of times the runtime polled the current run
+    pub polls: usize,
+    /// Whether the run is suspended waiting on something
+    pub awaiting: bool,
+    /// Whether the run has finished
+    pub finished: bool,
+    /// Number of runs started, i.e. steps that executed the action
+    pub runs: usize,
+}
+
+#[derive(Default)]
+struct ProbeState {
+    started_at: Option<Instant>,
+    finished_after: Option<Duration>,
+    polls: usize,
+    awaiting: bool,
+    runs: usize,
+    slow_reported: bool,
+}
+
+type SlowCallback = Arc<dyn Fn(&StepDiagnostics) + Send + Sync>;
+
+/// Shared, cloneable view onto an instrumented action.
+#[derive(Clone, Default)]
+pub struct StepProbe {
+    state: Arc<Mutex<ProbeState>>,
+    slow: Option<(Duration, SlowCallback)>,
+}
+
+impl StepProbe {
+    /// Create a probe without a slow-step threshold
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invoke `on_slow` once per run when the action runs longer than
+    /// `threshold`.
+    pub fn with_slow_threshold<F>(mut self, threshold: Duration, on_slow: F) -> Self
+    where
+        F: Fn(&StepDiagnostics) + Send + Sync + 'static,
+    {
+        self.slow = Some((threshold, Arc::new(on_slow)));
+        self
+    }
+
+    /// Current diagnostics of the instrumented action
+    pub fn diagnostics(&self) -> StepDiagnostics {
+        let state = self.lock();
+        Self::snapshot(&state)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProbeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn snapshot(state: &ProbeState) -> StepDiagnostics {
+        let elapsed = state.finished_after.unwrap_or_else(|| {
+            state
+                .started_at
+                .map(|started| started.elapsed())
+                .unwrap_or_default()
+        });
+        StepDiagnostics {
+            elapsed,
+            polls: state.polls,
+            awaiting: state.awaiting,
+            finished: state.finished_after.is_some(),
+            runs: state.runs,
+        }
+    }
+
+    fn start(&self) {
+        let mut state = self.lock();
+        *state = ProbeState {
+            started_at: Some(Instant::now()),
+            runs: state.runs + 1,
+            ..ProbeState::default()
+        };
+    }
+
+    fn record_poll(&self, ready: bool) {
+        let report = {
+            let mut state = self.lock();
+            state.polls += 1;
+            state.awaiting = !ready;
+            if ready {
+                state.finished_after = state.started_at.map(|started| started.elapsed());
+            }
+            let diagnostics = Self::snapshot(&state);
+            match &self.slow {
+                Some((threshold, _))
+                    if !state.slow_reported && diagnostics.elapsed > *threshold =>
+                {
+                    state.slow_reported = true;
+                    Some(diagnostics)
+                }
+                _ => None,
+            }
+        };
+        // Invoke the callback without holding the lock, so it may read the probe
+        if let (Some(diagnostics), Some((_, on_slow))) = (report, &self.slow) {
+            on_slow(&diagnostics);
+        }
+    }
+}
+
+/// Future wrapper reporting every poll to a probe.
+struct Probed<F> {
+    inner: Pin<Box<F>>,
+    probe: StepProbe,
+}
+
+impl<F: Future> Future for Probed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let poll = self.inner.as_mut().poll(cx);
+        self.probe.record_poll(poll.is_ready());
+        poll
+    }
+}
+
+impl<S, Env> Transition<S, Env>
+where
+    S: State + 'static,
+    Env: Clone + Send + Sync + 'static,
+{
+    /// Report the progress of this transition's action to `probe`.
+    pub fn instrumented(self, probe: StepProbe) -> Self {
+        let action = self.action;
+        Self {
+            action: Arc::new(move || {
+                let action = Arc::clone(&action);
+                let probe = probe.clone();
+                from_async(move |env: &Env| {
+                    let env = env.clone();
+                    probe.start();
+                    Probed {
+                        inner: Box::pin(async move { action().run(&env).await }),
+                        probe,
+                    }
+                })
+                .boxed()
+            }),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult, TransitionResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Waiting,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Waiting => "Waiting",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    fn machine(probe: StepProbe, delay: Duration) -> StateMachine<TestState, ()> {
+        let mut machine = StateMachine::new(TestState::Waiting);
+        machine.add_transition(
+            Transition {
+                from: TestState::Waiting,
+                to: TestState::Done,
+                guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
+                action: Arc::new(move || {
+                    from_async(move |_: &()| async move {
+                        tokio::time::sleep(delay).await;
+                        Ok(TransitionResult::Success(TestState::Done))
+                    })
+                    .boxed()
+                }),
+            }
+            .instrumented(probe),
+        );
+        machine
+    }
+
+    #[tokio::test]
+    async fn probe_tracks_pending_action() {
+        let probe = StepProbe::new();
+        let machine = machine(probe.clone(), Duration::from_millis(50));
+
+        let step = tokio::spawn(async move { machine.step().run(&()).await.map(|r| r.1) });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let pending = probe.diagnostics();
+        assert!(pending.awaiting);
+        assert!(!pending.finished);
+
+        let result = step.await.unwrap().unwrap();
+        assert_eq!(result, StepResult::Transitioned(TestState::Done));
+        let done = probe.diagnostics();
+        assert!(done.finished && !done.awaiting);
+        assert!(done.polls >= 2);
+        assert!(done.elapsed >= Duration::from_millis(50));
+        assert_eq!(done.runs, 1);
+    }
+
+    #[tokio::test]
+    async fn slow_threshold_reports_once_per_run() {
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&reports);
+        let probe = StepProbe::new().with_slow_threshold(Duration::from_millis(1), move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let machine = machine(probe, Duration::from_millis(10));
+
+        machine.step().run(&()).await.unwrap();
+        machine.step().run(&()).await.unwrap();
+
+        assert_eq!(reports.load(Ordering::SeqCst), 2);
+    }
+}
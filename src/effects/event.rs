@@ -0,0 +1,273 @@
+//! Transitions driven by typed events.
+//!
+//! `step()` sequences a machine through the first transition that can
+//! execute. Reactive machines instead move when something happens: an
+//! [`EventTransition`] responds to one kind of [`Event`], its guard sees
+//! the event's payload, and its action receives the event.
+//! [`StateMachine::on_event`] picks the transition out of the current state
+//! that handles the event and runs it like a step, entry and exit hooks
+//! included, so the result is applied with `apply_result()` as usual.
+//!
+//! Event transitions are kept apart from the machine's own transitions,
+//! since the machine is not generic over an event type; hosts hold them
+//! next to the machine like an `ActionRegistry`.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepEffect, StepResult};
+use crate::effects::transition::{TransitionError, TransitionResult};
+use std::sync::Arc;
+use stillwater::effect::{BoxedEffect, Effect};
+use stillwater::prelude::*;
+
+/// An event a machine reacts to.
+///
+/// Transitions select events by [`name`](Event::name), typically the enum
+/// variant, and inspect the payload through guards and actions.
+pub trait Event: Clone + Send + Sync + 'static {
+    /// Name identifying the kind of event
+    fn name(&self) -> &str;
+}
+
+type EventGuard<S, Ev> = Arc<dyn Fn(&S, &Ev) -> bool + Send + Sync>;
+
+/// Action of an [`EventTransition`], receiving the event.
+pub type EventAction<S, Ev, Env> =
+    Arc<dyn Fn(&Ev) -> BoxedEffect<TransitionResult<S>, TransitionError, Env> + Send + Sync>;
+
+/// A transition taken in response to an event.
+pub struct EventTransition<S: State, Ev: Event, Env> {
+    /// Source state
+    pub from: S,
+    /// Target state
+    pub to: S,
+    /// Name of the event the transition responds to
+    pub event: String,
+    guard: Option<EventGuard<S, Ev>>,
+    action: EventAction<S, Ev, Env>,
+}
+
+impl<S: State, Ev: Event, Env> Clone for EventTransition<S, Ev, Env> {
+    fn clone(&self) -> Self {
+        Self {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            event: self.event.clone(),
+            guard: self.guard.clone(),
+            action: Arc::clone(&self.action),
+        }
+    }
+}
+
+impl<S, Ev, Env> EventTransition<S, Ev, Env>
+where
+    S: State + 'static,
+    Ev: Event,
+    Env: Clone + Send + Sync + 'static,
+{
+    /// Respond to `event` in `from` by running `action` with the event
+    pub fn new<F>(from: S, to: S, event: impl Into<String>, action: F) -> Self
+    where
+        F: Fn(&Ev) -> BoxedEffect<TransitionResult<S>, TransitionError, Env>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            from,
+            to,
+            event: event.into(),
+            guard: None,
+            action: Arc::new(action),
+        }
+    }
+
+    /// Respond to `event` in `from` by moving to `to` without side effects
+    pub fn moves(from: S, to: S, event: impl Into<String>) -> Self {
+        let target = to.clone();
+        Self::new(from, to, event, move |_: &Ev| {
+            pure(TransitionResult::Success(target.clone())).boxed()
+        })
+    }
+
+    /// Only respond to events for which `guard` holds
+    pub fn when<F>(mut self, guard: F) -> Self
+    where
+        F: Fn(&S, &Ev) -> bool + Send + Sync + 'static,
+    {
+        self.guard = Some(Arc::new(guard));
+        self
+    }
+
+    /// Check whether the transition handles `event` in `state` (pure)
+    pub fn handles(&self, state: &S, event: &Ev) -> bool {
+        &self.from == state
+            && event.name() == self.event
+            && self.guard.as_ref().is_none_or(|g| g(state, event))
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Handle `event` with the first of `transitions` that responds to it
+    /// from the current state.
+    ///
+    /// Like `step()`, returns `(from_state, result, attempt_count)` to pass
+    /// to `apply_result()`, and reports [`StepResult::Paused`] or
+    /// [`StepResult::AwaitingSignal`] without running anything while the
+    /// machine is paused or its state waits for a signal. Fails with
+    /// [`TransitionError::UnhandledEvent`] if no transition responds, and
+    /// with [`TransitionError::UnexpectedTarget`] if the action succeeds
+    /// into a state other than the transition's `to`.
+    pub fn on_event<Ev: Event>(
+        &self,
+        transitions: &[EventTransition<S, Ev, Env>],
+        event: Ev,
+    ) -> impl Effect<Output = (S, StepResult<S>, usize), Error = TransitionError, Env = Env> {
+        let from = self.current_state().clone();
+        if let Some(pause) = self.pause_info() {
+            return StepEffect::Ready((
+                from,
+                StepResult::Paused {
+                    reason: pause.reason.clone(),
+                },
+                self.attempt_count(),
+            ));
+        }
+        if let Some(signal) = self.awaited_signal() {
+            return StepEffect::Ready((
+                from,
+                StepResult::AwaitingSignal {
+                    signal: signal.to_string(),
+                },
+                self.attempt_count(),
+            ));
+        }
+        match transitions.iter().find(|t| t.handles(&from, &event)) {
+            Some(transition) => StepEffect::Run {
+                action: (transition.action)(&event),
+                from,
+                attempt_count: self.attempt_count(),
+                target: Some(transition.to.clone()),
+                hooks: self.state_hooks(),
+            },
+            None => StepEffect::Failed(TransitionError::UnhandledEvent {
+                event: event.name().to_string(),
+                state: from.display_name().into_owned(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_enum;
+
+    state_enum! {
+        enum Door {
+            Locked,
+            Unlocked,
+            Open,
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum Input {
+        Code(u32),
+        Push,
+    }
+
+    impl Event for Input {
+        fn name(&self) -> &str {
+            match self {
+                Self::Code(_) => "Code",
+                Self::Push => "Push",
+            }
+        }
+    }
+
+    fn transitions() -> Vec<EventTransition<Door, Input, ()>> {
+        vec![
+            EventTransition::moves(Door::Locked, Door::Unlocked, "Code")
+                .when(|_, input| matches!(input, Input::Code(1234))),
+            EventTransition::new(Door::Unlocked, Door::Open, "Push", |_: &Input| {
+                pure(TransitionResult::Success(Door::Open)).boxed()
+            }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn events_drive_matching_transitions() {
+        let transitions = transitions();
+        let mut machine = StateMachine::<Door, ()>::new(Door::Locked);
+
+        let wrong = machine
+            .on_event(&transitions, Input::Code(1111))
+            .run(&())
+            .await;
+        assert!(matches!(
+            wrong,
+            Err(TransitionError::UnhandledEvent { ref event, ref state })
+                if event == "Code" && state == "Locked"
+        ));
+
+        for input in [Input::Code(1234), Input::Push] {
+            let (from, result, attempt) = machine
+                .on_event(&transitions, input)
+                .run(&())
+                .await
+                .unwrap();
+            machine.apply_result(from, result, attempt);
+        }
+        assert_eq!(machine.current_state(), &Door::Open);
+        assert_eq!(machine.history().transitions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn paused_machines_do_not_handle_events() {
+        let mut machine = StateMachine::<Door, ()>::new(Door::Locked);
+        machine.pause("maintenance");
+
+        let (_, result, _) = machine
+            .on_event(&transitions(), Input::Code(1234))
+            .run(&())
+            .await
+            .unwrap();
+        assert!(matches!(result, StepResult::Paused { .. }));
+    }
+
+    #[tokio::test]
+    async fn waiting_states_do_not_handle_events() {
+        let mut machine = StateMachine::<Door, ()>::new(Door::Locked);
+        machine.await_signal(&Door::Locked, crate::effects::SignalWait::new("badge"));
+
+        let (_, result, _) = machine
+            .on_event(&transitions(), Input::Code(1234))
+            .run(&())
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            StepResult::AwaitingSignal {
+                signal: "badge".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn actions_must_reach_the_declared_target() {
+        let machine = StateMachine::<Door, ()>::new(Door::Locked);
+        let sneaky = [EventTransition::new(
+            Door::Locked,
+            Door::Unlocked,
+            "Push",
+            |_: &Input| pure(TransitionResult::Success(Door::Open)).boxed(),
+        )];
+
+        let result = machine.on_event(&sneaky, Input::Push).run(&()).await;
+        assert!(matches!(
+            result,
+            Err(TransitionError::UnexpectedTarget { ref expected, ref actual })
+                if expected == "Unlocked" && actual == "Open"
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+//! Explanations for why a transition is currently unavailable.
+//!
+//! Frontends need to tell users *why* an action is not possible right now,
+//! not just that it failed. These types describe each blocker in a
+//! structured way so they can be rendered as actionable messages.
+
+use crate::core::{DisplayName, State};
+use crate::effects::machine::StateMachine;
+use std::fmt;
+
+/// Why a single transition towards the requested target cannot execute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockReason<S: State> {
+    /// The transition starts from a different state than the current one
+    WrongSourceState { required: S, current: S },
+
+    /// The transition's guard rejected the current state
+    GuardFailed { from: S, label: Option<String> },
+
+    /// The transition is rolled out behind a feature flag
+    DisabledByFlag { flag: String },
+}
+
+impl<S: State> BlockReason<S> {
+    /// Get a localization-ready message for this blocker.
+    ///
+    /// Keys are `explain.wrong_source_state` (args `required`, `current`),
+    /// `explain.guard_failed` (args `from` and, when labeled, `label`) and
+    /// `explain.disabled_by_flag` (arg `flag`).
+    pub fn display_key(&self) -> DisplayName {
+        match self {
+            Self::WrongSourceState { required, current } => {
+                DisplayName::new("explain.wrong_source_state", self.to_string())
+                    .with_arg("required", required.name())
+                    .with_arg("current", current.name())
+            }
+            Self::GuardFailed { from, label } => {
+                let name = DisplayName::new("explain.guard_failed", self.to_string())
+                    .with_arg("from", from.name());
+                match label {
+                    Some(label) => name.with_arg("label", label.as_str()),
+                    None => name,
+                }
+            }
+            Self::DisabledByFlag { flag } => {
+                DisplayName::new("explain.disabled_by_flag", self.to_string())
+                    .with_arg("flag", flag.as_str())
+            }
+        }
+    }
+}
+
+impl<S: State> fmt::Display for BlockReason<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongSourceState { required, current } => write!(
+                f,
+                "requires state '{}' but machine is in '{}'",
+                required.display(),
+                current.display()
+            ),
+            Self::GuardFailed {
+                from,
+                label: Some(label),
+            } => write!(f, "guard '{}' rejected state '{}'", label, from.display()),
+            Self::GuardFailed { from, label: None } => {
+                write!(f, "guard rejected state '{}'", from.display())
+            }
+            Self::DisabledByFlag { flag } => write!(f, "feature flag '{flag}' is disabled"),
+        }
+    }
+}
+
+/// Structured answer to "can the machine move to this state right now?"
+#[derive(Clone, Debug, PartialEq)]
+pub enum Explanation<S: State> {
+    /// A transition to the target can execute from the current state
+    Available,
+
+    /// No transition in the machine leads to the target state
+    NoTransitionTo { target: S },
+
+    /// Transitions to the target exist, but every one of them is blocked
+    Blocked { reasons: Vec<BlockReason<S>> },
+}
+
+impl<S: State> Explanation<S> {
+    /// Check if the target is currently reachable in one step (pure)
+    pub fn is_available(&self) -> bool {
+        matches!(self, Self::Available)
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Explain whether a transition to `target` is currently available (pure).
+    ///
+    /// Every transition leading to `target` is inspected, and each one that
+    /// cannot execute contributes a [`BlockReason`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use mindset::builder::{StateMachineBuilder, TransitionBuilder};
+    /// use mindset::core::Guard;
+    /// use mindset::effects::{BlockReason, Explanation};
+    /// use mindset::state_enum;
+    ///
+    /// state_enum! {
+    ///     enum Order {
+    ///         Draft,
+    ///         Paid,
+    ///     }
+    ///     final: [Paid]
+    /// }
+    ///
+    /// let machine = StateMachineBuilder::<Order, ()>::new()
+    ///     .initial(Order::Draft)
+    ///     .transition(
+    ///         TransitionBuilder::new()
+    ///             .from(Order::Draft)
+    ///             .to(Order::Paid)
+    ///             .guard(Guard::new(|_: &Order| false).with_label("cart is empty"))
+    ///             .succeeds(),
+    ///     )
+    ///     .unwrap()
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let explanation = machine.explain(&Order::Paid);
+    /// assert_eq!(
+    ///     explanation,
+    ///     Explanation::Blocked {
+    ///         reasons: vec![BlockReason::GuardFailed {
+    ///             from: Order::Draft,
+    ///             label: Some("cart is empty".to_string()),
+    ///         }],
+    ///     }
+    /// );
+    /// ```
+    pub fn explain(&self, target: &S) -> Explanation<S> {
+        let current = self.current_state();
+        let candidates: Vec<_> = self
+            .transitions()
+            .iter()
+            .filter(|t| &t.to == target)
+            .collect();
+
+        if candidates.is_empty() {
+            return Explanation::NoTransitionTo {
+                target: target.clone(),
+            };
+        }
+
+        if candidates.iter().any(|t| t.can_execute(current)) {
+            return Explanation::Available;
+        }
+
+        let reasons = candidates
+            .into_iter()
+            .map(|t| {
+                if &t.from != current {
+                    BlockReason::WrongSourceState {
+                        required: t.from.clone(),
+                        current: current.clone(),
+                    }
+                } else if let Some(flag) = &t.flag {
+                    BlockReason::DisabledByFlag { flag: flag.clone() }
+                } else {
+                    BlockReason::GuardFailed {
+                        from: current.clone(),
+                        label: t.guard.as_ref().and_then(|g| g.label()).map(String::from),
+                    }
+                }
+            })
+            .collect();
+
+        Explanation::Blocked { reasons }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Guard;
+    use crate::effects::{Transition, TransitionResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+    use stillwater::prelude::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Start,
+        Middle,
+        End,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Start => "Start",
+                Self::Middle => "Middle",
+                Self::End => "End",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::End)
+        }
+    }
+
+    fn transition(
+        from: TestState,
+        to: TestState,
+        guard: Option<Guard<TestState>>,
+    ) -> Transition<TestState, ()> {
+        let target = to.clone();
+        Transition {
+            from,
+            to,
+            guard,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(move || pure(TransitionResult::Success(target.clone())).boxed()),
+        }
+    }
+
+    #[test]
+    fn explain_reports_available_transition() {
+        let mut machine = StateMachine::new(TestState::Start);
+        machine.add_transition(transition(TestState::Start, TestState::Middle, None));
+
+        assert!(machine.explain(&TestState::Middle).is_available());
+    }
+
+    #[test]
+    fn explain_reports_missing_transition() {
+        let mut machine = StateMachine::new(TestState::Start);
+        machine.add_transition(transition(TestState::Start, TestState::Middle, None));
+
+        assert_eq!(
+            machine.explain(&TestState::End),
+            Explanation::NoTransitionTo {
+                target: TestState::End
+            }
+        );
+    }
+
+    #[test]
+    fn explain_reports_wrong_source_and_failing_guard() {
+        let mut machine = StateMachine::new(TestState::Start);
+        machine.add_transition(transition(TestState::Middle, TestState::End, None));
+        machine.add_transition(transition(
+            TestState::Start,
+            TestState::End,
+            Some(Guard::new(|_: &TestState| false).with_label("work not done")),
+        ));
+
+        let explanation = machine.explain(&TestState::End);
+        let Explanation::Blocked { reasons } = explanation else {
+            panic!("Expected Blocked explanation");
+        };
+
+        assert_eq!(
+            reasons,
+            vec![
+                BlockReason::WrongSourceState {
+                    required: TestState::Middle,
+                    current: TestState::Start,
+                },
+                BlockReason::GuardFailed {
+                    from: TestState::Start,
+                    label: Some("work not done".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            reasons[1].to_string(),
+            "guard 'work not done' rejected state 'Start'"
+        );
+    }
+
+    #[test]
+    fn block_reason_display_key_carries_arguments() {
+        let reason = BlockReason::WrongSourceState {
+            required: TestState::Middle,
+            current: TestState::Start,
+        };
+        let key = reason.display_key();
+
+        assert_eq!(key.key, "explain.wrong_source_state");
+        assert_eq!(key.fallback, reason.to_string());
+        assert_eq!(key.args["required"], "Middle");
+        assert_eq!(key.args["current"], "Start");
+    }
+}
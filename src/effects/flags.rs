@@ -0,0 +1,125 @@
+//! Feature-flagged transitions.
+//!
+//! A transition marked with [`Transition::behind_flag`] only executes while
+//! its flag is enabled. Flags are evaluated on every step through the
+//! environment's [`FeatureFlagProvider`], so new workflow branches can be
+//! rolled out gradually without changing the call sites that drive the
+//! machine. Plain `step()` has no flag provider and treats every flagged
+//! transition as disabled; drive flagged machines with
+//! `StateMachine::step_with_flags`.
+
+use crate::core::State;
+use crate::effects::transition::Transition;
+
+/// Source of feature flag values, e.g. a flag service client.
+pub trait FeatureFlagProvider: Send + Sync {
+    /// Whether `flag` is currently enabled
+    fn is_enabled(&self, flag: &str) -> bool;
+}
+
+/// Environment capability exposing a [`FeatureFlagProvider`].
+pub trait HasFeatureFlags {
+    /// The flags transitions are evaluated against
+    fn feature_flags(&self) -> &dyn FeatureFlagProvider;
+}
+
+impl<S: State, Env> Transition<S, Env> {
+    /// Only execute this transition while `flag` is enabled.
+    pub fn behind_flag(self, flag: impl Into<String>) -> Self {
+        Self {
+            flag: Some(flag.into()),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::effects::{StateMachine, StepResult};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashSet;
+    use std::sync::{Arc, RwLock};
+    use stillwater::effect::Effect;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Checkout {
+        Cart,
+        OneClick,
+        Classic,
+    }
+
+    impl State for Checkout {
+        fn name(&self) -> &str {
+            match self {
+                Self::Cart => "Cart",
+                Self::OneClick => "OneClick",
+                Self::Classic => "Classic",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            !matches!(self, Self::Cart)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestEnv {
+        enabled: Arc<RwLock<HashSet<String>>>,
+    }
+
+    impl FeatureFlagProvider for RwLock<HashSet<String>> {
+        fn is_enabled(&self, flag: &str) -> bool {
+            self.read().unwrap().contains(flag)
+        }
+    }
+
+    impl HasFeatureFlags for TestEnv {
+        fn feature_flags(&self) -> &dyn FeatureFlagProvider {
+            self.enabled.as_ref()
+        }
+    }
+
+    fn machine() -> StateMachine<Checkout, TestEnv> {
+        let mut machine = StateMachine::new(Checkout::Cart);
+        machine.add_transition(
+            simple_transition(Checkout::Cart, Checkout::OneClick).behind_flag("one-click"),
+        );
+        machine.add_transition(simple_transition(Checkout::Cart, Checkout::Classic));
+        machine
+    }
+
+    async fn target(
+        machine: &StateMachine<Checkout, TestEnv>,
+        env: &TestEnv,
+    ) -> StepResult<Checkout> {
+        machine.step_with_flags().run(env).await.unwrap().1
+    }
+
+    #[tokio::test]
+    async fn flag_is_evaluated_on_each_step() {
+        let env = TestEnv::default();
+        let machine = machine();
+
+        assert_eq!(
+            target(&machine, &env).await,
+            StepResult::Transitioned(Checkout::Classic)
+        );
+
+        env.enabled.write().unwrap().insert("one-click".to_string());
+        assert_eq!(
+            target(&machine, &env).await,
+            StepResult::Transitioned(Checkout::OneClick)
+        );
+    }
+
+    #[tokio::test]
+    async fn plain_step_skips_flagged_transitions() {
+        let env = TestEnv::default();
+        env.enabled.write().unwrap().insert("one-click".to_string());
+
+        let (_, result, _) = machine().step().run(&env).await.unwrap();
+        assert_eq!(result, StepResult::Transitioned(Checkout::Classic));
+    }
+}
@@ -0,0 +1,145 @@
+//! Driving a machine with a step budget.
+//!
+//! Hosts that advance a machine as far as it will go in one call (e.g. a
+//! request handler that runs until the workflow waits for input) give up
+//! control of latency: a long chain of transitions, or a transition that
+//! keeps retrying, runs to the end. [`StateMachine::run_with_fuel`] caps the
+//! number of steps per call and reports whether work remains, so the host
+//! can resume later.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepResult};
+use crate::effects::transition::TransitionError;
+use stillwater::effect::Effect;
+
+/// Outcome of [`StateMachine::run_with_fuel`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuelReport<S: State> {
+    /// Results of the steps taken, oldest first
+    pub results: Vec<StepResult<S>>,
+    /// Whether the budget ran out while the machine could still make
+    /// progress; false once it is final, aborted, paused or awaiting a
+    /// signal
+    pub more_work: bool,
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Step and apply up to `fuel` times, stopping early once the machine
+    /// is final, aborts, pauses or waits for a signal.
+    ///
+    /// Retries consume fuel like transitions. A passed deadline moves the
+    /// machine to its deadline state instead of stepping. Fails like
+    /// `step()` if no transition can execute; steps taken before are kept
+    /// applied.
+    pub async fn run_with_fuel(
+        &mut self,
+        env: &Env,
+        fuel: usize,
+    ) -> Result<FuelReport<S>, TransitionError> {
+        let mut results = Vec::new();
+        while results.len() < fuel && !self.is_final() {
+            if self.enforce_deadline() {
+                break;
+            }
+            let (from, result, attempt) = self.step().run(env).await?;
+            self.apply_result(from, result.clone(), attempt);
+            let blocked = matches!(
+                result,
+                StepResult::Aborted { .. }
+                    | StepResult::Paused { .. }
+                    | StepResult::AwaitingSignal { .. }
+            );
+            results.push(result);
+            if blocked {
+                return Ok(FuelReport {
+                    results,
+                    more_work: false,
+                });
+            }
+        }
+        Ok(FuelReport {
+            results,
+            more_work: !self.is_final(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::effects::{Transition, TransitionResult};
+    use crate::state_enum;
+    use std::sync::Arc;
+    use stillwater::prelude::*;
+
+    state_enum! {
+        enum Import {
+            Listed,
+            Downloaded,
+            Parsed,
+            Stored,
+            Rejected,
+        }
+        final: [Stored]
+        error: [Rejected]
+    }
+
+    fn machine() -> StateMachine<Import, ()> {
+        let mut machine = StateMachine::new(Import::Listed);
+        machine.add_transition(simple_transition(Import::Listed, Import::Downloaded));
+        machine.add_transition(simple_transition(Import::Downloaded, Import::Parsed));
+        machine.add_transition(simple_transition(Import::Parsed, Import::Stored));
+        machine
+    }
+
+    #[tokio::test]
+    async fn budget_limits_steps_per_call() {
+        let mut machine = machine();
+
+        let first = machine.run_with_fuel(&(), 2).await.unwrap();
+        assert_eq!(first.results.len(), 2);
+        assert!(first.more_work);
+        assert_eq!(machine.current_state(), &Import::Parsed);
+
+        let second = machine.run_with_fuel(&(), 2).await.unwrap();
+        assert_eq!(
+            second,
+            FuelReport {
+                results: vec![StepResult::Transitioned(Import::Stored)],
+                more_work: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn paused_machines_report_no_more_work() {
+        let mut machine = machine();
+        machine.pause("maintenance");
+
+        let report = machine.run_with_fuel(&(), 5).await.unwrap();
+        assert_eq!(report.results.len(), 1);
+        assert!(!report.more_work);
+    }
+
+    #[tokio::test]
+    async fn aborted_machines_report_no_more_work() {
+        let mut machine = StateMachine::<Import, ()>::new(Import::Listed);
+        machine.add_transition(Transition {
+            action: Arc::new(|| {
+                pure(TransitionResult::Abort {
+                    reason: "malformed listing".to_string(),
+                    error_state: Import::Rejected,
+                })
+                .boxed()
+            }),
+            ..simple_transition(Import::Listed, Import::Downloaded)
+        });
+        machine.add_transition(simple_transition(Import::Rejected, Import::Listed));
+
+        let report = machine.run_with_fuel(&(), 5).await.unwrap();
+        assert_eq!(report.results.len(), 1);
+        assert!(!report.more_work);
+        assert_eq!(machine.current_state(), &Import::Rejected);
+    }
+}
@@ -0,0 +1,224 @@
+//! Entry and exit hooks per state.
+//!
+//! Setup and teardown that belong to a state rather than to a transition,
+//! such as opening a session on entering `Connected` and closing it on
+//! leaving, would otherwise be repeated in every action targeting or
+//! leaving the state. Hooks registered with [`StateMachine::on_entry`] and
+//! [`StateMachine::on_exit`] are effects run against the step's
+//! environment, so they can do the same I/O actions do.
+//!
+//! They run as part of the step, once the action has decided to move to a
+//! state of another name: first the exit hook of the state left, then the
+//! entry hook of the state entered. Aborts count as leaving the state for
+//! the error state; retries, pauses, signal waits and transitions back into
+//! the same state run no hooks. A failing hook fails the step with its
+//! error, so the result is not applied and the next step runs the action
+//! again; hooks should therefore tolerate being repeated.
+//!
+//! Speculation and replay run no steps and so no hooks, and forced
+//! transitions bypass actions and hooks alike.
+
+use crate::core::State;
+use crate::effects::machine::StateMachine;
+use crate::effects::transition::{TransitionError, TransitionResult};
+use std::collections::HashMap;
+use std::sync::Arc;
+use stillwater::effect::{BoxedEffect, Effect};
+
+/// Hook receiving the state entered or left, returning the effect to run.
+pub type StateHook<S, Env> = Arc<dyn Fn(&S) -> BoxedEffect<(), TransitionError, Env> + Send + Sync>;
+
+/// Hooks of one state.
+pub(crate) struct StateHooks<S: State, Env> {
+    pub(crate) entry: Option<StateHook<S, Env>>,
+    pub(crate) exit: Option<StateHook<S, Env>>,
+}
+
+/// Hooks of a machine by state name.
+pub(crate) type StateHookTable<S, Env> = HashMap<String, StateHooks<S, Env>>;
+
+impl<S: State, Env> Default for StateHooks<S, Env> {
+    fn default() -> Self {
+        Self {
+            entry: None,
+            exit: None,
+        }
+    }
+}
+
+impl<S: State, Env> Clone for StateHooks<S, Env> {
+    fn clone(&self) -> Self {
+        Self {
+            entry: self.entry.clone(),
+            exit: self.exit.clone(),
+        }
+    }
+}
+
+/// Run the exit hook of `from` and the entry hook of the state `result`
+/// moves to, if that state has another name.
+pub(crate) async fn run_state_hooks<S, Env>(
+    hooks: &StateHookTable<S, Env>,
+    from: &S,
+    result: &TransitionResult<S>,
+    env: &Env,
+) -> Result<(), TransitionError>
+where
+    S: State,
+    Env: Clone + Send + Sync + 'static,
+{
+    let entered = match result {
+        TransitionResult::Success(to) => to,
+        TransitionResult::Abort { error_state, .. } => error_state,
+        TransitionResult::Retry { .. } => return Ok(()),
+    };
+    if entered.name() == from.name() {
+        return Ok(());
+    }
+    if let Some(exit) = hooks.get(from.name()).and_then(|h| h.exit.as_ref()) {
+        exit(from).run(env).await?;
+    }
+    if let Some(entry) = hooks.get(entered.name()).and_then(|h| h.entry.as_ref()) {
+        entry(entered).run(env).await?;
+    }
+    Ok(())
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Run `hook` whenever a step moves the machine into `state`, replacing
+    /// an earlier entry hook. States are matched by name.
+    pub fn on_entry<F>(&mut self, state: &S, hook: F)
+    where
+        F: Fn(&S) -> BoxedEffect<(), TransitionError, Env> + Send + Sync + 'static,
+    {
+        self.state_hooks_mut(state).entry = Some(Arc::new(hook));
+    }
+
+    /// Run `hook` whenever a step moves the machine out of `state`,
+    /// replacing an earlier exit hook. States are matched by name.
+    pub fn on_exit<F>(&mut self, state: &S, hook: F)
+    where
+        F: Fn(&S) -> BoxedEffect<(), TransitionError, Env> + Send + Sync + 'static,
+    {
+        self.state_hooks_mut(state).exit = Some(Arc::new(hook));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::effects::{StepResult, Transition};
+    use crate::state_enum;
+    use std::sync::Mutex;
+    use stillwater::prelude::*;
+
+    state_enum! {
+        enum Link {
+            Idle,
+            Connected,
+            Failed,
+        }
+        error: [Failed]
+    }
+
+    /// Environment the hooks log to
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn recording(what: &'static str) -> impl Fn(&Link) -> BoxedEffect<(), TransitionError, Log> {
+        move |state: &Link| {
+            let line = format!("{what} {}", state.name());
+            from_async(move |log: &Log| {
+                log.lock().unwrap().push(line.clone());
+                async { Ok(()) }
+            })
+            .boxed()
+        }
+    }
+
+    fn aborting(from: Link, to: Link) -> Transition<Link, Log> {
+        Transition {
+            action: Arc::new(|| {
+                pure(TransitionResult::Abort {
+                    reason: "reset".to_string(),
+                    error_state: Link::Failed,
+                })
+                .boxed()
+            }),
+            ..simple_transition(from, to)
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks_run_in_steps_that_leave_and_enter_states() {
+        let log = Log::default();
+        let mut machine = StateMachine::<Link, Log>::new(Link::Idle);
+        machine.add_transition(simple_transition(Link::Idle, Link::Connected));
+        machine.add_transition(aborting(Link::Connected, Link::Idle));
+        machine.on_exit(&Link::Idle, recording("exit"));
+        machine.on_entry(&Link::Connected, recording("enter"));
+        machine.on_exit(&Link::Connected, recording("exit"));
+        machine.on_entry(&Link::Failed, recording("enter"));
+
+        for _ in 0..2 {
+            let (from, result, attempt) = machine.step().run(&log).await.unwrap();
+            machine.apply_result(from, result, attempt);
+        }
+
+        assert_eq!(machine.current_state(), &Link::Failed);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "exit Idle",
+                "enter Connected",
+                "exit Connected",
+                "enter Failed"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_hooks_fail_the_step() {
+        let mut machine = StateMachine::<Link, Log>::new(Link::Idle);
+        machine.add_transition(simple_transition(Link::Idle, Link::Connected));
+        machine.on_entry(&Link::Connected, |_: &Link| {
+            fail(TransitionError::ActionFailed("no session".to_string())).boxed()
+        });
+
+        let result = machine.step().run(&Log::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TransitionError::ActionFailed(ref message)) if message == "no session"
+        ));
+        assert_eq!(machine.current_state(), &Link::Idle);
+    }
+
+    #[tokio::test]
+    async fn retries_and_speculation_run_no_hooks() {
+        let log = Log::default();
+        let mut machine = StateMachine::<Link, Log>::new(Link::Idle);
+        machine.add_transition(Transition {
+            action: Arc::new(|| {
+                pure(TransitionResult::Retry {
+                    feedback: "busy".to_string(),
+                    current_state: Link::Idle,
+                })
+                .boxed()
+            }),
+            ..simple_transition(Link::Idle, Link::Connected)
+        });
+        machine.on_exit(&Link::Idle, recording("exit"));
+        machine.on_entry(&Link::Connected, recording("enter"));
+
+        let (from, result, attempt) = machine.step().run(&log).await.unwrap();
+        assert!(matches!(result, StepResult::Retry { .. }));
+        machine.apply_result(from, result, attempt);
+        let mut speculation = machine.speculate();
+        speculation.succeed().unwrap();
+        assert_eq!(speculation.machine().current_state(), &Link::Connected);
+
+        assert_eq!(machine.current_state(), &Link::Idle);
+        assert!(log.lock().unwrap().is_empty());
+    }
+}
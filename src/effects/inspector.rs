@@ -0,0 +1,76 @@
+//! Read-only inspection handles for state machines.
+//!
+//! A [`MachineInspector`] lets monitoring or render threads observe a
+//! machine while its owner keeps stepping it. The machine publishes an
+//! immutable [`MachineView`] after every change; inspectors only ever see
+//! complete views and cannot mutate the machine.
+
+use crate::checkpoint::MachineMetadata;
+use crate::core::{State, StateHistory};
+use std::sync::{Arc, RwLock};
+
+/// Immutable snapshot of a machine published to inspectors.
+#[derive(Clone, Debug)]
+pub struct MachineView<S: State> {
+    /// Current state of the machine
+    pub current: S,
+    /// Whether the current state is final
+    pub is_final: bool,
+    /// Attempts made so far at the current transition
+    pub attempt_count: usize,
+    /// Transition history
+    pub history: StateHistory<S>,
+    /// Machine metadata
+    pub metadata: MachineMetadata,
+}
+
+impl<S: State> MachineView<S> {
+    /// Number of transitions taken so far (pure)
+    pub fn transitions_taken(&self) -> usize {
+        self.history.transitions().len()
+    }
+}
+
+pub(crate) type SharedView<S> = Arc<RwLock<Arc<MachineView<S>>>>;
+
+/// Cheap, cloneable, read-only handle onto a running [`StateMachine`].
+///
+/// Obtain one with [`StateMachine::inspector`]. Reads return the most
+/// recently published [`MachineView`]; they never block the owner for
+/// longer than an `Arc` swap.
+///
+/// [`StateMachine`]: crate::effects::StateMachine
+/// [`StateMachine::inspector`]: crate::effects::StateMachine::inspector
+#[derive(Clone, Debug)]
+pub struct MachineInspector<S: State> {
+    view: SharedView<S>,
+}
+
+impl<S: State> MachineInspector<S> {
+    pub(crate) fn new(view: SharedView<S>) -> Self {
+        Self { view }
+    }
+
+    /// Latest published view of the machine
+    pub fn view(&self) -> Arc<MachineView<S>> {
+        // A poisoned lock still holds a complete view, since writers only
+        // swap in fully built ones.
+        let guard = self.view.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&guard)
+    }
+
+    /// Current state of the machine
+    pub fn current_state(&self) -> S {
+        self.view().current.clone()
+    }
+
+    /// Whether the machine is in a final state
+    pub fn is_final(&self) -> bool {
+        self.view().is_final
+    }
+}
+
+pub(crate) fn publish<S: State>(shared: &SharedView<S>, view: MachineView<S>) {
+    let mut guard = shared.write().unwrap_or_else(|e| e.into_inner());
+    *guard = Arc::new(view);
+}
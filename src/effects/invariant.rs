@@ -0,0 +1,156 @@
+//! Invariants checked when a machine completes.
+//!
+//! Graph edits can silently make mandatory steps skippable. Invariants
+//! state what every completed run must satisfy, e.g. "history contains a
+//! Paid -> Shipped transition". They are evaluated automatically whenever
+//! the machine enters a final state; each violation is recorded in history
+//! as [`HistoryEvent::InvariantViolated`] and reported by
+//! [`StateMachine::verify_invariants`].
+//!
+//! [`HistoryEvent::InvariantViolated`]: crate::core::HistoryEvent::InvariantViolated
+
+use crate::core::{State, StateHistory};
+use crate::effects::machine::StateMachine;
+use std::sync::Arc;
+use thiserror::Error;
+
+type InvariantCheck<S> = Arc<dyn Fn(&S, &StateHistory<S>) -> bool + Send + Sync>;
+
+/// Named predicate over the final state and the history that led to it.
+pub struct Invariant<S: State> {
+    name: String,
+    check: InvariantCheck<S>,
+}
+
+impl<S: State> Clone for Invariant<S> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            check: Arc::clone(&self.check),
+        }
+    }
+}
+
+impl<S: State> Invariant<S> {
+    /// Create an invariant from a pure predicate.
+    pub fn new<F>(name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(&S, &StateHistory<S>) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            check: Arc::new(check),
+        }
+    }
+
+    /// Name the invariant is reported under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check the invariant (pure)
+    pub fn holds(&self, state: &S, history: &StateHistory<S>) -> bool {
+        (self.check)(state, history)
+    }
+}
+
+/// Invariants violated by a completed run.
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("Final state '{state}' violates invariants: {}", violated.join(", "))]
+pub struct InvariantViolation {
+    /// Final state the machine completed in
+    pub state: String,
+    /// Names of the violated invariants
+    pub violated: Vec<String>,
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Check every invariant against the current state and history (pure).
+    ///
+    /// Invariants only constrain completed runs, so a machine that is not
+    /// in a final state always passes.
+    pub fn verify_invariants(&self) -> Result<(), InvariantViolation> {
+        if !self.is_final() {
+            return Ok(());
+        }
+        let violated: Vec<String> = self
+            .invariants()
+            .iter()
+            .filter(|invariant| !invariant.holds(self.current_state(), self.history()))
+            .map(|invariant| invariant.name().to_string())
+            .collect();
+        if violated.is_empty() {
+            Ok(())
+        } else {
+            Err(InvariantViolation {
+                state: self.current_state().display_name().into_owned(),
+                violated,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::simple_transition;
+    use crate::core::{HistoryEvent, State};
+    use crate::effects::{Invariant, StateMachine, StepResult};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Order {
+        Placed,
+        Paid,
+        Shipped,
+    }
+
+    impl State for Order {
+        fn name(&self) -> &str {
+            match self {
+                Self::Placed => "Placed",
+                Self::Paid => "Paid",
+                Self::Shipped => "Shipped",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Shipped)
+        }
+    }
+
+    fn machine() -> StateMachine<Order, ()> {
+        let mut machine = StateMachine::new(Order::Placed);
+        machine.add_transition(simple_transition(Order::Placed, Order::Paid));
+        machine.add_transition(simple_transition(Order::Paid, Order::Shipped));
+        machine.add_invariant(Invariant::new("paid before shipping", |_, history| {
+            history
+                .transitions()
+                .iter()
+                .any(|t| t.from == Order::Paid && t.to == Order::Shipped)
+        }));
+        machine
+    }
+
+    #[test]
+    fn completed_run_satisfying_invariants_passes() {
+        let mut machine = machine();
+        machine.apply_result(Order::Placed, StepResult::Transitioned(Order::Paid), 1);
+        machine.apply_result(Order::Paid, StepResult::Transitioned(Order::Shipped), 1);
+
+        assert!(machine.verify_invariants().is_ok());
+        assert!(machine.history().events().is_empty());
+    }
+
+    #[test]
+    fn skipped_step_is_recorded_at_completion() {
+        let mut machine = machine();
+        machine.force_transition_to(Order::Shipped, "customer escalation", "ops");
+
+        let violation = machine.verify_invariants().unwrap_err();
+        assert_eq!(violation.violated, vec!["paid before shipping".to_string()]);
+        assert!(matches!(
+            machine.history().events(),
+            [HistoryEvent::InvariantViolated { invariant, .. }] if invariant == "paid before shipping"
+        ));
+    }
+}
@@ -0,0 +1,629 @@
+//! External lock integration for transitions.
+//!
+//! Workflows that touch shared resources can hold a lock from an external
+//! lock service while a transition's action runs. The environment supplies
+//! the lock service through [`HasLockManager`]. Acquiring is asynchronous,
+//! and the lock is released by a guard once the action is done with it,
+//! whatever its outcome (success, retry, abort or error), including when
+//! the step is cancelled or panics.
+//!
+//! Transitions sharing a key queue for the lock rather than fail: a lock
+//! manager waits until the key is free, as [`LocalLocks`] does for machines
+//! driven in one process. [`HasLockManager::lock_timeout`] bounds the wait.
+//!
+//! Static keys ([`Transition::with_lock`]) only need [`HasLockManager`].
+//! Keys computed from the step context ([`Transition::with_lock_by`]) also
+//! need [`HasStepContext`], and with it, acquiring and releasing are
+//! recorded as [`HistoryEvent::LockAcquired`] and
+//! [`HistoryEvent::LockReleased`] in the machine's history. A static key
+//! whose activity should be recorded can be returned from `with_lock_by`.
+
+use crate::core::{HistoryEvent, State};
+use crate::effects::context::{HasStepContext, StepContext, StepEvents};
+use crate::effects::transition::{Transition, TransitionError};
+use chrono::Utc;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use stillwater::effect::{with_timeout, BoxFuture};
+use stillwater::prelude::*;
+use stillwater::TimeoutError;
+
+/// External lock service.
+pub trait LockManager: Send + Sync {
+    /// Acquire the lock for `key`, waiting while it is held elsewhere, or
+    /// explain why it cannot be acquired.
+    ///
+    /// The returned future may be dropped before it completes, e.g. when
+    /// the wait times out; it must then give up its place in the queue.
+    fn acquire<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Release a lock previously acquired for `key`.
+    ///
+    /// Called when a guard is dropped, possibly while unwinding, so it must
+    /// not block or panic.
+    fn release(&self, key: &str);
+}
+
+/// Environment capability exposing a [`LockManager`].
+pub trait HasLockManager {
+    /// The lock service transitions acquire their locks from
+    fn lock_manager(&self) -> &dyn LockManager;
+
+    /// Longest time a transition waits for its lock; `None` waits as long
+    /// as it takes
+    fn lock_timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// In-process [`LockManager`] that queues acquirers of a key in arrival
+/// order.
+///
+/// Suits runners driving many machines in one process. Acquirers that stop
+/// waiting leave the queue.
+#[derive(Debug, Default)]
+pub struct LocalLocks {
+    keys: Mutex<HashMap<String, KeyQueue>>,
+}
+
+#[derive(Debug, Default)]
+struct KeyQueue {
+    /// Ticket the lock was handed to but that has not taken it yet
+    granted: Option<u64>,
+    waiters: VecDeque<(u64, Waker)>,
+    next_ticket: u64,
+}
+
+impl LocalLocks {
+    /// Create a lock manager with no locks held
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `key` is held or handed to a waiter (pure)
+    pub fn is_held(&self, key: &str) -> bool {
+        self.keys().contains_key(key)
+    }
+
+    fn keys(&self) -> MutexGuard<'_, HashMap<String, KeyQueue>> {
+        self.keys.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hand `key` to its next waiter, or free it
+    fn hand_over(keys: &mut HashMap<String, KeyQueue>, key: &str) {
+        let Some(queue) = keys.get_mut(key) else {
+            return;
+        };
+        match queue.waiters.pop_front() {
+            Some((ticket, waker)) => {
+                queue.granted = Some(ticket);
+                waker.wake();
+            }
+            None => {
+                keys.remove(key);
+            }
+        }
+    }
+}
+
+impl LockManager for LocalLocks {
+    fn acquire<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(Acquire {
+            locks: self,
+            key,
+            ticket: None,
+            done: false,
+        })
+    }
+
+    fn release(&self, key: &str) {
+        Self::hand_over(&mut self.keys(), key);
+    }
+}
+
+/// Future of [`LocalLocks::acquire`]
+struct Acquire<'a> {
+    locks: &'a LocalLocks,
+    key: &'a str,
+    ticket: Option<u64>,
+    done: bool,
+}
+
+impl Future for Acquire<'_> {
+    type Output = Result<(), String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut keys = self.locks.keys();
+        let Some(ticket) = self.ticket else {
+            let Some(queue) = keys.get_mut(self.key) else {
+                keys.insert(self.key.to_string(), KeyQueue::default());
+                drop(keys);
+                self.done = true;
+                return Poll::Ready(Ok(()));
+            };
+            let ticket = queue.next_ticket;
+            queue.next_ticket += 1;
+            queue.waiters.push_back((ticket, cx.waker().clone()));
+            drop(keys);
+            self.ticket = Some(ticket);
+            return Poll::Pending;
+        };
+
+        let queue = keys.get_mut(self.key).expect("waiting key is held");
+        if queue.granted == Some(ticket) {
+            queue.granted = None;
+            drop(keys);
+            self.done = true;
+            return Poll::Ready(Ok(()));
+        }
+        if let Some((_, waker)) = queue.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+            waker.clone_from(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket.filter(|_| !self.done) else {
+            return;
+        };
+        let mut keys = self.locks.keys();
+        let Some(queue) = keys.get_mut(self.key) else {
+            return;
+        };
+        if queue.granted == Some(ticket) {
+            // Handed the lock but gone before taking it
+            queue.granted = None;
+            LocalLocks::hand_over(&mut keys, self.key);
+        } else {
+            queue.waiters.retain(|(t, _)| *t != ticket);
+        }
+    }
+}
+
+/// A lock held for a running action, released when dropped.
+struct HeldLock<Env: HasLockManager> {
+    env: Env,
+    key: Arc<str>,
+    events: Option<StepEvents>,
+}
+
+impl<Env: HasLockManager> Drop for HeldLock<Env> {
+    fn drop(&mut self) {
+        self.env.lock_manager().release(&self.key);
+        if let Some(events) = &self.events {
+            events.record(HistoryEvent::LockReleased {
+                key: self.key.to_string(),
+                timestamp: Utc::now(),
+            });
+        }
+    }
+}
+
+/// Acquire `key` from the environment's lock manager within its lock
+/// timeout
+async fn acquire<Env>(env: &Env, key: &Arc<str>) -> Result<(), String>
+where
+    Env: HasLockManager + Clone + Send + Sync + 'static,
+{
+    let Some(timeout) = env.lock_timeout() else {
+        return env.lock_manager().acquire(key).await;
+    };
+    let key = Arc::clone(key);
+    let acquiring = from_async(move |env: &Env| {
+        let env = env.clone();
+        let key = Arc::clone(&key);
+        async move { env.lock_manager().acquire(&key).await }
+    });
+    with_timeout(acquiring, timeout)
+        .run(env)
+        .await
+        .map_err(|error| match error {
+            TimeoutError::Timeout { duration } => format!("not acquired within {duration:?}"),
+            TimeoutError::Inner(reason) => reason,
+        })
+}
+
+impl<S, Env> Transition<S, Env>
+where
+    S: State + 'static,
+    Env: HasLockManager + Clone + Send + Sync + 'static,
+{
+    /// Hold the external lock `key` while this transition's action runs.
+    ///
+    /// The action waits while the lock is held elsewhere. If the lock
+    /// cannot be acquired, or not within the environment's lock timeout,
+    /// the action does not run and the step fails with
+    /// [`TransitionError::LockUnavailable`].
+    pub fn with_lock(self, key: impl Into<String>) -> Self {
+        let key: Arc<str> = Arc::from(key.into());
+        self.locked_by(move |_| Ok(Arc::clone(&key)), |_| None)
+    }
+
+    /// Hold the lock `key` computes around the action, recording its
+    /// activity to the events `events` finds in the environment
+    fn locked_by<K, E>(self, key: K, events: E) -> Self
+    where
+        K: Fn(&Env) -> Result<Arc<str>, TransitionError> + Send + Sync + 'static,
+        E: Fn(&Env) -> Option<StepEvents> + Send + Sync + 'static,
+    {
+        let key = Arc::new(key);
+        let events = Arc::new(events);
+        let action = self.action;
+        Self {
+            action: Arc::new(move || {
+                let key = Arc::clone(&key);
+                let events = Arc::clone(&events);
+                let action = Arc::clone(&action);
+                from_async(move |env: &Env| {
+                    let env = env.clone();
+                    async move {
+                        let key = key(&env)?;
+                        acquire(&env, &key).await.map_err(|reason| {
+                            TransitionError::LockUnavailable {
+                                key: key.to_string(),
+                                reason,
+                            }
+                        })?;
+                        let events = events(&env);
+                        if let Some(events) = &events {
+                            events.record(HistoryEvent::LockAcquired {
+                                key: key.to_string(),
+                                timestamp: Utc::now(),
+                            });
+                        }
+                        let _held = HeldLock {
+                            env: env.clone(),
+                            key,
+                            events,
+                        };
+                        action().run(&env).await
+                    }
+                })
+                .boxed()
+            }),
+            ..self
+        }
+    }
+}
+
+impl<S, Env> Transition<S, Env>
+where
+    S: State + 'static,
+    Env: HasLockManager + HasStepContext<S> + Clone + Send + Sync + 'static,
+{
+    /// Hold an external lock whose key is computed from the step context,
+    /// e.g. the customer id of the machine's labels.
+    ///
+    /// Transitions of different machine instances computing the same key
+    /// never run their actions concurrently; they take turns in the order
+    /// they asked for the lock, and acquiring and releasing are recorded in
+    /// the machine's history. The machine must be driven
+    /// with `StateMachine::step_with_context`; without a context the step
+    /// fails with [`TransitionError::ActionFailed`].
+    pub fn with_lock_by<F>(self, key: F) -> Self
+    where
+        F: Fn(&StepContext<S>) -> String + Send + Sync + 'static,
+    {
+        const NO_CONTEXT: &str =
+            "lock key needs a step context; drive the machine with step_with_context";
+        self.locked_by(
+            move |env: &Env| {
+                env.step_context()
+                    .map(|context| Arc::from(key(context)))
+                    .ok_or_else(|| TransitionError::ActionFailed(NO_CONTEXT.to_string()))
+            },
+            |env: &Env| env.step_context().map(|context| context.events.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult, TransitionResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::Mutex;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Idle,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Idle => "Idle",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingLocks {
+        locks: LocalLocks,
+        log: Mutex<Vec<String>>,
+    }
+
+    impl RecordingLocks {
+        fn record(&self, entry: String) {
+            self.log.lock().unwrap().push(entry);
+        }
+    }
+
+    impl LockManager for RecordingLocks {
+        fn acquire<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                self.locks.acquire(key).await?;
+                self.record(format!("acquire {key}"));
+                Ok(())
+            })
+        }
+
+        fn release(&self, key: &str) {
+            self.locks.release(key);
+            self.record(format!("release {key}"));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestEnv {
+        locks: Arc<RecordingLocks>,
+        timeout: Option<Duration>,
+        context: Option<Arc<StepContext<TestState>>>,
+    }
+
+    impl TestEnv {
+        fn impatient() -> Self {
+            Self {
+                timeout: Some(Duration::from_millis(10)),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl HasLockManager for TestEnv {
+        fn lock_manager(&self) -> &dyn LockManager {
+            self.locks.as_ref()
+        }
+
+        fn lock_timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+    }
+
+    impl HasStepContext<TestState> for TestEnv {
+        fn step_context(&self) -> Option<&StepContext<TestState>> {
+            self.context.as_deref()
+        }
+
+        fn with_step_context(&self, context: Arc<StepContext<TestState>>) -> Self {
+            Self {
+                context: Some(context),
+                ..self.clone()
+            }
+        }
+    }
+
+    fn machine(result: TransitionResult<TestState>) -> StateMachine<TestState, TestEnv> {
+        let mut machine = StateMachine::new(TestState::Idle);
+        machine.add_transition(
+            Transition {
+                from: TestState::Idle,
+                to: TestState::Done,
+                guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
+                action: Arc::new(move || pure(result.clone()).boxed()),
+            }
+            .with_lock("account-7"),
+        );
+        machine
+    }
+
+    #[tokio::test]
+    async fn lock_is_held_around_action_and_released() {
+        let env = TestEnv::default();
+        let machine = machine(TransitionResult::Success(TestState::Done));
+
+        let (_, result, _) = machine.step().run(&env).await.unwrap();
+
+        assert_eq!(result, StepResult::Transitioned(TestState::Done));
+        assert_eq!(
+            *env.locks.log.lock().unwrap(),
+            vec!["acquire account-7", "release account-7"]
+        );
+    }
+
+    #[tokio::test]
+    async fn lock_is_released_on_retry() {
+        let env = TestEnv::default();
+        let machine = machine(TransitionResult::Retry {
+            feedback: "later".to_string(),
+            current_state: TestState::Idle,
+        });
+
+        machine.step().run(&env).await.unwrap();
+
+        assert!(!env.locks.locks.is_held("account-7"));
+    }
+
+    #[tokio::test]
+    async fn lock_is_released_when_the_step_is_cancelled() {
+        let env = TestEnv::default();
+        let mut machine = StateMachine::new(TestState::Idle);
+        machine.add_transition(
+            Transition {
+                action: Arc::new(|| from_async(|_: &TestEnv| std::future::pending()).boxed()),
+                ..crate::builder::simple_transition(TestState::Idle, TestState::Done)
+            }
+            .with_lock("account-7"),
+        );
+
+        let step = machine.step_with_context().run(&env);
+        let timed_out = tokio::time::timeout(std::time::Duration::from_millis(10), step).await;
+
+        assert!(timed_out.is_err());
+        assert!(!env.locks.locks.is_held("account-7"));
+        assert_eq!(
+            *env.locks.log.lock().unwrap(),
+            vec!["acquire account-7", "release account-7"]
+        );
+    }
+
+    #[tokio::test]
+    async fn static_keys_need_no_step_context() {
+        #[derive(Clone, Default)]
+        struct LocksOnly(Arc<RecordingLocks>);
+
+        impl HasLockManager for LocksOnly {
+            fn lock_manager(&self) -> &dyn LockManager {
+                self.0.as_ref()
+            }
+        }
+
+        let env = LocksOnly::default();
+        let mut machine = StateMachine::<TestState, LocksOnly>::new(TestState::Idle);
+        machine.add_transition(
+            crate::builder::simple_transition(TestState::Idle, TestState::Done)
+                .with_lock("account-7"),
+        );
+
+        let (_, result, _) = machine.step().run(&env).await.unwrap();
+
+        assert_eq!(result, StepResult::Transitioned(TestState::Done));
+        assert_eq!(
+            *env.0.log.lock().unwrap(),
+            vec!["acquire account-7", "release account-7"]
+        );
+    }
+
+    #[tokio::test]
+    async fn lock_activity_is_recorded_in_history() {
+        let env = TestEnv::default();
+        let mut machine = StateMachine::new(TestState::Idle);
+        machine.add_transition(
+            crate::builder::simple_transition(TestState::Idle, TestState::Done)
+                .with_lock_by(|_| "account-7".to_string()),
+        );
+
+        let (from, result, attempt) = machine.step_with_context().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+
+        let events = machine.history().events();
+        assert!(matches!(
+            events,
+            [
+                HistoryEvent::LockAcquired { key: acquired, .. },
+                HistoryEvent::LockReleased { key: released, .. },
+            ] if acquired == "account-7" && released == "account-7"
+        ));
+    }
+
+    #[tokio::test]
+    async fn lock_not_acquired_in_time_skips_action() {
+        let env = TestEnv::impatient();
+        env.locks.acquire("account-7").await.unwrap();
+        let machine = machine(TransitionResult::Success(TestState::Done));
+
+        let result = machine.step().run(&env).await;
+        assert!(matches!(
+            result,
+            Err(TransitionError::LockUnavailable { ref key, .. }) if key == "account-7"
+        ));
+
+        // The timed out step left the queue, so the lock is free once released
+        env.locks.release("account-7");
+        let (_, result, _) = machine.step().run(&env).await.unwrap();
+        assert_eq!(result, StepResult::Transitioned(TestState::Done));
+    }
+
+    #[tokio::test]
+    async fn steps_sharing_a_key_queue_for_the_lock() {
+        let env = TestEnv::default();
+        let order = |name: &'static str| {
+            let mut machine = StateMachine::new(TestState::Idle);
+            machine.add_transition(
+                Transition {
+                    action: Arc::new(move || {
+                        from_async(move |env: &TestEnv| {
+                            let locks = Arc::clone(&env.locks);
+                            async move {
+                                locks.record(format!("start {name}"));
+                                tokio::time::sleep(Duration::from_millis(5)).await;
+                                locks.record(format!("end {name}"));
+                                Ok(TransitionResult::Success(TestState::Done))
+                            }
+                        })
+                        .boxed()
+                    }),
+                    ..crate::builder::simple_transition(TestState::Idle, TestState::Done)
+                }
+                .with_lock("account-7"),
+            );
+            machine
+        };
+        let (first, second) = (order("a"), order("b"));
+
+        let (a, b) = tokio::join!(first.step().run(&env), second.step().run(&env));
+
+        assert_eq!(a.unwrap().1, StepResult::Transitioned(TestState::Done));
+        assert_eq!(b.unwrap().1, StepResult::Transitioned(TestState::Done));
+        assert_eq!(
+            *env.locks.log.lock().unwrap(),
+            vec![
+                "acquire account-7",
+                "start a",
+                "end a",
+                "release account-7",
+                "acquire account-7",
+                "start b",
+                "end b",
+                "release account-7",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn lock_key_is_computed_per_instance() {
+        let env = TestEnv::impatient();
+        env.locks.acquire("customer-42").await.unwrap();
+        let for_customer = |customer: &str| {
+            let mut machine = StateMachine::new(TestState::Idle);
+            machine.add_transition(
+                crate::builder::simple_transition(TestState::Idle, TestState::Done)
+                    .with_lock_by(|context| format!("customer-{}", context.labels["customer"])),
+            );
+            machine.set_label("customer", customer);
+            machine
+        };
+
+        let busy = for_customer("42").step_with_context().run(&env).await;
+        assert!(matches!(
+            busy,
+            Err(TransitionError::LockUnavailable { ref key, .. }) if key == "customer-42"
+        ));
+
+        let (_, result, _) = for_customer("7")
+            .step_with_context()
+            .run(&env)
+            .await
+            .unwrap();
+        assert_eq!(result, StepResult::Transitioned(TestState::Done));
+        assert_eq!(
+            env.locks.log.lock().unwrap()[1..],
+            ["acquire customer-7", "release customer-7"]
+        );
+    }
+}
@@ -1,11 +1,39 @@
 //! State machine that executes effectful transitions.
-
-use crate::checkpoint::MachineMetadata;
-use crate::core::{State, StateHistory, StateTransition};
-use crate::effects::transition::{Transition, TransitionError, TransitionResult};
-use chrono::Utc;
-use stillwater::effect::Effect;
-use stillwater::prelude::*;
+//!
+//! # Persistence
+//!
+//! Checkpoints record what a machine has done: its states, history and
+//! metadata, including pauses, pending signals, timers, the deadline,
+//! namespace, labels and machine id. How it behaves is code and is not
+//! persisted; transitions, hooks, invariants, signal waits, scrubbers,
+//! redactions, id generators, capability requirements, snapshot policies,
+//! history bounds and the monotonic timestamp setting are configured again
+//! on the machine a checkpoint is resumed into.
+
+use crate::checkpoint::{
+    IdGenerator, MachineMetadata, MachineStats, PauseInfo, PendingTimer, Signal, StateMigration,
+    StateRedaction, UuidIds,
+};
+use crate::core::{
+    ForcedTransition, HistoryEvent, Scrubber, SnapshotPolicy, State, StateHistory, StateTransition,
+};
+use crate::effects::capabilities::CapabilityKey;
+use crate::effects::context::StepEvents;
+use crate::effects::flags::{FeatureFlagProvider, HasFeatureFlags};
+use crate::effects::hooks::{run_state_hooks, StateHookTable, StateHooks};
+use crate::effects::inspector::{self, MachineInspector, MachineView, SharedView};
+use crate::effects::invariant::Invariant;
+use crate::effects::signal::SignalWait;
+use crate::effects::transition::{
+    CandidateCheck, DefinitionError, Transition, TransitionContext, TransitionError,
+    TransitionResult,
+};
+use bincode::Options;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::panic::Location;
+use std::sync::{Arc, RwLock};
+use stillwater::effect::{from_async, BoxedEffect, Effect};
 
 /// Result of executing a single step
 #[derive(Clone, Debug, PartialEq)]
@@ -18,9 +46,113 @@ pub enum StepResult<S: State> {
 
     /// Transition aborted permanently
     Aborted { reason: String, error_state: S },
+
+    /// Machine is paused; no transition was attempted
+    Paused { reason: String },
+
+    /// Current state waits for an external signal; no transition was attempted
+    AwaitingSignal { signal: String },
+}
+
+/// Effect returned by `StateMachine::step()`.
+///
+/// Wraps the action effect directly instead of boxing a mapped effect
+/// around it. The action is a `BoxedEffect` for `Transition`s and the
+/// transition set's own effect type for `StaticTransition`s.
+pub(crate) enum StepEffect<
+    S: State,
+    Env,
+    A = BoxedEffect<TransitionResult<S>, TransitionError, Env>,
+> {
+    Failed(TransitionError),
+    Ready((S, StepResult<S>, usize)),
+    Run {
+        action: A,
+        from: S,
+        attempt_count: usize,
+        /// Target a successful action must reach, if the transition fixes it
+        target: Option<S>,
+        /// Entry and exit hooks, run once the action decided to move
+        hooks: Arc<StateHookTable<S, Env>>,
+    },
+}
+
+impl<S, Env, A> Effect for StepEffect<S, Env, A>
+where
+    S: State,
+    Env: Clone + Send + Sync + 'static,
+    A: Effect<Output = TransitionResult<S>, Error = TransitionError, Env = Env>,
+{
+    type Output = (S, StepResult<S>, usize);
+    type Error = TransitionError;
+    type Env = Env;
+
+    async fn run(self, env: &Env) -> Result<Self::Output, TransitionError> {
+        match self {
+            Self::Failed(error) => Err(error),
+            Self::Ready(output) => Ok(output),
+            Self::Run {
+                action,
+                from,
+                attempt_count,
+                target,
+                hooks,
+            } => {
+                let result = action.run(env).await?;
+                if let (TransitionResult::Success(actual), Some(expected)) = (&result, &target) {
+                    if actual != expected {
+                        return Err(TransitionError::UnexpectedTarget {
+                            expected: expected.display_name().into_owned(),
+                            actual: actual.display_name().into_owned(),
+                        });
+                    }
+                }
+                run_state_hooks(&hooks, &from, &result, env).await?;
+                let step_result = StepResult::from_action(result, attempt_count);
+                Ok((from, step_result, attempt_count))
+            }
+        }
+    }
+}
+
+impl<S: State> StepResult<S> {
+    /// Step result of an action that returned `result` after
+    /// `attempt_count` earlier attempts.
+    pub(crate) fn from_action(result: TransitionResult<S>, attempt_count: usize) -> Self {
+        match result {
+            TransitionResult::Success(new_state) => Self::Transitioned(new_state),
+            TransitionResult::Retry { feedback, .. } => Self::Retry {
+                feedback,
+                attempts: attempt_count + 1,
+            },
+            TransitionResult::Abort {
+                reason,
+                error_state,
+            } => Self::Aborted {
+                reason,
+                error_state,
+            },
+        }
+    }
+}
+
+/// History events reported by running steps, recorded when a result is
+/// applied. Clones start empty: a running step belongs to the machine
+/// that started it.
+#[derive(Default)]
+struct PendingEvents(StepEvents);
+
+impl Clone for PendingEvents {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
 }
 
 /// State machine that executes effectful transitions.
+///
+/// Cloning a machine is cheap for transitions (action factories are shared
+/// via `Arc`) but copies the history. Clones share any inspector.
+#[derive(Clone)]
 pub struct StateMachine<S: State + 'static, Env: Clone + Send + Sync + 'static> {
     initial: S,
     current: S,
@@ -28,6 +160,23 @@ pub struct StateMachine<S: State + 'static, Env: Clone + Send + Sync + 'static>
     history: StateHistory<S>,
     attempt_count: usize,
     metadata: MachineMetadata,
+    inspector: Option<SharedView<S>>,
+    invariants: Vec<Invariant<S>>,
+    scrubber: Option<Scrubber<S>>,
+    signal_waits: HashMap<String, SignalWait<S>>,
+    state_hooks: Arc<StateHookTable<S, Env>>,
+    redaction: Option<StateRedaction<S>>,
+    monotonic_timestamps: bool,
+    id_generator: Option<Arc<dyn IdGenerator>>,
+    required_capabilities: Vec<CapabilityKey>,
+    /// Resumed from a checkpoint and not moved since
+    resume_pending: bool,
+    /// State the machine is moved to once its deadline passes
+    deadline_state: Option<S>,
+    snapshot_policy: SnapshotPolicy,
+    step_events: PendingEvents,
+    /// Number of latest transitions and events history keeps
+    history_bound: Option<usize>,
 }
 
 impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
@@ -40,14 +189,99 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
             history: StateHistory::new(),
             attempt_count: 0,
             metadata: MachineMetadata::default(),
+            inspector: None,
+            invariants: Vec::new(),
+            scrubber: None,
+            signal_waits: HashMap::new(),
+            state_hooks: Arc::default(),
+            redaction: None,
+            monotonic_timestamps: false,
+            id_generator: None,
+            required_capabilities: Vec::new(),
+            resume_pending: false,
+            deadline_state: None,
+            snapshot_policy: SnapshotPolicy::new(),
+            step_events: PendingEvents::default(),
+            history_bound: None,
         }
     }
 
-    /// Add a transition to the machine
-    pub fn add_transition(&mut self, transition: Transition<S, Env>) {
+    /// Add a transition to the machine.
+    /// Records the caller's source location if the transition has none.
+    #[track_caller]
+    pub fn add_transition(&mut self, mut transition: Transition<S, Env>) {
+        transition.location.get_or_insert(Location::caller());
         self.transitions.push(transition);
     }
 
+    /// Add a transition to a live machine, keeping the graph sound.
+    ///
+    /// The transition must start from a state the machine already knows
+    /// (the initial state or an endpoint of an existing transition), so it
+    /// cannot introduce a dead edge. The change is recorded in history.
+    #[track_caller]
+    pub fn add_transition_checked(
+        &mut self,
+        transition: Transition<S, Env>,
+    ) -> Result<(), DefinitionError> {
+        let known = transition.from == self.initial
+            || self
+                .transitions
+                .iter()
+                .any(|t| t.from == transition.from || t.to == transition.from);
+        if !known {
+            return Err(DefinitionError::UnknownSourceState {
+                from: transition.from.display_name().into_owned(),
+            });
+        }
+        self.record_event(HistoryEvent::TransitionAdded {
+            from: transition.from.name().to_string(),
+            to: transition.to.name().to_string(),
+            timestamp: Utc::now(),
+        });
+        self.add_transition(transition);
+        self.publish();
+        Ok(())
+    }
+
+    /// Remove the transition at `index` from a live machine.
+    ///
+    /// Fails if the current state is not final and the removed transition
+    /// is its last way out. The change is recorded in history.
+    pub fn remove_transition_checked(
+        &mut self,
+        index: usize,
+    ) -> Result<Transition<S, Env>, DefinitionError> {
+        let Some(removed) = self.transitions.get(index) else {
+            return Err(DefinitionError::NoSuchTransition { index });
+        };
+        let orphans = removed.from == self.current
+            && !self.current.is_final()
+            && !self
+                .transitions
+                .iter()
+                .enumerate()
+                .any(|(i, t)| i != index && t.from == self.current);
+        if orphans {
+            return Err(DefinitionError::OrphansCurrentState {
+                state: self.current.display_name().into_owned(),
+            });
+        }
+        let removed = self.transitions.remove(index);
+        self.record_event(HistoryEvent::TransitionRemoved {
+            from: removed.from.name().to_string(),
+            to: removed.to.name().to_string(),
+            timestamp: Utc::now(),
+        });
+        self.publish();
+        Ok(removed)
+    }
+
+    /// Get the initial state the machine started from (pure)
+    pub fn initial_state(&self) -> &S {
+        &self.initial
+    }
+
     /// Get current state (pure)
     pub fn current_state(&self) -> &S {
         &self.current
@@ -63,86 +297,751 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
         &self.history
     }
 
+    /// When the machine was created, preserved across checkpoints (pure)
+    pub(crate) fn created_at(&self) -> DateTime<Utc> {
+        self.metadata.created_at
+    }
+
+    /// Attempts made so far at the current transition (pure)
+    pub(crate) fn attempt_count(&self) -> usize {
+        self.attempt_count
+    }
+
+    /// Feedback of the last retry of the current transition (pure)
+    pub(crate) fn retry_feedback(&self) -> Option<&str> {
+        self.metadata.retry_feedback.as_deref()
+    }
+
+    /// Get the transitions defined on this machine (pure)
+    pub fn transitions(&self) -> &[Transition<S, Env>] {
+        &self.transitions
+    }
+
     /// Execute one step of the state machine.
     /// Returns impl Effect for zero-cost composition.
     /// After running the effect, call apply_result() to update the machine state.
+    ///
+    /// Transitions behind a feature flag are skipped; use
+    /// [`step_with_flags`](Self::step_with_flags) to evaluate flags.
     pub fn step(
         &self,
     ) -> impl Effect<Output = (S, StepResult<S>, usize), Error = TransitionError, Env = Env> + '_
     {
+        self.step_selecting(None)
+    }
+
+    /// Execute one step, evaluating feature flags through the environment.
+    ///
+    /// Flags are read when the step runs, so flipping a flag takes effect
+    /// on the next step without redefining the machine.
+    pub fn step_with_flags(
+        &self,
+    ) -> impl Effect<Output = (S, StepResult<S>, usize), Error = TransitionError, Env = Env> + '_
+    where
+        Env: HasFeatureFlags,
+    {
+        from_async(move |env: &Env| {
+            let step = self.step_selecting(Some(env.feature_flags()));
+            let env = env.clone();
+            async move { step.run(&env).await }
+        })
+    }
+
+    fn step_selecting(&self, flags: Option<&dyn FeatureFlagProvider>) -> StepEffect<S, Env> {
+        if let Some(pause) = &self.metadata.paused {
+            return StepEffect::Ready((
+                self.current.clone(),
+                StepResult::Paused {
+                    reason: pause.reason.clone(),
+                },
+                self.attempt_count,
+            ));
+        }
+
+        if let Some(signal) = self.awaited_signal() {
+            return StepEffect::Ready((
+                self.current.clone(),
+                StepResult::AwaitingSignal {
+                    signal: signal.to_string(),
+                },
+                self.attempt_count,
+            ));
+        }
+
         // Find applicable transition (pure)
-        let transition_opt = self
-            .transitions
-            .iter()
-            .find(|t| t.can_execute(&self.current));
+        let transition_opt = self.transitions.iter().find(|t| match flags {
+            Some(flags) => t.can_execute_with(&self.current, flags),
+            None => t.can_execute(&self.current),
+        });
 
         let Some(transition) = transition_opt else {
-            return fail(TransitionError::NoTransition {
-                from: self.current.name().to_string(),
-            })
-            .boxed();
+            return StepEffect::Failed(self.no_transition(flags));
         };
 
-        // Get fresh effect from action factory
-        let from_state = self.current.clone();
-        let attempt_count = self.attempt_count;
-        let action = (transition.action)();
-
-        // Execute action and return result with context
-        action
-            .map(move |result| {
-                let step_result = match &result {
-                    TransitionResult::Success(new_state) => {
-                        StepResult::Transitioned(new_state.clone())
-                    }
-                    TransitionResult::Retry {
-                        feedback,
-                        current_state: _,
-                    } => StepResult::Retry {
-                        feedback: feedback.clone(),
-                        attempts: attempt_count + 1,
-                    },
-                    TransitionResult::Abort {
-                        reason,
-                        error_state,
-                    } => StepResult::Aborted {
-                        reason: reason.clone(),
-                        error_state: error_state.clone(),
-                    },
-                };
-                (from_state.clone(), step_result, attempt_count)
-            })
-            .boxed()
+        // Get fresh effect from action factory. The action is the only
+        // boxed effect on this path; the step itself is not boxed again.
+        StepEffect::Run {
+            action: (transition.action)(),
+            from: self.current.clone(),
+            attempt_count: self.attempt_count,
+            target: None,
+            hooks: self.state_hooks(),
+        }
+    }
+
+    /// Entry and exit hooks a step of this machine runs
+    pub(crate) fn state_hooks(&self) -> Arc<StateHookTable<S, Env>> {
+        Arc::clone(&self.state_hooks)
+    }
+
+    pub(crate) fn state_hooks_mut(&mut self, state: &S) -> &mut StateHooks<S, Env> {
+        Arc::make_mut(&mut self.state_hooks)
+            .entry(state.name().to_string())
+            .or_default()
+    }
+
+    /// Sink the steps of this machine report history events to
+    pub(crate) fn step_events(&self) -> &StepEvents {
+        &self.step_events.0
+    }
+
+    /// Error for a step that found no executable transition, diagnosing
+    /// every transition out of the current state (pure)
+    pub(crate) fn no_transition(&self, flags: Option<&dyn FeatureFlagProvider>) -> TransitionError {
+        TransitionError::NoTransition {
+            from: self.current.display_name().into_owned(),
+            candidates: self.candidate_checks(flags),
+        }
+    }
+
+    /// Check every transition out of states named like the current one (pure)
+    pub(crate) fn candidate_checks(
+        &self,
+        flags: Option<&dyn FeatureFlagProvider>,
+    ) -> Vec<CandidateCheck> {
+        self.transitions
+            .iter()
+            .filter(|t| t.from.name() == self.current.name())
+            .map(|t| t.check(&self.current, flags))
+            .collect()
+    }
+
+    /// Preview the context of the transition `step()` would attempt (pure).
+    ///
+    /// Returns `None` when the machine is paused, awaits a signal, or no
+    /// transition can execute from the current state. `started_at` is
+    /// [`current_transition_started_at`](Self::current_transition_started_at).
+    pub fn preview(&self) -> Option<TransitionContext<S>> {
+        if self.is_paused() || self.awaited_signal().is_some() {
+            return None;
+        }
+
+        let transition = self
+            .transitions
+            .iter()
+            .find(|t| t.can_execute(&self.current))?;
+
+        Some(TransitionContext {
+            from: self.current.clone(),
+            to: transition.to.clone(),
+            attempt: self.attempt_count + 1,
+            started_at: self.current_transition_started_at(),
+        })
     }
 
     /// Apply the result from step() to update machine state.
     /// Call this after running the effect.
     pub fn apply_result(&mut self, from_state: S, result: StepResult<S>, attempt_count: usize) {
+        self.apply_result_with_correlation(from_state, result, attempt_count, BTreeMap::new());
+    }
+
+    /// Like [`apply_result`](Self::apply_result), but attaches correlation
+    /// data (e.g. request or user ids) to the recorded transition.
+    ///
+    /// The data is only recorded when the result is a transition.
+    pub fn apply_result_with_correlation(
+        &mut self,
+        from_state: S,
+        result: StepResult<S>,
+        attempt_count: usize,
+        correlation: BTreeMap<String, String>,
+    ) {
+        self.apply_result_at(from_state, result, attempt_count, correlation, Utc::now());
+    }
+
+    /// Like [`apply_result`](Self::apply_result), but also records a
+    /// snapshot of `data`, the machine's extended context, for the new
+    /// transition as allowed by the [`SnapshotPolicy`].
+    ///
+    /// Nothing is captured unless the result is a transition.
+    pub fn apply_result_with_snapshot<T: serde::Serialize + ?Sized>(
+        &mut self,
+        from_state: S,
+        result: StepResult<S>,
+        attempt_count: usize,
+        data: &T,
+    ) {
+        // History length stays put once a bounded history is full, so the
+        // transition count tells whether one was recorded
+        let before = self.metadata.stats.transitions;
+        self.apply_result(from_state, result, attempt_count);
+        if self.metadata.stats.transitions == before {
+            return;
+        }
+        let sequence = self.history.last_sequence();
+        if let Some(snapshot) = self.snapshot_policy.capture(sequence, data) {
+            let snapshot = match &self.scrubber {
+                Some(scrubber) => scrubber.scrub_snapshot(snapshot),
+                None => snapshot,
+            };
+            self.history.push_snapshot(snapshot);
+        }
+    }
+
+    /// Apply a step result as if it happened at `at`.
+    pub(crate) fn apply_result_at(
+        &mut self,
+        from_state: S,
+        result: StepResult<S>,
+        attempt_count: usize,
+        correlation: BTreeMap<String, String>,
+        at: DateTime<Utc>,
+    ) {
+        for event in self.step_events.0.take() {
+            self.record_event(event);
+        }
         match result {
             StepResult::Transitioned(new_state) => {
                 let transition_record = StateTransition {
                     from: from_state.clone(),
                     to: new_state.clone(),
-                    timestamp: Utc::now(),
+                    timestamp: at,
                     attempt: attempt_count,
+                    correlation,
+                    sequence: 0,
+                    forced: None,
                 };
-                self.history = self.history.record(transition_record);
+                self.consume_signal(&from_state);
+                self.record_transition(transition_record);
                 self.current = new_state;
                 self.attempt_count = 0;
-                self.update_metadata(from_state.name().to_string());
+                self.update_metadata(from_state.name().to_string(), at);
+                self.record_invariant_violations(at);
+                self.resume_pending = false;
             }
-            StepResult::Retry { .. } => {
+            StepResult::Retry { feedback, .. } => {
                 self.attempt_count += 1;
+                self.metadata.retry_feedback = Some(feedback);
+                self.metadata.stats.retries += 1;
             }
             StepResult::Aborted { error_state, .. } => {
+                self.consume_signal(&from_state);
+                self.metadata.stats.aborts += 1;
+                self.count_entry(&error_state);
                 self.current = error_state;
+                self.metadata.current_transition_started_at = Some(at);
+                self.metadata.retry_feedback = None;
+                self.record_invariant_violations(at);
+                self.resume_pending = false;
             }
+            StepResult::Paused { .. } | StepResult::AwaitingSignal { .. } => {}
         }
+        self.publish();
     }
 
-    /// Update metadata after transition
-    fn update_metadata(&mut self, transition_name: String) {
+    /// Whether the running process resumed this machine from a checkpoint
+    /// rather than creating it fresh (pure)
+    pub fn is_resumed(&self) -> bool {
+        self.metadata.resumed_at.is_some()
+    }
+
+    /// Times the machine was resumed from a checkpoint, across processes (pure)
+    pub fn resume_count(&self) -> usize {
+        self.metadata.resume_count
+    }
+
+    /// Whether the machine was resumed and has not transitioned since, i.e.
+    /// the next step may repeat work interrupted before the checkpoint (pure)
+    pub fn is_resume_pending(&self) -> bool {
+        self.resume_pending
+    }
+
+    /// Run `hook` if the running process resumed this machine from a
+    /// checkpoint; fresh machines are returned unchanged.
+    ///
+    /// Chain it onto the loading call, e.g.
+    /// `StateMachine::from_json(&json, transitions)?.on_resume(|m| ...)`,
+    /// to reconcile external state before the machine is driven again.
+    pub fn on_resume(mut self, hook: impl FnOnce(&mut Self)) -> Self {
+        if self.is_resumed() {
+            hook(&mut self);
+        }
+        self
+    }
+
+    /// Get the id identifying the machine across checkpoints (pure)
+    pub fn id(&self) -> &str {
+        self.metadata.machine_id.as_deref().unwrap_or_default()
+    }
+
+    /// Get the namespace (tenant) the machine belongs to (pure)
+    pub fn namespace(&self) -> Option<&str> {
+        self.metadata.namespace.as_deref()
+    }
+
+    /// Assign the machine to a namespace (tenant).
+    /// The namespace is persisted in checkpoints.
+    pub fn set_namespace(&mut self, namespace: impl Into<String>) {
+        self.metadata.namespace = Some(namespace.into());
+        self.publish();
+    }
+
+    /// Get the machine's labels (pure)
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.metadata.labels
+    }
+
+    /// Run statistics: transitions, retries, aborts and state entries (pure)
+    pub fn stats(&self) -> &MachineStats {
+        &self.metadata.stats
+    }
+
+    /// Set a label, replacing any previous value for the key.
+    /// Labels are persisted in checkpoints.
+    pub fn set_label(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata.labels.insert(key.into(), value.into());
+        self.publish();
+    }
+
+    /// When attempts at the current transition started (pure).
+    ///
+    /// Reset whenever the machine changes state. Falls back to the last
+    /// metadata update for checkpoints written before this was tracked.
+    pub fn current_transition_started_at(&self) -> DateTime<Utc> {
+        self.metadata
+            .current_transition_started_at
+            .unwrap_or(self.metadata.updated_at)
+    }
+
+    /// Deadline of the whole run, if one is set (pure)
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.metadata.deadline
+    }
+
+    /// Give the whole run until `at` to reach a final state, moving it to
+    /// `expired` otherwise, e.g. a `Cancelled` or `Expired` state.
+    ///
+    /// The deadline is persisted in checkpoints; like transitions, the
+    /// expiry state is not, so resumed machines must set it again with
+    /// [`set_deadline_state`](Self::set_deadline_state).
+    pub fn set_deadline(&mut self, at: DateTime<Utc>, expired: S) {
+        self.metadata.deadline = Some(at);
+        self.deadline_state = Some(expired);
+        self.publish();
+    }
+
+    /// Set the state a resumed machine moves to once its deadline passes
+    pub fn set_deadline_state(&mut self, expired: S) {
+        self.deadline_state = Some(expired);
+    }
+
+    /// Remove the deadline
+    pub fn clear_deadline(&mut self) {
+        self.metadata.deadline = None;
+        self.publish();
+    }
+
+    /// Move the machine to its deadline state if the deadline has passed.
+    /// Returns whether it did. `run_with_fuel` does this before every step.
+    pub fn enforce_deadline(&mut self) -> bool {
+        self.enforce_deadline_at(Utc::now())
+    }
+
+    /// Move the machine to its deadline state if the deadline has passed
+    /// by `now`. Returns whether it did.
+    ///
+    /// The move is recorded as a forced transition by operator `deadline`,
+    /// with the deadline as the reason. Final machines, machines already in
+    /// the deadline state and machines without one are left alone.
+    pub fn enforce_deadline_at(&mut self, now: DateTime<Utc>) -> bool {
+        let Some(deadline) = self.metadata.deadline.filter(|at| *at <= now) else {
+            return false;
+        };
+        let Some(expired) = self.deadline_state.clone() else {
+            return false;
+        };
+        if self.current.is_final() || self.current.name() == expired.name() {
+            return false;
+        }
+        let forced = ForcedTransition {
+            reason: format!("deadline {} passed", deadline.to_rfc3339()),
+            operator: "deadline".to_string(),
+        };
+        self.force_transition_at(expired, forced, now);
+        true
+    }
+
+    /// Check if the machine is paused (pure)
+    pub fn is_paused(&self) -> bool {
+        self.metadata.paused.is_some()
+    }
+
+    /// Why and since when the machine is paused, if it is (pure)
+    pub(crate) fn pause_info(&self) -> Option<&PauseInfo> {
+        self.metadata.paused.as_ref()
+    }
+
+    /// Pause the machine.
+    ///
+    /// While paused, `step()` attempts no transition and returns
+    /// `StepResult::Paused`. The pause is persisted in metadata and
+    /// recorded in history. Pausing an already paused machine is a no-op.
+    pub fn pause(&mut self, reason: impl Into<String>) {
+        if self.is_paused() {
+            return;
+        }
+        let reason = reason.into();
+        let now = Utc::now();
+        self.record_event(HistoryEvent::Paused {
+            reason: reason.clone(),
+            timestamp: now,
+        });
+        self.metadata.paused = Some(PauseInfo { reason, since: now });
+        self.metadata.updated_at = now;
+        self.publish();
+    }
+
+    /// Resume a paused machine. Resuming a running machine is a no-op.
+    pub fn resume(&mut self) {
+        if self.metadata.paused.take().is_none() {
+            return;
+        }
+        let now = Utc::now();
+        self.record_event(HistoryEvent::Resumed { timestamp: now });
+        self.metadata.updated_at = now;
+        self.publish();
+    }
+
+    /// Make `state` wait for an external signal before any transition out
+    /// of it is attempted. States are matched by name.
+    pub fn await_signal(&mut self, state: &S, wait: SignalWait<S>) {
+        self.signal_waits.insert(state.name().to_string(), wait);
+    }
+
+    /// Deliver an external signal.
+    ///
+    /// The signal is persisted in metadata until a waiting state accepts it
+    /// and the machine leaves that state, so it may be delivered before
+    /// the machine reaches the waiting state or while nothing drives it.
+    pub fn signal(&mut self, name: impl Into<String>, payload: serde_json::Value) {
+        let now = Utc::now();
+        self.metadata.pending_signals.push(Signal {
+            name: name.into(),
+            payload,
+            received_at: now,
+        });
+        self.metadata.updated_at = now;
+        self.publish();
+    }
+
+    /// Get delivered signals not consumed yet, oldest first (pure)
+    pub fn pending_signals(&self) -> &[Signal] {
+        &self.metadata.pending_signals
+    }
+
+    /// Get timers scheduled with a timer service and not fired yet (pure)
+    pub fn pending_timers(&self) -> &[PendingTimer] {
+        &self.metadata.timers
+    }
+
+    pub(crate) fn set_pending_timers(&mut self, timers: Vec<PendingTimer>) {
+        self.metadata.timers = timers;
         self.metadata.updated_at = Utc::now();
+        self.publish();
+    }
+
+    /// Get the signal accepted by the current state, if any (pure)
+    pub fn received_signal(&self) -> Option<&Signal> {
+        self.accepted_signal(&self.current)
+            .map(|index| &self.metadata.pending_signals[index])
+    }
+
+    /// Name of the signal the current state still waits for (pure)
+    pub fn awaited_signal(&self) -> Option<&str> {
+        let wait = self.signal_waits.get(self.current.name())?;
+        match self.accepted_signal(&self.current) {
+            Some(_) => None,
+            None => Some(wait.signal()),
+        }
+    }
+
+    /// Index of the oldest pending signal `state` accepts
+    fn accepted_signal(&self, state: &S) -> Option<usize> {
+        let wait = self.signal_waits.get(state.name())?;
+        self.metadata
+            .pending_signals
+            .iter()
+            .position(|signal| wait.accepts(state, &signal.name, &signal.payload))
+    }
+
+    /// Consume the signal that unblocked `state` as the machine leaves it
+    fn consume_signal(&mut self, state: &S) {
+        if let Some(index) = self.accepted_signal(state) {
+            self.metadata.pending_signals.remove(index);
+        }
+    }
+
+    /// Execute one step without mutating this machine.
+    ///
+    /// Runs the step effect against `env` and returns a new machine with the
+    /// result applied, together with the step result. The original machine is
+    /// left untouched, which suits architectures that keep machines in
+    /// immutable application state.
+    pub async fn step_pure(&self, env: &Env) -> Result<(Self, StepResult<S>), TransitionError> {
+        let (from, result, attempt) = self.step().run(env).await?;
+        let mut next = self.clone();
+        next.apply_result(from, result.clone(), attempt);
+        Ok((next, result))
+    }
+
+    /// Force the machine into `state`, bypassing guards and the graph.
+    ///
+    /// This is an operator escape hatch for production support. The move is
+    /// recorded in history like any other transition, but carries a
+    /// [`ForcedTransition`] with the reason and operator identity so audits
+    /// can tell it apart from regular transitions.
+    pub fn force_transition_to(
+        &mut self,
+        state: S,
+        reason: impl Into<String>,
+        operator: impl Into<String>,
+    ) {
+        let forced = ForcedTransition {
+            reason: reason.into(),
+            operator: operator.into(),
+        };
+        self.force_transition_at(state, forced, Utc::now());
+    }
+
+    pub(crate) fn force_transition_at(
+        &mut self,
+        state: S,
+        forced: ForcedTransition,
+        at: DateTime<Utc>,
+    ) {
+        let from_state = self.current.clone();
+        let transition_record = StateTransition {
+            from: from_state.clone(),
+            to: state.clone(),
+            timestamp: at,
+            attempt: self.attempt_count,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: Some(forced),
+        };
+        self.record_transition(transition_record);
+        self.current = state;
+        self.attempt_count = 0;
+        self.update_metadata(from_state.name().to_string(), at);
+        self.record_invariant_violations(at);
+        self.resume_pending = false;
+        self.publish();
+    }
+
+    /// Register an invariant checked whenever the machine enters a final
+    /// state. Violations are recorded in history as
+    /// [`HistoryEvent::InvariantViolated`].
+    pub fn add_invariant(&mut self, invariant: Invariant<S>) {
+        self.invariants.push(invariant);
+    }
+
+    /// Get the registered invariants (pure)
+    pub fn invariants(&self) -> &[Invariant<S>] {
+        &self.invariants
+    }
+
+    /// Redact sensitive data from everything recorded into history from
+    /// now on, and from the retry feedback and signal payloads in
+    /// checkpoints. Existing history is left as is; see
+    /// [`scrub_existing`](crate::checkpoint::scrub_existing).
+    pub fn set_scrubber(&mut self, scrubber: Scrubber<S>) {
+        self.scrubber = Some(scrubber);
+    }
+
+    /// Map states to a persistable form whenever a checkpoint is taken.
+    /// The machine itself keeps the unredacted states.
+    pub fn set_state_redaction(&mut self, redaction: StateRedaction<S>) {
+        self.redaction = Some(redaction);
+    }
+
+    /// Generate checkpoint ids and OTLP trace ids with `ids` instead of
+    /// random UUIDs, e.g. time-ordered or deterministic ones. A machine
+    /// that has not moved or been resumed also takes its own id from
+    /// `ids`.
+    pub fn set_id_generator(&mut self, ids: impl IdGenerator + 'static) {
+        self.install_id_generator(Arc::new(ids));
+    }
+
+    pub(crate) fn install_id_generator(&mut self, ids: Arc<dyn IdGenerator>) {
+        if self.history.transitions().is_empty() && self.metadata.resume_count == 0 {
+            self.metadata.machine_id = Some(ids.generate());
+        }
+        self.id_generator = Some(ids);
+    }
+
+    /// A new id from the machine's id generator
+    pub(crate) fn generate_id(&self) -> String {
+        match &self.id_generator {
+            Some(ids) => ids.generate(),
+            None => UuidIds.generate(),
+        }
+    }
+
+    /// Declare that this machine's actions need the capability `key` from
+    /// the environment.
+    pub fn require_capability(&mut self, key: CapabilityKey) {
+        if !self.required_capabilities.contains(&key) {
+            self.required_capabilities.push(key);
+        }
+    }
+
+    /// Capabilities declared with [`require_capability`](Self::require_capability)
+    pub fn required_capabilities(&self) -> &[CapabilityKey] {
+        &self.required_capabilities
+    }
+
+    /// Never record a transition with a timestamp earlier than the
+    /// previous one, e.g. after the system clock was set back by NTP.
+    ///
+    /// Durations derived from history are then never negative. Sequence
+    /// numbers order transitions either way.
+    pub fn set_monotonic_timestamps(&mut self, enabled: bool) {
+        self.monotonic_timestamps = enabled;
+    }
+
+    /// Limit how often and how much data
+    /// [`apply_result_with_snapshot`](Self::apply_result_with_snapshot)
+    /// captures.
+    pub fn set_snapshot_policy(&mut self, policy: SnapshotPolicy) {
+        self.snapshot_policy = policy;
+    }
+
+    /// Keep only the latest `N` transitions in history, the window a
+    /// [`BoundedHistory`](crate::core::BoundedHistory) of capacity `N`
+    /// retains, and the latest `N` events. Snapshots of evicted
+    /// transitions are dropped. History is trimmed in place as entries are
+    /// recorded; queries and checkpoints then only see the window, while
+    /// [`MachineStats`] keeps counting the whole run.
+    pub fn bound_history<const N: usize>(&mut self) {
+        self.set_history_bound(N);
+    }
+
+    pub(crate) fn set_history_bound(&mut self, capacity: usize) {
+        self.history.retain_latest(capacity);
+        self.history_bound = Some(capacity);
+    }
+
+    fn record_transition(&mut self, mut transition: StateTransition<S>) {
+        self.metadata.stats.transitions += 1;
+        self.count_entry(&transition.to);
+        if self.monotonic_timestamps {
+            if let Some(last) = self.history.transitions().last() {
+                transition.timestamp = transition.timestamp.max(last.timestamp);
+            }
+        }
+        let transition = match &self.scrubber {
+            Some(scrubber) => scrubber.scrub_transition(transition),
+            None => transition,
+        };
+        self.history.push(transition);
+        if let Some(capacity) = self.history_bound {
+            self.history.retain_latest(capacity);
+        }
+    }
+
+    fn count_entry(&mut self, state: &S) {
+        *self
+            .metadata
+            .stats
+            .state_entries
+            .entry(state.name().to_string())
+            .or_insert(0) += 1;
+    }
+
+    fn record_event(&mut self, event: HistoryEvent) {
+        let event = match &self.scrubber {
+            Some(scrubber) => scrubber.scrub_event(event),
+            None => event,
+        };
+        self.history.push_event(event);
+        if let Some(capacity) = self.history_bound {
+            self.history.retain_latest(capacity);
+        }
+    }
+
+    /// Record violated invariants if the machine just completed
+    fn record_invariant_violations(&mut self, at: DateTime<Utc>) {
+        let Err(violation) = self.verify_invariants() else {
+            return;
+        };
+        for invariant in violation.violated {
+            self.record_event(HistoryEvent::InvariantViolated {
+                invariant,
+                state: violation.state.clone(),
+                timestamp: at,
+            });
+        }
+    }
+
+    /// Get a read-only inspector for this machine.
+    ///
+    /// The inspector observes every later change made through this machine,
+    /// and through clones of it such as the one returned by
+    /// [`step_pure`](Self::step_pure).
+    pub fn inspector(&mut self) -> MachineInspector<S> {
+        let shared = match &self.inspector {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared = Arc::new(RwLock::new(Arc::new(self.view())));
+                self.inspector = Some(Arc::clone(&shared));
+                shared
+            }
+        };
+        MachineInspector::new(shared)
+    }
+
+    fn view(&self) -> MachineView<S> {
+        MachineView {
+            current: self.current.clone(),
+            is_final: self.is_final(),
+            attempt_count: self.attempt_count,
+            history: self.history.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Copy of the machine that does not publish to this machine's
+    /// inspectors or run its state hooks.
+    pub(crate) fn detached(&self) -> Self {
+        Self {
+            inspector: None,
+            state_hooks: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Publish the current view to inspectors, if any
+    fn publish(&self) {
+        if let Some(shared) = &self.inspector {
+            inspector::publish(shared, self.view());
+        }
+    }
+
+    /// Update metadata after transition
+    fn update_metadata(&mut self, transition_name: String, now: DateTime<Utc>) {
+        self.metadata.updated_at = now;
+        self.metadata.current_transition_started_at = Some(now);
+        self.metadata.retry_feedback = None;
         *self
             .metadata
             .total_attempts
@@ -154,16 +1053,25 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
     /// Pure function - does not modify machine.
     pub fn checkpoint(&self) -> crate::checkpoint::Checkpoint<S> {
         use crate::checkpoint::Checkpoint;
-        use uuid::Uuid;
 
-        Checkpoint {
+        let checkpoint = Checkpoint {
             version: crate::checkpoint::CHECKPOINT_VERSION,
-            id: Uuid::new_v4().to_string(),
+            state_schema: crate::checkpoint::StateSchema::of::<S>(),
+            id: self.generate_id(),
             timestamp: Utc::now(),
             initial_state: self.initial.clone(),
             current_state: self.current.clone(),
             history: self.history.clone(),
-            metadata: self.metadata.clone(),
+            metadata: match &self.scrubber {
+                Some(scrubber) => {
+                    crate::checkpoint::scrub_metadata(self.metadata.clone(), scrubber)
+                }
+                None => self.metadata.clone(),
+            },
+        };
+        match &self.redaction {
+            Some(redaction) => redaction.redact_checkpoint(checkpoint),
+            None => checkpoint,
         }
     }
 
@@ -177,7 +1085,8 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
     /// Serialize to binary format
     pub fn to_binary(&self) -> Result<Vec<u8>, crate::checkpoint::CheckpointError> {
         let checkpoint = self.checkpoint();
-        bincode::serialize(&checkpoint)
+        crate::checkpoint::binary_options()
+            .serialize(&checkpoint)
             .map_err(|e| crate::checkpoint::CheckpointError::SerializationFailed(e.to_string()))
     }
 
@@ -197,27 +1106,150 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
             });
         }
 
+        let mut metadata = checkpoint.metadata;
+        metadata
+            .machine_id
+            .get_or_insert_with(|| UuidIds.generate());
+        metadata.resume_count += 1;
+        metadata.resumed_at = Some(Utc::now());
+
         Ok(Self {
             initial: checkpoint.initial_state,
             current: checkpoint.current_state,
             transitions,
             history: checkpoint.history,
             attempt_count: 0,
-            metadata: checkpoint.metadata,
+            metadata,
+            inspector: None,
+            invariants: Vec::new(),
+            scrubber: None,
+            signal_waits: HashMap::new(),
+            state_hooks: Arc::default(),
+            redaction: None,
+            monotonic_timestamps: false,
+            id_generator: None,
+            required_capabilities: Vec::new(),
+            resume_pending: true,
+            deadline_state: None,
+            snapshot_policy: SnapshotPolicy::new(),
+            step_events: PendingEvents::default(),
+            history_bound: None,
         })
     }
 
-    /// Deserialize from JSON string
-    pub fn from_json(
-        json: &str,
+    /// Create state machine from a checkpoint written with `redaction`.
+    ///
+    /// The restore hook rebuilds the initial and current states, and the
+    /// redaction stays set for later checkpoints.
+    pub fn from_checkpoint_with_redaction(
+        checkpoint: crate::checkpoint::Checkpoint<S>,
         transitions: Vec<Transition<S, Env>>,
+        redaction: StateRedaction<S>,
     ) -> Result<Self, crate::checkpoint::CheckpointError> {
-        let checkpoint: crate::checkpoint::Checkpoint<S> =
-            serde_json::from_str(json).map_err(|e| {
-                crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string())
-            })?;
-
-        Self::from_checkpoint(checkpoint, transitions)
+        let mut machine =
+            Self::from_checkpoint(redaction.restore_checkpoint(checkpoint), transitions)?;
+        machine.redaction = Some(redaction);
+        Ok(machine)
+    }
+
+    /// Create a machine positioned directly in `state`, without history.
+    ///
+    /// This is the sanctioned way to move a stuck workflow to a specific
+    /// state without hand-crafting a checkpoint. The machine's metadata
+    /// records the synthetic start so it is visible in later checkpoints.
+    pub fn resume_at(state: S, transitions: Vec<Transition<S, Env>>) -> Self {
+        let mut metadata = MachineMetadata::default();
+        metadata.synthetic_start = Some(crate::checkpoint::SyntheticStart {
+            state: state.name().to_string(),
+            timestamp: metadata.created_at,
+        });
+
+        Self {
+            initial: state.clone(),
+            current: state,
+            transitions,
+            history: StateHistory::new(),
+            attempt_count: 0,
+            metadata,
+            inspector: None,
+            invariants: Vec::new(),
+            scrubber: None,
+            signal_waits: HashMap::new(),
+            state_hooks: Arc::default(),
+            redaction: None,
+            monotonic_timestamps: false,
+            id_generator: None,
+            required_capabilities: Vec::new(),
+            resume_pending: false,
+            deadline_state: None,
+            snapshot_policy: SnapshotPolicy::new(),
+            step_events: PendingEvents::default(),
+            history_bound: None,
+        }
+    }
+
+    /// Deserialize from JSON string
+    pub fn from_json(
+        json: &str,
+        transitions: Vec<Transition<S, Env>>,
+    ) -> Result<Self, crate::checkpoint::CheckpointError> {
+        Self::from_checkpoint(Self::checkpoint_from_json(json)?, transitions)
+    }
+
+    /// Deserialize from a JSON string written with `redaction`
+    pub fn from_json_with_redaction(
+        json: &str,
+        transitions: Vec<Transition<S, Env>>,
+        redaction: StateRedaction<S>,
+    ) -> Result<Self, crate::checkpoint::CheckpointError> {
+        Self::from_checkpoint_with_redaction(
+            Self::checkpoint_from_json(json)?,
+            transitions,
+            redaction,
+        )
+    }
+
+    /// Deserialize from a JSON string written with an older shape of the
+    /// state type, rewriting its states with `migration` first
+    pub fn from_json_migrated(
+        json: &str,
+        transitions: Vec<Transition<S, Env>>,
+        migration: &StateMigration,
+    ) -> Result<Self, crate::checkpoint::CheckpointError> {
+        let value = migration.migrate_checkpoint(Self::json_value(json)?);
+        Self::from_checkpoint(Self::checkpoint_from_value(value)?, transitions)
+    }
+
+    fn json_value(json: &str) -> Result<serde_json::Value, crate::checkpoint::CheckpointError> {
+        serde_json::from_str(json)
+            .map_err(|e| crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string()))
+    }
+
+    fn checkpoint_from_json(
+        json: &str,
+    ) -> Result<crate::checkpoint::Checkpoint<S>, crate::checkpoint::CheckpointError> {
+        Self::checkpoint_from_value(Self::json_value(json)?)
+    }
+
+    fn checkpoint_from_value(
+        value: serde_json::Value,
+    ) -> Result<crate::checkpoint::Checkpoint<S>, crate::checkpoint::CheckpointError> {
+        // Check the schema first so a changed state enum yields a precise error
+        if let Some(schema) = value.get("state_schema").filter(|v| !v.is_null()) {
+            let schema: crate::checkpoint::StateSchema = serde_json::from_value(schema.clone())
+                .map_err(|e| {
+                    crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string())
+                })?;
+            if let Some(current) = crate::checkpoint::StateSchema::of::<S>() {
+                schema.verify_against(
+                    &current,
+                    &crate::checkpoint::schema::variants_in_json(&value),
+                )?;
+            }
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string()))
     }
 
     /// Deserialize from binary format
@@ -225,12 +1257,85 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
         bytes: &[u8],
         transitions: Vec<Transition<S, Env>>,
     ) -> Result<Self, crate::checkpoint::CheckpointError> {
-        let checkpoint: crate::checkpoint::Checkpoint<S> =
-            bincode::deserialize(bytes).map_err(|e| {
-                crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string())
-            })?;
+        Self::from_checkpoint(Self::checkpoint_from_binary(bytes)?, transitions)
+    }
+
+    /// Deserialize from binary format written with `redaction`
+    pub fn from_binary_with_redaction(
+        bytes: &[u8],
+        transitions: Vec<Transition<S, Env>>,
+        redaction: StateRedaction<S>,
+    ) -> Result<Self, crate::checkpoint::CheckpointError> {
+        Self::from_checkpoint_with_redaction(
+            Self::checkpoint_from_binary(bytes)?,
+            transitions,
+            redaction,
+        )
+    }
+
+    fn checkpoint_from_binary(
+        bytes: &[u8],
+    ) -> Result<crate::checkpoint::Checkpoint<S>, crate::checkpoint::CheckpointError> {
+        // Leading fields of Checkpoint, decodable without knowing the state type
+        #[derive(serde::Deserialize)]
+        struct Header {
+            _version: u32,
+            state_schema: Option<crate::checkpoint::StateSchema>,
+        }
+
+        let options = crate::checkpoint::binary_options();
+        let decode_failed = |e: bincode::Error| {
+            crate::checkpoint::CheckpointError::DeserializationFailed(e.to_string())
+        };
+
+        // Version 1 predates the schema and the fields added since
+        if options.deserialize::<u32>(bytes).map_err(decode_failed)? == 1 {
+            return options
+                .deserialize::<crate::checkpoint::CheckpointV1<S>>(bytes)
+                .map(Into::into)
+                .map_err(decode_failed);
+        }
 
-        Self::from_checkpoint(checkpoint, transitions)
+        let checkpoint = options.deserialize::<crate::checkpoint::Checkpoint<S>>(bytes);
+        let header = options.deserialize::<Header>(bytes).ok();
+        if let (Some(recorded), Some(current)) = (
+            header.and_then(|h| h.state_schema),
+            crate::checkpoint::StateSchema::of::<S>(),
+        ) {
+            // Variants are stored by index: map the decoded indices back to
+            // the names they had when written. Undecodable checkpoints are
+            // checked against every recorded variant.
+            let used = match &checkpoint {
+                Ok(checkpoint) => Self::checkpoint_states(checkpoint)
+                    .filter_map(crate::checkpoint::schema::variant_index)
+                    .filter_map(|index| recorded.members.get(index as usize).cloned())
+                    .fold(Vec::new(), |mut used, name| {
+                        if !used.contains(&name) {
+                            used.push(name);
+                        }
+                        used
+                    }),
+                Err(_) => recorded.members.clone(),
+            };
+            recorded.verify_positional(&current, &used)?;
+        }
+
+        checkpoint.map_err(decode_failed)
+    }
+
+    /// States of `checkpoint`: initial, current and those in its history
+    fn checkpoint_states(
+        checkpoint: &crate::checkpoint::Checkpoint<S>,
+    ) -> impl Iterator<Item = &S> {
+        [&checkpoint.initial_state, &checkpoint.current_state]
+            .into_iter()
+            .chain(
+                checkpoint
+                    .history
+                    .transitions()
+                    .iter()
+                    .flat_map(|t| [&t.from, &t.to]),
+            )
     }
 }
 
@@ -238,9 +1343,10 @@ impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env
 mod tests {
     use super::*;
     use crate::core::Guard;
-    use crate::effects::transition::{Transition, TransitionResult};
+    use crate::effects::transition::{CandidateCheck, Transition, TransitionResult};
     use serde::{Deserialize, Serialize};
     use std::sync::Arc;
+    use stillwater::prelude::*;
 
     #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
     enum WorkflowState {
@@ -278,6 +1384,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         };
 
@@ -303,6 +1412,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: Some(guard),
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         };
 
@@ -326,6 +1438,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| {
                 pure(TransitionResult::Retry {
                     feedback: "Not ready yet".to_string(),
@@ -365,6 +1480,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| {
                 from_fn(|env: &TestEnv| {
                     if env._should_succeed {
@@ -400,6 +1518,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| {
                 pure(TransitionResult::Abort {
                     reason: "Something went wrong".to_string(),
@@ -426,6 +1547,33 @@ mod tests {
         assert_eq!(machine.current_state(), &WorkflowState::Failed);
     }
 
+    #[tokio::test]
+    async fn step_pure_returns_new_machine() {
+        let mut machine = StateMachine::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            from: WorkflowState::Initial,
+            to: WorkflowState::Processing,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
+        });
+
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let (next, result) = machine.step_pure(&env).await.unwrap();
+
+        assert_eq!(result, StepResult::Transitioned(WorkflowState::Processing));
+        assert_eq!(next.current_state(), &WorkflowState::Processing);
+        assert_eq!(next.history().transitions().len(), 1);
+
+        // Original machine is unchanged
+        assert_eq!(machine.current_state(), &WorkflowState::Initial);
+        assert!(machine.history().transitions().is_empty());
+    }
+
     #[tokio::test]
     async fn checkpoint_serializes_to_json() {
         let machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
@@ -448,6 +1596,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         });
 
@@ -455,6 +1606,9 @@ mod tests {
             from: WorkflowState::Processing,
             to: WorkflowState::Complete,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Complete)).boxed()),
         });
 
@@ -476,6 +1630,9 @@ mod tests {
                 from: WorkflowState::Initial,
                 to: WorkflowState::Processing,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Processing)).boxed()
                 }),
@@ -484,6 +1641,9 @@ mod tests {
                 from: WorkflowState::Processing,
                 to: WorkflowState::Complete,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Complete)).boxed()
                 }),
@@ -500,6 +1660,452 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn paused_machine_does_not_step() {
+        let mut machine = StateMachine::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            from: WorkflowState::Initial,
+            to: WorkflowState::Processing,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
+        });
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+
+        machine.pause("maintenance window");
+        assert!(machine.is_paused());
+
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        assert_eq!(
+            result,
+            StepResult::Paused {
+                reason: "maintenance window".to_string()
+            }
+        );
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.current_state(), &WorkflowState::Initial);
+
+        // Pause survives a checkpoint round trip
+        let json = machine.to_json().unwrap();
+        let transitions = machine.transitions().to_vec();
+        let mut machine = StateMachine::from_json(&json, transitions).unwrap();
+        assert!(machine.is_paused());
+
+        machine.resume();
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.current_state(), &WorkflowState::Processing);
+
+        let events = machine.history().events();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], HistoryEvent::Paused { .. }));
+        assert!(matches!(events[1], HistoryEvent::Resumed { .. }));
+    }
+
+    #[tokio::test]
+    async fn preview_reports_next_transition_context() {
+        let mut machine = StateMachine::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            from: WorkflowState::Initial,
+            to: WorkflowState::Processing,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| {
+                pure(TransitionResult::Retry {
+                    feedback: "Not ready yet".to_string(),
+                    current_state: WorkflowState::Initial,
+                })
+                .boxed()
+            }),
+        });
+
+        let context = machine.preview().unwrap();
+        assert_eq!(context.from, WorkflowState::Initial);
+        assert_eq!(context.to, WorkflowState::Processing);
+        assert_eq!(context.attempt, 1);
+
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.preview().unwrap().attempt, 2);
+
+        machine.pause("inspection");
+        assert!(machine.preview().is_none());
+    }
+
+    #[tokio::test]
+    async fn inspector_observes_owner_changes() {
+        let mut machine = StateMachine::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            from: WorkflowState::Initial,
+            to: WorkflowState::Processing,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
+        });
+        let inspector = machine.inspector();
+        let monitor = inspector.clone();
+        assert_eq!(monitor.current_state(), WorkflowState::Initial);
+
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+
+        let view = monitor.view();
+        assert_eq!(view.current, WorkflowState::Processing);
+        assert_eq!(view.transitions_taken(), 1);
+
+        machine.pause("maintenance");
+        assert!(inspector.view().metadata.paused.is_some());
+    }
+
+    #[test]
+    fn scrubber_redacts_history_but_not_current_state() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.set_scrubber(
+            crate::core::Scrubber::new()
+                .states(|_: &WorkflowState| WorkflowState::Failed)
+                .text(|_| "[redacted]".to_string()),
+        );
+        machine.apply_result(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+        );
+        machine.pause("call jane on 555-0100");
+
+        let transition = &machine.history().transitions()[0];
+        assert_eq!(transition.from, WorkflowState::Failed);
+        assert_eq!(transition.to, WorkflowState::Failed);
+        assert_eq!(machine.current_state(), &WorkflowState::Processing);
+        assert!(matches!(
+            &machine.history().events()[0],
+            HistoryEvent::Paused { reason, .. } if reason == "[redacted]"
+        ));
+    }
+
+    #[test]
+    fn scrubber_redacts_correlation_feedback_and_signals_in_checkpoints() {
+        let scrubber = crate::core::Scrubber::new().text(|_| "[redacted]".to_string());
+        let run = |scrubber: Option<&crate::core::Scrubber<WorkflowState>>| {
+            let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+            if let Some(scrubber) = scrubber {
+                machine.set_scrubber(scrubber.clone());
+            }
+            machine.apply_result_with_correlation(
+                WorkflowState::Initial,
+                StepResult::Transitioned(WorkflowState::Processing),
+                1,
+                BTreeMap::from([("customer".to_string(), "jane@example.com".to_string())]),
+            );
+            machine.apply_result(
+                WorkflowState::Processing,
+                StepResult::Retry {
+                    feedback: "card 4111 declined".to_string(),
+                    attempts: 1,
+                },
+                1,
+            );
+            machine.signal("approval", serde_json::json!({"by": "jane", "level": 2}));
+            machine
+        };
+
+        let live = run(Some(&scrubber));
+        let existing = crate::checkpoint::scrub_existing(run(None).checkpoint(), &scrubber);
+
+        assert_eq!(
+            live.metadata.retry_feedback.as_deref(),
+            Some("card 4111 declined")
+        );
+        for checkpoint in [live.checkpoint(), existing] {
+            assert_eq!(
+                checkpoint.history.transitions()[0].correlation["customer"],
+                "[redacted]"
+            );
+            assert_eq!(
+                checkpoint.metadata.retry_feedback.as_deref(),
+                Some("[redacted]")
+            );
+            assert_eq!(
+                checkpoint.metadata.pending_signals[0].payload,
+                serde_json::json!({"by": "[redacted]", "level": 2})
+            );
+        }
+    }
+
+    #[test]
+    fn scrub_existing_checkpoint_keeps_resumable_state() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.apply_result(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+        );
+        let scrubber =
+            crate::core::Scrubber::new().states(|_: &WorkflowState| WorkflowState::Failed);
+
+        let checkpoint = crate::checkpoint::scrub_existing(machine.checkpoint(), &scrubber);
+
+        assert_eq!(
+            checkpoint.history.transitions()[0].from,
+            WorkflowState::Failed
+        );
+        assert_eq!(checkpoint.current_state, WorkflowState::Processing);
+    }
+
+    #[test]
+    fn checked_definition_changes_keep_current_state_reachable() {
+        use crate::builder::simple_transition;
+
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.add_transition(simple_transition(
+            WorkflowState::Initial,
+            WorkflowState::Processing,
+        ));
+
+        assert!(matches!(
+            machine.add_transition_checked(simple_transition(
+                WorkflowState::Complete,
+                WorkflowState::Failed,
+            )),
+            Err(DefinitionError::UnknownSourceState { .. })
+        ));
+        assert!(matches!(
+            machine.remove_transition_checked(0),
+            Err(DefinitionError::OrphansCurrentState { ref state }) if state == "Initial"
+        ));
+
+        machine
+            .add_transition_checked(simple_transition(
+                WorkflowState::Initial,
+                WorkflowState::Failed,
+            ))
+            .unwrap();
+        let removed = machine.remove_transition_checked(0).unwrap();
+        assert_eq!(removed.to, WorkflowState::Processing);
+        assert_eq!(machine.transitions().len(), 1);
+        assert!(matches!(
+            machine.history().events(),
+            [
+                HistoryEvent::TransitionAdded { to: added, .. },
+                HistoryEvent::TransitionRemoved { to: dropped, .. },
+            ] if added == "Failed" && dropped == "Processing"
+        ));
+        assert!(matches!(
+            machine.remove_transition_checked(5),
+            Err(DefinitionError::NoSuchTransition { index: 5 })
+        ));
+    }
+
+    #[test]
+    fn namespace_and_labels_survive_checkpoint() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        assert_eq!(machine.namespace(), None);
+        machine.set_namespace("tenant-a");
+        machine.set_label("team", "billing");
+
+        let restored = StateMachine::<WorkflowState, TestEnv>::from_binary(
+            &machine.to_binary().unwrap(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(restored.namespace(), Some("tenant-a"));
+        assert_eq!(
+            restored.labels().get("team").map(String::as_str),
+            Some("billing")
+        );
+    }
+
+    #[test]
+    fn shared_states_roundtrip_through_checkpoint() {
+        let mut machine =
+            StateMachine::<Arc<WorkflowState>, TestEnv>::new(Arc::new(WorkflowState::Initial));
+        machine.apply_result(
+            Arc::new(WorkflowState::Initial),
+            StepResult::Transitioned(Arc::new(WorkflowState::Processing)),
+            1,
+        );
+        assert!(Arc::ptr_eq(
+            machine.current_state(),
+            &machine.history().transitions()[0].to
+        ));
+
+        let restored = StateMachine::<Arc<WorkflowState>, TestEnv>::from_binary(
+            &machine.to_binary().unwrap(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(**restored.current_state(), WorkflowState::Processing);
+        assert_eq!(restored.history().transitions().len(), 1);
+    }
+
+    #[test]
+    fn correlation_data_is_recorded_and_checkpointed() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let correlation = BTreeMap::from([("request_id".to_string(), "req-42".to_string())]);
+        machine.apply_result_with_correlation(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+            correlation.clone(),
+        );
+
+        let restored =
+            StateMachine::<WorkflowState, TestEnv>::from_json(&machine.to_json().unwrap(), vec![])
+                .unwrap();
+        assert_eq!(restored.history().transitions()[0].correlation, correlation);
+    }
+
+    #[tokio::test]
+    async fn started_at_resets_on_state_change_only() {
+        let mut machine = StateMachine::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            from: WorkflowState::Initial,
+            to: WorkflowState::Processing,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| {
+                pure(TransitionResult::Retry {
+                    feedback: "Not ready yet".to_string(),
+                    current_state: WorkflowState::Initial,
+                })
+                .boxed()
+            }),
+        });
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let started = machine.current_transition_started_at();
+
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.current_transition_started_at(), started);
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        machine.force_transition_to(WorkflowState::Processing, "unblock", "ops");
+        assert!(machine.current_transition_started_at() > started);
+
+        let restored =
+            StateMachine::<WorkflowState, TestEnv>::from_json(&machine.to_json().unwrap(), vec![])
+                .unwrap();
+        assert_eq!(
+            restored.current_transition_started_at(),
+            machine.current_transition_started_at()
+        );
+    }
+
+    #[test]
+    fn force_transition_records_audit_trail() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+
+        machine.force_transition_to(WorkflowState::Complete, "stuck on vendor", "alice");
+
+        assert_eq!(machine.current_state(), &WorkflowState::Complete);
+        let record = &machine.history().transitions()[0];
+        assert_eq!(record.from, WorkflowState::Initial);
+        assert_eq!(
+            record.forced,
+            Some(ForcedTransition {
+                reason: "stuck on vendor".to_string(),
+                operator: "alice".to_string(),
+            })
+        );
+
+        let restored =
+            StateMachine::<WorkflowState, TestEnv>::from_json(&machine.to_json().unwrap(), vec![])
+                .unwrap();
+        assert!(restored.history().transitions()[0].forced.is_some());
+    }
+
+    #[test]
+    fn machine_and_checkpoint_ids_come_from_the_id_generator() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.set_id_generator(crate::checkpoint::SequentialIds::new("order"));
+
+        assert_eq!(machine.id(), "order-1");
+        assert_eq!(machine.checkpoint().id, "order-2");
+        assert_eq!(machine.clone().checkpoint().id, "order-3");
+
+        let mut restored =
+            StateMachine::<WorkflowState, TestEnv>::from_checkpoint(machine.checkpoint(), vec![])
+                .unwrap();
+        restored.set_id_generator(crate::checkpoint::SequentialIds::new("other"));
+        assert_eq!(restored.id(), "order-1");
+    }
+
+    #[test]
+    fn stats_are_kept_in_checkpoint_metadata() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let retry = StepResult::Retry {
+            feedback: "busy".to_string(),
+            attempts: 1,
+        };
+        machine.apply_result(WorkflowState::Initial, retry, 0);
+        machine.apply_result(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+        );
+        machine.apply_result(
+            WorkflowState::Processing,
+            StepResult::Aborted {
+                reason: "quota".to_string(),
+                error_state: WorkflowState::Failed,
+            },
+            0,
+        );
+        machine.force_transition_to(WorkflowState::Processing, "quota raised", "alice");
+
+        let stats = machine.checkpoint().metadata.stats;
+        assert_eq!((stats.transitions, stats.retries, stats.aborts), (2, 1, 1));
+        assert_eq!(stats.state_entries["Processing"], 2);
+        assert_eq!(stats.state_entries["Failed"], 1);
+        assert!(!stats.state_entries.contains_key("Initial"));
+    }
+
+    #[tokio::test]
+    async fn resume_at_positions_machine_without_history() {
+        let transitions = vec![Transition {
+            from: WorkflowState::Processing,
+            to: WorkflowState::Complete,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Complete)).boxed()),
+        }];
+
+        let mut machine = StateMachine::resume_at(WorkflowState::Processing, transitions);
+        assert_eq!(machine.current_state(), &WorkflowState::Processing);
+        assert!(machine.history().transitions().is_empty());
+
+        let json = machine.to_json().unwrap();
+        assert!(json.contains("synthetic_start"));
+        let note = machine.checkpoint().metadata.synthetic_start.unwrap();
+        assert_eq!(note.state, "Processing");
+
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let (from, result, attempt) = machine.step().run(&env).await.unwrap();
+        machine.apply_result(from, result, attempt);
+        assert_eq!(machine.current_state(), &WorkflowState::Complete);
+    }
+
     #[test]
     fn binary_format_smaller_than_json() {
         let machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
@@ -511,6 +2117,39 @@ mod tests {
         assert!(binary.len() < json.len() / 2);
     }
 
+    #[test]
+    fn version_1_binary_checkpoints_still_load() {
+        // Written by mindset 0.1 with `bincode::serialize`
+        let bytes = include_bytes!("../../tests/fixtures/checkpoint_v1.bin");
+
+        let machine = StateMachine::<WorkflowState, TestEnv>::from_binary(bytes, vec![]).unwrap();
+        assert_eq!(machine.current_state(), &WorkflowState::Complete);
+        assert_eq!(
+            machine.history().get_path(),
+            vec![
+                &WorkflowState::Initial,
+                &WorkflowState::Processing,
+                &WorkflowState::Complete
+            ]
+        );
+        assert_eq!(machine.history().transitions()[1].sequence, 2);
+        assert_eq!(
+            machine.checkpoint().metadata.total_attempts["Processing"],
+            2
+        );
+
+        let restored = StateMachine::<WorkflowState, TestEnv>::from_binary(
+            &machine.to_binary().unwrap(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            restored.checkpoint().version,
+            crate::checkpoint::CHECKPOINT_VERSION
+        );
+        assert_eq!(restored.history().transitions().len(), 2);
+    }
+
     #[tokio::test]
     async fn resumed_machine_can_continue_execution() {
         let mut machine1 = StateMachine::new(WorkflowState::Initial);
@@ -522,6 +2161,9 @@ mod tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         });
 
@@ -529,6 +2171,9 @@ mod tests {
             from: WorkflowState::Processing,
             to: WorkflowState::Complete,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Complete)).boxed()),
         });
 
@@ -546,6 +2191,9 @@ mod tests {
                 from: WorkflowState::Initial,
                 to: WorkflowState::Processing,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Processing)).boxed()
                 }),
@@ -554,6 +2202,9 @@ mod tests {
                 from: WorkflowState::Processing,
                 to: WorkflowState::Complete,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Complete)).boxed()
                 }),
@@ -567,6 +2218,133 @@ mod tests {
         assert_eq!(machine2.current_state(), &WorkflowState::Complete);
     }
 
+    #[test]
+    fn changed_state_enum_reports_schema_mismatch() {
+        use crate::checkpoint::{CheckpointError, SchemaKind, StateSchema};
+
+        let machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let schema = |members: &[&str]| StateSchema {
+            type_name: "WorkflowState".to_string(),
+            kind: SchemaKind::Enum,
+            members: members.iter().map(|m| m.to_string()).collect(),
+            fields: Default::default(),
+        };
+
+        // A removed variant the checkpoint never used does not matter
+        let mut checkpoint = machine.checkpoint();
+        checkpoint.state_schema = Some(schema(&["Initial", "Retired", "Processing"]));
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        assert!(StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![]).is_ok());
+
+        let json = json.replace(
+            "\"current_state\":\"Initial\"",
+            "\"current_state\":\"Retired\"",
+        );
+        let from_json = StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![]);
+        assert!(matches!(
+            from_json,
+            Err(CheckpointError::StateSchemaMismatch { ref missing, .. })
+                if missing == &vec!["Retired".to_string()]
+        ));
+
+        // Binary checkpoints store indices: index 0 was `Retired` when written
+        checkpoint.state_schema = Some(schema(&["Retired", "Initial"]));
+        let bytes = crate::checkpoint::binary_options()
+            .serialize(&checkpoint)
+            .unwrap();
+        let from_binary = StateMachine::<WorkflowState, TestEnv>::from_binary(&bytes, vec![]);
+        assert!(matches!(
+            from_binary,
+            Err(CheckpointError::StateSchemaMismatch { ref missing, .. })
+                if missing == &vec!["Retired".to_string()]
+        ));
+
+        checkpoint.state_schema = Some(schema(&["Initial", "Retired"]));
+        let bytes = crate::checkpoint::binary_options()
+            .serialize(&checkpoint)
+            .unwrap();
+        assert!(StateMachine::<WorkflowState, TestEnv>::from_binary(&bytes, vec![]).is_ok());
+    }
+
+    #[test]
+    fn passed_deadline_moves_machine_to_deadline_state() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let deadline = machine.created_at() + chrono::Duration::minutes(5);
+        machine.set_deadline(deadline, WorkflowState::Failed);
+
+        assert!(!machine.enforce_deadline_at(deadline - chrono::Duration::seconds(1)));
+        assert_eq!(machine.current_state(), &WorkflowState::Initial);
+
+        let mut restored =
+            StateMachine::<WorkflowState, TestEnv>::from_json(&machine.to_json().unwrap(), vec![])
+                .unwrap();
+        assert_eq!(restored.deadline(), Some(deadline));
+        assert!(!restored.enforce_deadline_at(deadline));
+
+        restored.set_deadline_state(WorkflowState::Failed);
+        assert!(restored.enforce_deadline_at(deadline));
+        assert_eq!(restored.current_state(), &WorkflowState::Failed);
+        let record = restored.history().transitions().last().unwrap();
+        assert_eq!(record.forced.as_ref().unwrap().operator, "deadline");
+        assert!(!restored.enforce_deadline_at(deadline));
+    }
+
+    #[test]
+    fn resumed_machines_count_resumes_until_they_move() {
+        let fresh = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        assert!(!fresh.is_resumed());
+        assert_eq!(fresh.resume_count(), 0);
+
+        let json = fresh.to_json().unwrap();
+        let once = StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![]).unwrap();
+        let json = once.to_json().unwrap();
+        let mut hooked = false;
+        let mut twice = StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![])
+            .unwrap()
+            .on_resume(|_| hooked = true);
+
+        assert!(hooked);
+        assert!(twice.is_resumed());
+        assert_eq!(twice.resume_count(), 2);
+        assert!(twice.step_context().resumed);
+
+        twice.apply_result(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+        );
+        assert!(!twice.is_resume_pending());
+        assert!(twice.is_resumed());
+    }
+
+    #[test]
+    fn migrated_json_loads_renamed_variants() {
+        let machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let json = serde_json::to_string(&machine.checkpoint())
+            .unwrap()
+            .replace("\"Initial\"", "\"Draft\"");
+
+        assert!(StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![]).is_err());
+
+        let migration = crate::checkpoint::StateMigration::new().rename_variant("Draft", "Initial");
+        let restored =
+            StateMachine::<WorkflowState, TestEnv>::from_json_migrated(&json, vec![], &migration)
+                .unwrap();
+        assert_eq!(restored.current_state(), &WorkflowState::Initial);
+    }
+
+    #[test]
+    fn checkpoint_records_state_schema() {
+        let machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        let schema = machine.checkpoint().state_schema.unwrap();
+
+        assert_eq!(schema.type_name, "WorkflowState");
+        assert_eq!(
+            schema.members,
+            vec!["Initial", "Processing", "Complete", "Failed"]
+        );
+    }
+
     #[test]
     fn unsupported_version_returns_error() {
         use crate::checkpoint::Checkpoint;
@@ -574,6 +2352,7 @@ mod tests {
 
         let checkpoint = Checkpoint {
             version: 999,
+            state_schema: None,
             id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             initial_state: WorkflowState::Initial,
@@ -590,6 +2369,148 @@ mod tests {
             Err(crate::checkpoint::CheckpointError::UnsupportedVersion { .. })
         ));
     }
+
+    #[tokio::test]
+    async fn no_transition_diagnoses_each_candidate() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.add_transition(Transition {
+            guard: Some(Guard::new(|_: &WorkflowState| false).with_label("has budget")),
+            ..crate::builder::simple_transition(WorkflowState::Initial, WorkflowState::Processing)
+        });
+        machine.add_transition(
+            crate::builder::simple_transition(WorkflowState::Initial, WorkflowState::Complete)
+                .behind_flag("fast-path"),
+        );
+        machine.add_transition(crate::builder::simple_transition(
+            WorkflowState::Processing,
+            WorkflowState::Complete,
+        ));
+
+        let env = TestEnv {
+            _should_succeed: true,
+        };
+        let Err(TransitionError::NoTransition { from, candidates }) =
+            machine.step().run(&env).await
+        else {
+            panic!("expected NoTransition");
+        };
+
+        assert_eq!(from, "Initial");
+        assert_eq!(
+            candidates,
+            vec![
+                CandidateCheck {
+                    to: "Processing".to_string(),
+                    state_matched: true,
+                    guard_passed: Some(false),
+                    guard_label: Some("has budget".to_string()),
+                    disabled_by_flag: None,
+                },
+                CandidateCheck {
+                    to: "Complete".to_string(),
+                    state_matched: true,
+                    guard_passed: None,
+                    guard_label: None,
+                    disabled_by_flag: Some("fast-path".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn monotonic_timestamps_survive_clock_jumps() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.set_monotonic_timestamps(true);
+        let now = Utc::now();
+        machine.apply_result_at(
+            WorkflowState::Initial,
+            StepResult::Transitioned(WorkflowState::Processing),
+            1,
+            BTreeMap::new(),
+            now,
+        );
+        machine.apply_result_at(
+            WorkflowState::Processing,
+            StepResult::Transitioned(WorkflowState::Complete),
+            1,
+            BTreeMap::new(),
+            now - chrono::Duration::minutes(5),
+        );
+
+        let transitions = machine.history().transitions();
+        assert_eq!(transitions[1].timestamp, now);
+        assert_eq!(transitions[1].sequence, 2);
+        assert_eq!(
+            machine.history().duration(),
+            Some(std::time::Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn sampled_data_snapshots_survive_checkpoints() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.set_snapshot_policy(SnapshotPolicy::new().every(2));
+        let steps = [
+            (WorkflowState::Initial, WorkflowState::Processing),
+            (WorkflowState::Processing, WorkflowState::Initial),
+            (WorkflowState::Initial, WorkflowState::Complete),
+        ];
+        for (items, (from, to)) in steps.into_iter().enumerate() {
+            machine.apply_result_with_snapshot(
+                from,
+                StepResult::Transitioned(to),
+                1,
+                &serde_json::json!({ "items": items }),
+            );
+        }
+        machine.apply_result_with_snapshot(
+            WorkflowState::Complete,
+            StepResult::Paused {
+                reason: "done".to_string(),
+            },
+            1,
+            &serde_json::json!({ "items": 9 }),
+        );
+
+        let json = machine.to_json().unwrap();
+        let restored = StateMachine::<WorkflowState, TestEnv>::from_json(&json, vec![]).unwrap();
+        let history = restored.history();
+        let sampled: Vec<_> = history.snapshots().iter().map(|s| s.sequence).collect();
+        assert_eq!(sampled, vec![1, 3]);
+        assert_eq!(
+            history.snapshot_for(3).and_then(|s| s.value()),
+            Some(serde_json::json!({ "items": 2 }))
+        );
+    }
+
+    #[test]
+    fn bounded_histories_keep_capturing_snapshots() {
+        let mut machine = StateMachine::<WorkflowState, TestEnv>::new(WorkflowState::Initial);
+        machine.bound_history::<2>();
+        machine.set_snapshot_policy(SnapshotPolicy::new());
+        let steps = [
+            (WorkflowState::Initial, WorkflowState::Processing),
+            (WorkflowState::Processing, WorkflowState::Initial),
+            (WorkflowState::Initial, WorkflowState::Processing),
+            (WorkflowState::Processing, WorkflowState::Complete),
+        ];
+        for (items, (from, to)) in steps.into_iter().enumerate() {
+            machine.apply_result_with_snapshot(
+                from,
+                StepResult::Transitioned(to),
+                1,
+                &serde_json::json!({ "items": items }),
+            );
+        }
+
+        let sampled: Vec<_> = machine
+            .history()
+            .snapshots()
+            .iter()
+            .map(|s| s.sequence)
+            .collect();
+        assert_eq!(sampled, vec![3, 4]);
+    }
 }
 
 #[cfg(test)]
@@ -598,6 +2519,7 @@ mod integration_tests {
     use crate::effects::transition::{Transition, TransitionResult};
     use serde::{Deserialize, Serialize};
     use std::sync::Arc;
+    use stillwater::prelude::*;
 
     #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
     enum WorkflowState {
@@ -634,6 +2556,9 @@ mod integration_tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         });
 
@@ -642,6 +2567,9 @@ mod integration_tests {
             from: WorkflowState::Processing,
             to: WorkflowState::Complete,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Complete)).boxed()),
         });
 
@@ -680,6 +2608,9 @@ mod integration_tests {
             from: WorkflowState::Initial,
             to: WorkflowState::Processing,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
         });
 
@@ -687,6 +2618,9 @@ mod integration_tests {
             from: WorkflowState::Processing,
             to: WorkflowState::Complete,
             guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Complete)).boxed()),
         });
 
@@ -704,6 +2638,9 @@ mod integration_tests {
                 from: WorkflowState::Initial,
                 to: WorkflowState::Processing,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Processing)).boxed()
                 }),
@@ -712,6 +2649,9 @@ mod integration_tests {
                 from: WorkflowState::Processing,
                 to: WorkflowState::Complete,
                 guard: None,
+                location: None,
+                metadata: Default::default(),
+                flag: None,
                 action: Arc::new(|| {
                     pure(TransitionResult::Success(WorkflowState::Complete)).boxed()
                 }),
@@ -16,8 +16,66 @@
 //! - Collections store `BoxedEffect` (one allocation per transition)
 //! - Use free-standing constructors: `pure()`, `fail()`, `from_fn()`
 
+mod action;
+mod alarm;
+mod capabilities;
+mod context;
+mod debugger;
+mod diagnostics;
+mod event;
+mod explain;
+mod flags;
+mod fuel;
+mod hooks;
+mod inspector;
+mod invariant;
+mod lock;
 mod machine;
+mod plan;
+mod registry;
+mod replay;
+mod sampling;
+mod signal;
+mod spawn;
+mod speculate;
+mod static_transition;
+mod timer;
 mod transition;
+mod verify;
 
+pub use action::{
+    action_from_async, action_map_err, action_retrying, action_retrying_with, action_try,
+    action_with_timeout, RetryAttempt, RetryDecider, RetryDecision,
+};
+pub use alarm::{StateAlarm, StateAlarms};
+pub use capabilities::{Capabilities, CapabilityKey, HasCapabilities, MissingCapabilities};
+pub use context::{
+    action_with_context, HasStepContext, StepContext, StepEvents, RECENT_TRANSITIONS,
+};
+pub use debugger::{Debugger, Stop, DEFAULT_STEP_LIMIT};
+pub use diagnostics::{StepDiagnostics, StepProbe};
+pub use event::{Event, EventAction, EventTransition};
+pub use explain::{BlockReason, Explanation};
+pub use flags::{FeatureFlagProvider, HasFeatureFlags};
+pub use fuel::FuelReport;
+pub use hooks::StateHook;
+pub use inspector::{MachineInspector, MachineView};
+pub use invariant::{Invariant, InvariantViolation};
+pub use lock::{HasLockManager, LocalLocks, LockManager};
 pub use machine::{StateMachine, StepResult};
-pub use transition::{Transition, TransitionError, TransitionResult};
+pub use plan::{Plan, PlanStep, MAX_PLANS};
+pub use registry::{ActionRegistry, TransitionSpec};
+pub use replay::{RecordedOutcome, ReplayError, StepRecord, Trace};
+pub use sampling::{DroppedEvents, TelemetryKind, TelemetrySampler};
+pub use signal::SignalWait;
+pub use spawn::{spawn_child, HasSpawner, Spawner, CHILD_COMPLETED};
+pub use speculate::Speculation;
+pub use static_transition::StaticTransition;
+pub use timer::{
+    DueTimer, MachineTimers, TimerError, TimerQueue, TimerReconciliation, TimerService,
+};
+pub use transition::{
+    CandidateCheck, DefinitionError, Transition, TransitionAction, TransitionContext,
+    TransitionError, TransitionResult,
+};
+pub use verify::Verification;
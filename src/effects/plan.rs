@@ -0,0 +1,176 @@
+//! Plans for reaching a target state.
+//!
+//! Where [`StateMachine::explain`] answers whether a target is one step
+//! away, [`StateMachine::plan_to`] lays out the routes through the
+//! transition graph that lead there, and what currently stands in the way
+//! on each hop. Guards are pure predicates on states, so they are evaluated
+//! against each hop's source state even for hops further down the route.
+
+use crate::core::State;
+use crate::effects::explain::BlockReason;
+use crate::effects::machine::StateMachine;
+
+/// Maximum number of plans returned by [`StateMachine::plan_to`].
+pub const MAX_PLANS: usize = 16;
+
+/// One transition along a [`Plan`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanStep<S: State> {
+    /// State the transition starts from
+    pub from: S,
+    /// State the transition leads to
+    pub to: S,
+    /// What currently prevents the transition, empty if nothing does
+    pub blockers: Vec<BlockReason<S>>,
+}
+
+/// A route of transitions from the current state to a target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan<S: State> {
+    /// Transitions in the order they have to happen
+    pub steps: Vec<PlanStep<S>>,
+}
+
+impl<S: State> Plan<S> {
+    /// Whether no step of the plan is blocked (pure)
+    pub fn is_clear(&self) -> bool {
+        self.steps.iter().all(|step| step.blockers.is_empty())
+    }
+
+    /// Blockers along the plan, in step order (pure)
+    pub fn blockers(&self) -> impl Iterator<Item = &BlockReason<S>> + '_ {
+        self.steps.iter().flat_map(|step| &step.blockers)
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Routes from the current state to `target`, without revisiting a
+    /// state (pure).
+    ///
+    /// Clear plans come first, then plans with fewer blockers; ties keep
+    /// the shorter plan first. At most [`MAX_PLANS`] plans are returned.
+    /// Flagged transitions count as blocked, since flags are only known to
+    /// the environment. A machine already in `target` gets a single empty
+    /// plan; an unreachable target gets none.
+    pub fn plan_to(&self, target: &S) -> Vec<Plan<S>> {
+        let mut plans = Vec::new();
+        let mut route = Vec::new();
+        let mut visited = vec![self.current_state().clone()];
+        self.collect_plans(target, &mut visited, &mut route, &mut plans);
+        plans.sort_by_key(|plan| (plan.blockers().count(), plan.steps.len()));
+        plans
+    }
+
+    fn collect_plans(
+        &self,
+        target: &S,
+        visited: &mut Vec<S>,
+        route: &mut Vec<PlanStep<S>>,
+        plans: &mut Vec<Plan<S>>,
+    ) {
+        let at = visited
+            .last()
+            .expect("route starts at the current state")
+            .clone();
+        if &at == target {
+            plans.push(Plan {
+                steps: route.clone(),
+            });
+            return;
+        }
+        let next: Vec<_> = self
+            .transitions()
+            .iter()
+            .filter(|t| t.from == at && !visited.contains(&t.to))
+            .collect();
+        for transition in next {
+            if plans.len() >= MAX_PLANS {
+                return;
+            }
+            let mut blockers = Vec::new();
+            if let Some(flag) = &transition.flag {
+                blockers.push(BlockReason::DisabledByFlag { flag: flag.clone() });
+            }
+            if let Some(guard) = transition.guard.as_ref().filter(|g| !g.check(&at)) {
+                blockers.push(BlockReason::GuardFailed {
+                    from: at.clone(),
+                    label: guard.label().map(String::from),
+                });
+            }
+            route.push(PlanStep {
+                from: at.clone(),
+                to: transition.to.clone(),
+                blockers,
+            });
+            visited.push(transition.to.clone());
+            self.collect_plans(target, visited, route, plans);
+            visited.pop();
+            route.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::core::Guard;
+    use crate::state_enum;
+
+    state_enum! {
+        enum Ticket {
+            Open,
+            Triaged,
+            Escalated,
+            Closed,
+        }
+        final: [Closed]
+    }
+
+    fn machine() -> StateMachine<Ticket, ()> {
+        let mut machine = StateMachine::new(Ticket::Open);
+        machine.add_transition(simple_transition(Ticket::Open, Ticket::Triaged));
+        machine.add_transition(simple_transition(Ticket::Triaged, Ticket::Closed));
+        machine.add_transition(simple_transition(Ticket::Open, Ticket::Escalated));
+        let mut close = simple_transition(Ticket::Escalated, Ticket::Closed);
+        close.guard = Some(Guard::new(|_: &Ticket| false).with_label("manager sign-off"));
+        machine.add_transition(close);
+        machine.add_transition(simple_transition(Ticket::Triaged, Ticket::Open));
+        machine
+    }
+
+    #[test]
+    fn plans_list_routes_with_blockers_clear_first() {
+        let plans = machine().plan_to(&Ticket::Closed);
+
+        let routes: Vec<Vec<_>> = plans
+            .iter()
+            .map(|plan| plan.steps.iter().map(|step| step.to.clone()).collect())
+            .collect();
+        assert_eq!(
+            routes,
+            vec![
+                vec![Ticket::Triaged, Ticket::Closed],
+                vec![Ticket::Escalated, Ticket::Closed],
+            ]
+        );
+        assert!(plans[0].is_clear());
+        assert_eq!(
+            plans[1].blockers().collect::<Vec<_>>(),
+            vec![&BlockReason::GuardFailed {
+                from: Ticket::Escalated,
+                label: Some("manager sign-off".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn current_and_unreachable_targets() {
+        let machine = machine();
+        assert_eq!(machine.plan_to(&Ticket::Open), vec![Plan { steps: vec![] }]);
+
+        let mut done = StateMachine::<Ticket, ()>::new(Ticket::Closed);
+        done.add_transition(simple_transition(Ticket::Open, Ticket::Closed));
+        assert!(done.plan_to(&Ticket::Open).is_empty());
+    }
+}
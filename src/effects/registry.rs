@@ -0,0 +1,211 @@
+//! Late-bound transition actions.
+//!
+//! Transitions normally capture their action closure when they are built,
+//! so a definition only exists inside the binary that built it. A
+//! [`TransitionSpec`] instead names its action, and is plain data that can
+//! be stored next to checkpoints. At resume time an [`ActionRegistry`]
+//! resolves the names against the actions the running binary provides.
+//! Resolution checks every name up front, so a binary missing an action is
+//! rejected before any machine is restored rather than in the middle of a
+//! step.
+
+use crate::core::{GuardExpr, State};
+use crate::effects::transition::{DefinitionError, Transition, TransitionAction};
+use crate::effects::{TransitionError, TransitionResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use stillwater::effect::BoxedEffect;
+
+/// Serializable transition referring to its action by name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransitionSpec<S> {
+    /// Source state
+    pub from: S,
+    /// Target state
+    pub to: S,
+    /// Name the action is registered under
+    pub action: String,
+    /// Guard expression, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guard: Option<GuardExpr>,
+    /// Metadata copied onto the resolved transition
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Feature flag copied onto the resolved transition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag: Option<String>,
+}
+
+impl<S> TransitionSpec<S> {
+    /// Spec for an unguarded transition running the action named `action`
+    pub fn new(from: S, to: S, action: impl Into<String>) -> Self {
+        Self {
+            from,
+            to,
+            action: action.into(),
+            guard: None,
+            metadata: BTreeMap::new(),
+            flag: None,
+        }
+    }
+
+    /// Guard the transition with `expr`
+    pub fn when_expr(mut self, expr: GuardExpr) -> Self {
+        self.guard = Some(expr);
+        self
+    }
+}
+
+/// Named transition actions provided by the running binary.
+pub struct ActionRegistry<S: State, Env> {
+    actions: HashMap<String, TransitionAction<S, Env>>,
+}
+
+impl<S: State, Env> Default for ActionRegistry<S, Env> {
+    fn default() -> Self {
+        Self {
+            actions: HashMap::new(),
+        }
+    }
+}
+
+impl<S: State + 'static, Env> ActionRegistry<S, Env> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `action` under `name`, replacing any previous action
+    pub fn register<F>(&mut self, name: impl Into<String>, action: F) -> &mut Self
+    where
+        F: Fn() -> BoxedEffect<TransitionResult<S>, TransitionError, Env> + Send + Sync + 'static,
+    {
+        self.actions.insert(name.into(), Arc::new(action));
+        self
+    }
+
+    /// Whether an action is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.actions.contains_key(name)
+    }
+
+    /// Names of the registered actions, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.actions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Resolve specs into executable transitions.
+    ///
+    /// Fails with [`DefinitionError::UnknownActions`] listing every action
+    /// name the registry does not provide, without resolving any spec.
+    pub fn resolve(
+        &self,
+        specs: &[TransitionSpec<S>],
+    ) -> Result<Vec<Transition<S, Env>>, DefinitionError> {
+        let mut missing: Vec<String> = specs
+            .iter()
+            .filter(|spec| !self.contains(&spec.action))
+            .map(|spec| spec.action.clone())
+            .collect();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            missing.dedup();
+            return Err(DefinitionError::UnknownActions { names: missing });
+        }
+
+        Ok(specs
+            .iter()
+            .map(|spec| Transition {
+                from: spec.from.clone(),
+                to: spec.to.clone(),
+                guard: spec.guard.clone().map(GuardExpr::into_guard),
+                location: None,
+                metadata: spec.metadata.clone(),
+                flag: spec.flag.clone(),
+                action: Arc::clone(&self.actions[&spec.action]),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult};
+    use serde_json::json;
+    use stillwater::prelude::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Deploy {
+        Built { approved: bool },
+        Released,
+    }
+
+    impl State for Deploy {
+        fn name(&self) -> &str {
+            match self {
+                Self::Built { .. } => "Built",
+                Self::Released => "Released",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Released)
+        }
+    }
+
+    fn specs() -> Vec<TransitionSpec<Deploy>> {
+        vec![TransitionSpec::new(
+            Deploy::Built { approved: true },
+            Deploy::Released,
+            "release",
+        )
+        .when_expr(GuardExpr::eq("Built.approved", json!(true)))]
+    }
+
+    #[tokio::test]
+    async fn resumes_with_actions_from_the_registry() {
+        let machine = StateMachine::<Deploy, ()>::new(Deploy::Built { approved: true });
+        let stored = serde_json::to_string(&specs()).unwrap();
+
+        let mut registry = ActionRegistry::<Deploy, ()>::new();
+        registry.register("release", || {
+            pure(TransitionResult::Success(Deploy::Released)).boxed()
+        });
+        let specs: Vec<TransitionSpec<Deploy>> = serde_json::from_str(&stored).unwrap();
+        let restored = StateMachine::from_json(
+            &machine.to_json().unwrap(),
+            registry.resolve(&specs).unwrap(),
+        )
+        .unwrap();
+
+        let (_, result, _) = restored.step().run(&()).await.unwrap();
+        assert_eq!(result, StepResult::Transitioned(Deploy::Released));
+    }
+
+    #[test]
+    fn missing_actions_are_reported_up_front() {
+        let mut specs = specs();
+        specs.push(TransitionSpec::new(
+            Deploy::Released,
+            Deploy::Released,
+            "audit",
+        ));
+        specs.push(TransitionSpec::new(
+            Deploy::Built { approved: false },
+            Deploy::Released,
+            "release",
+        ));
+
+        let registry = ActionRegistry::<Deploy, ()>::new();
+        assert_eq!(
+            registry.resolve(&specs).err(),
+            Some(DefinitionError::UnknownActions {
+                names: vec!["audit".to_string(), "release".to_string()]
+            })
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Step-level recording and deterministic replay.
+//!
+//! [`StateMachine::step_recorded`] runs a step like `step()` followed by
+//! `apply_result()`, and appends what happened to a [`Trace`]: the state
+//! the step started from, the transition chosen, the outcome and its
+//! timestamps. Traces are serializable, so they can be captured in
+//! production and shipped alongside an incident report.
+//!
+//! [`StateMachine::replay`] re-drives a machine from a trace. Actions are
+//! not run; each step applies its recorded outcome with its recorded
+//! timestamp, so the resulting history matches the original run. Replay
+//! stops with a [`ReplayError`] as soon as the machine diverges from the
+//! trace, e.g. because the definition changed since it was recorded.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepResult};
+use crate::effects::transition::TransitionError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use stillwater::effect::Effect;
+use thiserror::Error;
+
+/// Outcome of a recorded step.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum RecordedOutcome<S: State> {
+    /// The machine moved to a new state
+    Transitioned(S),
+    /// The action asked to be retried
+    Retry { feedback: String, attempts: usize },
+    /// The action aborted into an error state
+    Aborted { reason: String, error_state: S },
+    /// The machine was paused and attempted nothing
+    Paused { reason: String },
+    /// The current state waited for a signal and attempted nothing
+    AwaitingSignal { signal: String },
+    /// The step failed with an error and changed nothing
+    Failed { error: String },
+}
+
+impl<S: State> From<StepResult<S>> for RecordedOutcome<S> {
+    fn from(result: StepResult<S>) -> Self {
+        match result {
+            StepResult::Transitioned(state) => Self::Transitioned(state),
+            StepResult::Retry { feedback, attempts } => Self::Retry { feedback, attempts },
+            StepResult::Aborted {
+                reason,
+                error_state,
+            } => Self::Aborted {
+                reason,
+                error_state,
+            },
+            StepResult::Paused { reason } => Self::Paused { reason },
+            StepResult::AwaitingSignal { signal } => Self::AwaitingSignal { signal },
+        }
+    }
+}
+
+impl<S: State> RecordedOutcome<S> {
+    /// The step result to re-apply, or `None` for failed steps
+    fn to_step_result(&self) -> Option<StepResult<S>> {
+        match self {
+            Self::Transitioned(state) => Some(StepResult::Transitioned(state.clone())),
+            Self::Retry { feedback, attempts } => Some(StepResult::Retry {
+                feedback: feedback.clone(),
+                attempts: *attempts,
+            }),
+            Self::Aborted {
+                reason,
+                error_state,
+            } => Some(StepResult::Aborted {
+                reason: reason.clone(),
+                error_state: error_state.clone(),
+            }),
+            Self::Paused { reason } => Some(StepResult::Paused {
+                reason: reason.clone(),
+            }),
+            Self::AwaitingSignal { signal } => Some(StepResult::AwaitingSignal {
+                signal: signal.clone(),
+            }),
+            Self::Failed { .. } => None,
+        }
+    }
+}
+
+/// Record of a single step.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct StepRecord<S: State> {
+    /// State the step started from
+    pub from: S,
+    /// Index of the transition chosen, or `None` if none was
+    pub transition: Option<usize>,
+    /// Attempt count the step ran with
+    pub attempt: usize,
+    /// What the step produced
+    pub outcome: RecordedOutcome<S>,
+    /// When the step started
+    pub started_at: DateTime<Utc>,
+    /// When the step finished
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Ordered trace of recorded steps.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Trace<S: State> {
+    /// Recorded steps, oldest first
+    pub steps: Vec<StepRecord<S>>,
+}
+
+impl<S: State> Default for Trace<S> {
+    fn default() -> Self {
+        Self { steps: Vec::new() }
+    }
+}
+
+impl<S: State> Trace<S> {
+    /// Create an empty trace
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Ways a replay can diverge from its trace.
+#[derive(Debug, Error, PartialEq)]
+pub enum ReplayError {
+    #[error(
+        "Replay diverged at step {step}: expected state '{expected}', machine is in '{actual}'"
+    )]
+    StateMismatch {
+        step: usize,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Replay diverged at step {step}: trace chose transition {expected:?}, machine chooses {actual:?}")]
+    TransitionMismatch {
+        step: usize,
+        expected: Option<usize>,
+        actual: Option<usize>,
+    },
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Index of the transition `step()` would run, if any (pure)
+    fn chosen_transition(&self) -> Option<usize> {
+        if self.is_paused() || self.awaited_signal().is_some() {
+            return None;
+        }
+        self.transitions()
+            .iter()
+            .position(|t| t.can_execute(self.current_state()))
+    }
+
+    /// Run one step, apply its result and append it to `trace`.
+    ///
+    /// Behaves like `step()` followed by `apply_result()`. Failed steps are
+    /// recorded too before the error is returned.
+    pub async fn step_recorded(
+        &mut self,
+        env: &Env,
+        trace: &mut Trace<S>,
+    ) -> Result<StepResult<S>, TransitionError> {
+        let from = self.current_state().clone();
+        let transition = self.chosen_transition();
+        let started_at = Utc::now();
+        let outcome = self.step().run(env).await;
+        let finished_at = Utc::now();
+
+        let (attempt, recorded, result) = match outcome {
+            Ok((from_state, result, attempt)) => {
+                self.apply_result_at(
+                    from_state,
+                    result.clone(),
+                    attempt,
+                    BTreeMap::new(),
+                    finished_at,
+                );
+                (attempt, result.clone().into(), Ok(result))
+            }
+            Err(error) => (
+                self.attempt_count(),
+                RecordedOutcome::Failed {
+                    error: error.to_string(),
+                },
+                Err(error),
+            ),
+        };
+
+        trace.steps.push(StepRecord {
+            from,
+            transition,
+            attempt,
+            outcome: recorded,
+            started_at,
+            finished_at,
+        });
+        result
+    }
+
+    /// Re-drive the machine from a trace without running any actions.
+    ///
+    /// Each recorded outcome is applied with its recorded timestamp.
+    /// Replay checks before every step that the machine is in the recorded
+    /// state and would choose the recorded transition.
+    pub fn replay(&mut self, trace: &Trace<S>) -> Result<(), ReplayError> {
+        for (step, record) in trace.steps.iter().enumerate() {
+            if self.current_state() != &record.from {
+                return Err(ReplayError::StateMismatch {
+                    step,
+                    expected: record.from.display_name().into_owned(),
+                    actual: self.current_state().display_name().into_owned(),
+                });
+            }
+
+            let Some(result) = record.outcome.to_step_result() else {
+                continue;
+            };
+            if !matches!(
+                result,
+                StepResult::Paused { .. } | StepResult::AwaitingSignal { .. }
+            ) {
+                let actual = self.chosen_transition();
+                if actual != record.transition {
+                    return Err(ReplayError::TransitionMismatch {
+                        step,
+                        expected: record.transition,
+                        actual,
+                    });
+                }
+            }
+
+            self.apply_result_at(
+                record.from.clone(),
+                result,
+                record.attempt,
+                BTreeMap::new(),
+                record.finished_at,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::effects::{Transition, TransitionResult};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use stillwater::prelude::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Queued,
+        Running,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Queued => "Queued",
+                Self::Running => "Running",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    /// Retries once before succeeding, counting action runs.
+    fn flaky_machine(runs: Arc<AtomicUsize>) -> StateMachine<TestState, ()> {
+        let mut machine = StateMachine::new(TestState::Queued);
+        machine.add_transition(simple_transition(TestState::Queued, TestState::Running));
+        machine.add_transition(Transition {
+            from: TestState::Running,
+            to: TestState::Done,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(move || {
+                let result = if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    TransitionResult::Retry {
+                        feedback: "busy".to_string(),
+                        current_state: TestState::Running,
+                    }
+                } else {
+                    TransitionResult::Success(TestState::Done)
+                };
+                pure(result).boxed()
+            }),
+        });
+        machine
+    }
+
+    #[tokio::test]
+    async fn replay_reproduces_recorded_run_without_actions() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut machine = flaky_machine(Arc::clone(&runs));
+        let mut trace = Trace::new();
+        while !machine.is_final() {
+            machine.step_recorded(&(), &mut trace).await.unwrap();
+        }
+        assert_eq!(trace.steps.len(), 3);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        let json = serde_json::to_string(&trace).unwrap();
+        let trace: Trace<TestState> = serde_json::from_str(&json).unwrap();
+        let replay_runs = Arc::new(AtomicUsize::new(0));
+        let mut replayed = flaky_machine(Arc::clone(&replay_runs));
+        replayed.replay(&trace).unwrap();
+
+        assert_eq!(replay_runs.load(Ordering::SeqCst), 0);
+        assert_eq!(replayed.current_state(), &TestState::Done);
+        let timestamps = |m: &StateMachine<TestState, ()>| {
+            m.history()
+                .transitions()
+                .iter()
+                .map(|t| (t.to.clone(), t.attempt, t.timestamp))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(timestamps(&replayed), timestamps(&machine));
+    }
+
+    #[tokio::test]
+    async fn failed_steps_are_recorded() {
+        let mut machine = StateMachine::<TestState, ()>::new(TestState::Queued);
+        let mut trace = Trace::new();
+
+        assert!(machine.step_recorded(&(), &mut trace).await.is_err());
+
+        assert_eq!(trace.steps[0].transition, None);
+        assert!(matches!(
+            trace.steps[0].outcome,
+            RecordedOutcome::Failed { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn waiting_and_paused_steps_record_no_transition() {
+        let mut machine = flaky_machine(Arc::new(AtomicUsize::new(0)));
+        let mut trace = Trace::new();
+        machine.await_signal(&TestState::Queued, crate::effects::SignalWait::new("go"));
+        machine.step_recorded(&(), &mut trace).await.unwrap();
+        machine.pause("maintenance");
+        machine.step_recorded(&(), &mut trace).await.unwrap();
+
+        assert_eq!(trace.steps.len(), 2);
+        assert!(trace.steps.iter().all(|step| step.transition.is_none()));
+        assert!(flaky_machine(Arc::new(AtomicUsize::new(0)))
+            .replay(&trace)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn replay_detects_changed_definition() {
+        let mut machine = flaky_machine(Arc::new(AtomicUsize::new(0)));
+        let mut trace = Trace::new();
+        machine.step_recorded(&(), &mut trace).await.unwrap();
+
+        let mut changed = StateMachine::<TestState, ()>::new(TestState::Queued);
+        changed.add_transition(simple_transition(TestState::Running, TestState::Done));
+        changed.add_transition(simple_transition(TestState::Queued, TestState::Running));
+
+        assert_eq!(
+            changed.replay(&trace),
+            Err(ReplayError::TransitionMismatch {
+                step: 0,
+                expected: Some(0),
+                actual: Some(1),
+            })
+        );
+    }
+}
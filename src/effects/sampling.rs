@@ -0,0 +1,249 @@
+//! Sampling of telemetry from chatty machines.
+//!
+//! A machine retrying in a tight loop can emit far more events than a
+//! telemetry pipeline wants to ingest. A [`TelemetrySampler`] sits in front
+//! of the host's metrics or event log and decides per event whether to
+//! forward it: only every Nth retry of a machine, and at most a fixed
+//! number of events per machine and minute. Dropped events are counted so
+//! the totals downstream can still be reconciled.
+
+use crate::core::State;
+use crate::effects::machine::StepResult;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+
+/// Kind of a telemetry event, as far as sampling is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TelemetryKind {
+    /// The machine changed state
+    Transition,
+    /// A transition attempt failed and will be retried
+    Retry,
+    /// A transition aborted into an error state
+    Abort,
+    /// The machine is paused or waits for a signal
+    Wait,
+}
+
+impl TelemetryKind {
+    /// Kind of the event reporting `result` (pure)
+    pub fn of<S: State>(result: &StepResult<S>) -> Self {
+        match result {
+            StepResult::Transitioned(_) => Self::Transition,
+            StepResult::Retry { .. } => Self::Retry,
+            StepResult::Aborted { .. } => Self::Abort,
+            StepResult::Paused { .. } | StepResult::AwaitingSignal { .. } => Self::Wait,
+        }
+    }
+}
+
+/// Events a [`TelemetrySampler`] dropped, by cause.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DroppedEvents {
+    /// Retries skipped by retry sampling
+    pub retries: u64,
+    /// Events over the per-minute cap
+    pub rate_limited: u64,
+}
+
+impl DroppedEvents {
+    /// Dropped events of any cause (pure)
+    pub fn total(&self) -> u64 {
+        self.retries + self.rate_limited
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MachineWindow {
+    started_at: DateTime<Utc>,
+    admitted: u32,
+    retries: u64,
+    dropped: DroppedEvents,
+}
+
+/// Decides which telemetry events of which machines to forward.
+///
+/// Machines are told apart by a caller-chosen key, e.g. a checkpoint or
+/// workflow id. The per-minute cap uses fixed windows starting at a
+/// machine's first event.
+///
+/// # Example
+///
+/// ```rust
+/// use mindset::effects::{TelemetryKind, TelemetrySampler};
+/// use chrono::Utc;
+///
+/// let mut sampler = TelemetrySampler::new().retry_every(10);
+/// let now = Utc::now();
+/// let forwarded = (0..25)
+///     .filter(|_| sampler.admit("order-7", TelemetryKind::Retry, now))
+///     .count();
+///
+/// assert_eq!(forwarded, 3);
+/// assert_eq!(sampler.dropped("order-7").retries, 22);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TelemetrySampler {
+    retry_every: u64,
+    max_per_minute: Option<u32>,
+    machines: HashMap<String, MachineWindow>,
+}
+
+impl Default for TelemetrySampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetrySampler {
+    /// Create a sampler forwarding every event
+    pub fn new() -> Self {
+        Self {
+            retry_every: 1,
+            max_per_minute: None,
+            machines: HashMap::new(),
+        }
+    }
+
+    /// Forward only every `n`th retry of a machine, starting with the
+    /// first. Zero is treated as one.
+    pub fn retry_every(mut self, n: u64) -> Self {
+        self.retry_every = n.max(1);
+        self
+    }
+
+    /// Forward at most `max` events per machine and minute
+    pub fn max_per_minute(mut self, max: u32) -> Self {
+        self.max_per_minute = Some(max);
+        self
+    }
+
+    /// Whether to forward an event of `kind` from `machine` at `now`,
+    /// counting it as dropped otherwise.
+    pub fn admit(&mut self, machine: &str, kind: TelemetryKind, now: DateTime<Utc>) -> bool {
+        let window = self
+            .machines
+            .entry(machine.to_string())
+            .or_insert_with(|| MachineWindow {
+                started_at: now,
+                admitted: 0,
+                retries: 0,
+                dropped: DroppedEvents::default(),
+            });
+
+        if kind == TelemetryKind::Retry {
+            window.retries += 1;
+            if (window.retries - 1) % self.retry_every != 0 {
+                window.dropped.retries += 1;
+                return false;
+            }
+        }
+
+        if now - window.started_at >= TimeDelta::minutes(1) {
+            window.started_at = now;
+            window.admitted = 0;
+        }
+        if self
+            .max_per_minute
+            .is_some_and(|max| window.admitted >= max)
+        {
+            window.dropped.rate_limited += 1;
+            return false;
+        }
+        window.admitted += 1;
+        true
+    }
+
+    /// Whether to forward the event reporting step `result` of `machine`
+    /// at `now`.
+    pub fn admit_step<S: State>(
+        &mut self,
+        machine: &str,
+        result: &StepResult<S>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.admit(machine, TelemetryKind::of(result), now)
+    }
+
+    /// Events dropped for `machine` so far (pure)
+    pub fn dropped(&self, machine: &str) -> DroppedEvents {
+        self.machines
+            .get(machine)
+            .map(|window| window.dropped)
+            .unwrap_or_default()
+    }
+
+    /// Events dropped across all machines so far (pure)
+    pub fn total_dropped(&self) -> DroppedEvents {
+        self.machines
+            .values()
+            .fold(DroppedEvents::default(), |total, window| DroppedEvents {
+                retries: total.retries + window.dropped.retries,
+                rate_limited: total.rate_limited + window.dropped.rate_limited,
+            })
+    }
+
+    /// Stop tracking `machine`, e.g. once it reached a final state,
+    /// returning the events dropped for it
+    pub fn forget(&mut self, machine: &str) -> DroppedEvents {
+        self.machines
+            .remove(machine)
+            .map(|window| window.dropped)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_enum;
+
+    state_enum! {
+        enum Feed {
+            Polling,
+            Done,
+        }
+        final: [Done]
+    }
+
+    #[test]
+    fn events_over_the_cap_are_dropped_until_the_next_minute() {
+        let mut sampler = TelemetrySampler::new().max_per_minute(2);
+        let start = Utc::now();
+        let step = StepResult::Transitioned(Feed::Done);
+
+        let admitted: Vec<_> = (0..3)
+            .map(|_| sampler.admit_step("a", &step, start))
+            .collect();
+        assert_eq!(admitted, vec![true, true, false]);
+        assert!(sampler.admit("b", TelemetryKind::Abort, start));
+        assert!(sampler.admit("a", TelemetryKind::Wait, start + TimeDelta::minutes(1)));
+
+        assert_eq!(sampler.dropped("a").rate_limited, 1);
+        assert_eq!(sampler.total_dropped().total(), 1);
+        assert_eq!(sampler.forget("a").total(), 1);
+        assert_eq!(sampler.total_dropped().total(), 0);
+    }
+
+    #[test]
+    fn sampled_out_retries_do_not_count_against_the_cap() {
+        let mut sampler = TelemetrySampler::new().retry_every(2).max_per_minute(2);
+        let now = Utc::now();
+        let retry = StepResult::<Feed>::Retry {
+            feedback: "timeout".to_string(),
+            attempts: 1,
+        };
+
+        let admitted: Vec<_> = (0..5)
+            .map(|_| sampler.admit_step("a", &retry, now))
+            .collect();
+        assert_eq!(admitted, vec![true, false, true, false, false]);
+        assert_eq!(
+            sampler.dropped("a"),
+            DroppedEvents {
+                retries: 2,
+                rate_limited: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! States that wait for an external signal.
+//!
+//! Some steps cannot proceed until something outside the machine happens:
+//! a human approval, a webhook, a payment confirmation. Registering a
+//! [`SignalWait`] for such a state makes `step()` return
+//! [`StepResult::AwaitingSignal`] instead of running an action until a
+//! matching signal has been delivered with [`StateMachine::signal`].
+//!
+//! Delivered signals are kept in the machine's metadata, so they survive
+//! checkpoints and can be delivered while nothing is driving the machine.
+//! A signal is consumed when the machine leaves the state it unblocked.
+//!
+//! [`StepResult::AwaitingSignal`]: crate::effects::StepResult::AwaitingSignal
+//! [`StateMachine::signal`]: crate::effects::StateMachine::signal
+
+use crate::core::State;
+use serde_json::Value;
+use std::sync::Arc;
+
+type AcceptFn<S> = Arc<dyn Fn(&S, &Value) -> bool + Send + Sync>;
+
+/// The signal a state waits for, and which payloads unblock it.
+pub struct SignalWait<S: State> {
+    signal: String,
+    accept: Option<AcceptFn<S>>,
+}
+
+impl<S: State> Clone for SignalWait<S> {
+    fn clone(&self) -> Self {
+        Self {
+            signal: self.signal.clone(),
+            accept: self.accept.clone(),
+        }
+    }
+}
+
+impl<S: State> SignalWait<S> {
+    /// Wait for any signal named `signal`
+    pub fn new(signal: impl Into<String>) -> Self {
+        Self {
+            signal: signal.into(),
+            accept: None,
+        }
+    }
+
+    /// Only accept signals whose payload satisfies `predicate`.
+    ///
+    /// Rejected signals stay pending, so another waiting state may still
+    /// accept them.
+    pub fn when<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&S, &Value) -> bool + Send + Sync + 'static,
+    {
+        self.accept = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Name of the awaited signal
+    pub fn signal(&self) -> &str {
+        &self.signal
+    }
+
+    /// Check whether a signal unblocks `state` (pure)
+    pub fn accepts(&self, state: &S, name: &str, payload: &Value) -> bool {
+        name == self.signal && self.accept.as_ref().is_none_or(|f| f(state, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use crate::effects::{StateMachine, StepResult};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use stillwater::effect::Effect;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Expense {
+        Submitted,
+        Approved,
+        Paid,
+    }
+
+    impl State for Expense {
+        fn name(&self) -> &str {
+            match self {
+                Self::Submitted => "Submitted",
+                Self::Approved => "Approved",
+                Self::Paid => "Paid",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Paid)
+        }
+    }
+
+    fn machine() -> StateMachine<Expense, ()> {
+        let mut machine = StateMachine::new(Expense::Submitted);
+        machine.add_transition(simple_transition(Expense::Submitted, Expense::Approved));
+        machine.add_transition(simple_transition(Expense::Approved, Expense::Paid));
+        machine.await_signal(
+            &Expense::Submitted,
+            SignalWait::new("review").when(|_, payload| payload["approved"] == json!(true)),
+        );
+        machine
+    }
+
+    async fn drive(machine: &mut StateMachine<Expense, ()>) -> StepResult<Expense> {
+        let (from, result, attempt) = machine.step().run(&()).await.unwrap();
+        machine.apply_result(from, result.clone(), attempt);
+        result
+    }
+
+    #[tokio::test]
+    async fn step_waits_until_signal_is_accepted() {
+        let mut machine = machine();
+        assert_eq!(machine.awaited_signal(), Some("review"));
+        assert!(machine.preview().is_none());
+        assert_eq!(
+            drive(&mut machine).await,
+            StepResult::AwaitingSignal {
+                signal: "review".to_string()
+            }
+        );
+
+        machine.signal("review", json!({ "approved": false }));
+        assert_eq!(machine.awaited_signal(), Some("review"));
+
+        machine.signal("review", json!({ "approved": true }));
+        assert_eq!(
+            machine.received_signal().map(|s| &s.payload),
+            Some(&json!({ "approved": true }))
+        );
+        assert_eq!(
+            drive(&mut machine).await,
+            StepResult::Transitioned(Expense::Approved)
+        );
+
+        // The accepted signal is consumed; the rejected one stays pending
+        assert_eq!(machine.pending_signals().len(), 1);
+        assert_eq!(
+            machine.pending_signals()[0].payload["approved"],
+            json!(false)
+        );
+    }
+
+    #[tokio::test]
+    async fn signals_survive_checkpoints() {
+        let mut machine = machine();
+        machine.signal("review", json!({ "approved": true }));
+
+        let mut restored =
+            StateMachine::from_json(&machine.to_json().unwrap(), machine.transitions().to_vec())
+                .unwrap();
+        restored.await_signal(&Expense::Submitted, SignalWait::new("review"));
+
+        assert_eq!(restored.pending_signals().len(), 1);
+        assert_eq!(
+            drive(&mut restored).await,
+            StepResult::Transitioned(Expense::Approved)
+        );
+        assert!(restored.pending_signals().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+//! Spawning child workflows from actions.
+//!
+//! An action can ask the host to start another machine instance through
+//! the [`Spawner`] capability of its environment, e.g. one child per order
+//! line. The host (typically whatever drives and stores machines) owns the
+//! definition lookup and the child's lifecycle.
+//!
+//! Parents wait for children with the signal machinery: a state that waits
+//! for [`SignalWait::child_completed`] is unblocked once the host reports
+//! the child's final state via [`StateMachine::notify_child_completed`].
+
+use crate::core::State;
+use crate::effects::machine::StateMachine;
+use crate::effects::signal::SignalWait;
+use crate::effects::transition::TransitionError;
+use serde_json::{json, Value};
+use stillwater::prelude::*;
+
+/// Name of the signal delivered when a child workflow completes.
+pub const CHILD_COMPLETED: &str = "child_completed";
+
+/// Host service that starts child workflows.
+pub trait Spawner: Send + Sync {
+    /// Start a machine of `definition` with `context`, returning the id
+    /// of the new instance or why it could not be started.
+    fn spawn(&self, definition: &str, context: Value) -> Result<String, String>;
+}
+
+/// Environment capability exposing a [`Spawner`].
+pub trait HasSpawner {
+    /// The service actions spawn child workflows with
+    fn spawner(&self) -> &dyn Spawner;
+}
+
+/// Effect spawning a child workflow, yielding the child's id.
+///
+/// Fails with [`TransitionError::SpawnFailed`] if the host refuses.
+pub fn spawn_child<Env>(
+    definition: impl Into<String>,
+    context: Value,
+) -> impl Effect<Output = String, Error = TransitionError, Env = Env>
+where
+    Env: HasSpawner + Clone + Send + Sync + 'static,
+{
+    let definition = definition.into();
+    from_fn(move |env: &Env| {
+        env.spawner()
+            .spawn(&definition, context)
+            .map_err(|reason| TransitionError::SpawnFailed { definition, reason })
+    })
+}
+
+impl<S: State> SignalWait<S> {
+    /// Wait for the completion of the child whose id `child_id` extracts
+    /// from the waiting state.
+    pub fn child_completed<F>(child_id: F) -> Self
+    where
+        F: Fn(&S) -> Option<String> + Send + Sync + 'static,
+    {
+        Self::new(CHILD_COMPLETED).when(move |state, payload| {
+            child_id(state).is_some_and(|id| payload["child"] == Value::String(id))
+        })
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Report that child `child_id` completed in `final_state`.
+    ///
+    /// Delivers a [`CHILD_COMPLETED`] signal with payload
+    /// `{"child", "state", "error"}`, where `state` is the child's state
+    /// name and `error` whether it ended in an error state.
+    pub fn notify_child_completed<C: State>(&mut self, child_id: &str, final_state: &C) {
+        self.signal(
+            CHILD_COMPLETED,
+            json!({
+                "child": child_id,
+                "state": final_state.name(),
+                "error": final_state.is_error(),
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StepResult, Transition, TransitionResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Order {
+        Received,
+        Fulfilling { child: String },
+        Shipped,
+    }
+
+    impl State for Order {
+        fn name(&self) -> &str {
+            match self {
+                Self::Received => "Received",
+                Self::Fulfilling { .. } => "Fulfilling",
+                Self::Shipped => "Shipped",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Shipped)
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingSpawner {
+        spawned: Mutex<Vec<(String, Value)>>,
+    }
+
+    impl Spawner for RecordingSpawner {
+        fn spawn(&self, definition: &str, context: Value) -> Result<String, String> {
+            if definition == "unknown" {
+                return Err("no such definition".to_string());
+            }
+            let mut spawned = self.spawned.lock().unwrap();
+            spawned.push((definition.to_string(), context));
+            Ok(format!("child-{}", spawned.len()))
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct TestEnv {
+        spawner: Arc<RecordingSpawner>,
+    }
+
+    impl HasSpawner for TestEnv {
+        fn spawner(&self) -> &dyn Spawner {
+            self.spawner.as_ref()
+        }
+    }
+
+    fn machine(definition: &'static str) -> StateMachine<Order, TestEnv> {
+        let mut machine = StateMachine::new(Order::Received);
+        machine.add_transition(Transition {
+            from: Order::Received,
+            to: Order::Fulfilling {
+                child: String::new(),
+            },
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(move || {
+                spawn_child(definition, json!({ "line": 1 }))
+                    .map(|child| TransitionResult::Success(Order::Fulfilling { child }))
+                    .boxed()
+            }),
+        });
+        machine.add_transition(crate::builder::simple_transition(
+            Order::Fulfilling {
+                child: "child-1".to_string(),
+            },
+            Order::Shipped,
+        ));
+        machine.await_signal(
+            &Order::Fulfilling {
+                child: String::new(),
+            },
+            SignalWait::child_completed(|state| match state {
+                Order::Fulfilling { child } => Some(child.clone()),
+                _ => None,
+            }),
+        );
+        machine
+    }
+
+    async fn drive(machine: &mut StateMachine<Order, TestEnv>, env: &TestEnv) -> StepResult<Order> {
+        let (from, result, attempt) = machine.step().run(env).await.unwrap();
+        machine.apply_result(from, result.clone(), attempt);
+        result
+    }
+
+    #[tokio::test]
+    async fn parent_waits_for_spawned_child() {
+        let env = TestEnv::default();
+        let mut machine = machine("fulfilment");
+
+        drive(&mut machine, &env).await;
+        assert_eq!(
+            env.spawner.spawned.lock().unwrap().as_slice(),
+            &[("fulfilment".to_string(), json!({ "line": 1 }))]
+        );
+        assert_eq!(machine.awaited_signal(), Some(CHILD_COMPLETED));
+
+        machine.notify_child_completed("child-2", &Order::Shipped);
+        assert_eq!(machine.awaited_signal(), Some(CHILD_COMPLETED));
+
+        machine.notify_child_completed("child-1", &Order::Shipped);
+        assert_eq!(
+            drive(&mut machine, &env).await,
+            StepResult::Transitioned(Order::Shipped)
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_spawn_fails_the_step() {
+        let machine = machine("unknown");
+        let result = machine.step().run(&TestEnv::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(TransitionError::SpawnFailed { ref definition, .. }) if definition == "unknown"
+        ));
+    }
+}
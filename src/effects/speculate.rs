@@ -0,0 +1,165 @@
+//! What-if execution on a sandboxed copy of a machine.
+//!
+//! [`StateMachine::speculate`] returns a [`Speculation`]: a detached copy
+//! of the machine whose steps take their action outcomes from the caller
+//! instead of running actions. Planning tools and tests can explore
+//! questions like "what happens if this step fails twice and then
+//! succeeds" without performing side effects or touching the real
+//! instance and its inspectors.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepResult};
+use crate::effects::transition::{TransitionError, TransitionResult};
+
+/// Sandboxed copy of a machine driven with stubbed action outcomes.
+pub struct Speculation<S: State + 'static, Env: Clone + Send + Sync + 'static> {
+    machine: StateMachine<S, Env>,
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Start a speculation from the machine's current position (pure).
+    ///
+    /// The copy shares transitions and invariants but not inspectors, so
+    /// nothing observing this machine sees the speculative steps.
+    pub fn speculate(&self) -> Speculation<S, Env> {
+        Speculation {
+            machine: self.detached(),
+        }
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> Speculation<S, Env> {
+    /// Take one step as if the selected transition's action returned
+    /// `outcome`, and apply it.
+    ///
+    /// Pauses and signal waits are honoured like in `step()`: the outcome
+    /// is discarded and the machine does not move. Fails with
+    /// [`TransitionError::NoTransition`] if no transition can execute.
+    pub fn step_with(
+        &mut self,
+        outcome: TransitionResult<S>,
+    ) -> Result<StepResult<S>, TransitionError> {
+        let machine = &mut self.machine;
+        let from = machine.current_state().clone();
+        let attempt_count = machine.attempt_count();
+        let result = if let Some(pause) = machine.pause_info() {
+            StepResult::Paused {
+                reason: pause.reason.clone(),
+            }
+        } else if let Some(signal) = machine.awaited_signal() {
+            StepResult::AwaitingSignal {
+                signal: signal.to_string(),
+            }
+        } else if machine.preview().is_some() {
+            StepResult::from_action(outcome, attempt_count)
+        } else {
+            return Err(machine.no_transition(None));
+        };
+        machine.apply_result(from, result.clone(), attempt_count);
+        Ok(result)
+    }
+
+    /// Take one step whose action succeeds into the transition's target
+    pub fn succeed(&mut self) -> Result<StepResult<S>, TransitionError> {
+        let to = self
+            .machine
+            .preview()
+            .map(|context| context.to)
+            .unwrap_or_else(|| self.machine.current_state().clone());
+        self.step_with(TransitionResult::Success(to))
+    }
+
+    /// Take one step whose action asks for a retry with `feedback`
+    pub fn retry(&mut self, feedback: impl Into<String>) -> Result<StepResult<S>, TransitionError> {
+        let current_state = self.machine.current_state().clone();
+        self.step_with(TransitionResult::Retry {
+            feedback: feedback.into(),
+            current_state,
+        })
+    }
+
+    /// The speculative machine, e.g. to inspect its state or history
+    pub fn machine(&self) -> &StateMachine<S, Env> {
+        &self.machine
+    }
+
+    /// Take the speculative machine out of the sandbox
+    pub fn into_machine(self) -> StateMachine<S, Env> {
+        self.machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+
+    #[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+    enum Payment {
+        Pending,
+        Captured,
+        Settled,
+        Declined,
+    }
+
+    impl State for Payment {
+        fn name(&self) -> &str {
+            match self {
+                Self::Pending => "Pending",
+                Self::Captured => "Captured",
+                Self::Settled => "Settled",
+                Self::Declined => "Declined",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Settled | Self::Declined)
+        }
+
+        fn is_error(&self) -> bool {
+            matches!(self, Self::Declined)
+        }
+    }
+
+    fn machine() -> StateMachine<Payment, ()> {
+        let mut machine = StateMachine::new(Payment::Pending);
+        machine.add_transition(simple_transition(Payment::Pending, Payment::Captured));
+        machine.add_transition(simple_transition(Payment::Captured, Payment::Settled));
+        machine
+    }
+
+    #[test]
+    fn scripted_outcomes_leave_the_real_machine_untouched() {
+        let mut machine = machine();
+        let inspector = machine.inspector();
+        let mut speculation = machine.speculate();
+
+        speculation.retry("gateway timeout").unwrap();
+        speculation.retry("gateway timeout").unwrap();
+        assert_eq!(
+            speculation.succeed().unwrap(),
+            StepResult::Transitioned(Payment::Captured)
+        );
+        assert_eq!(speculation.machine().history().transitions()[0].attempt, 2);
+
+        assert_eq!(machine.current_state(), &Payment::Pending);
+        assert_eq!(inspector.current_state(), Payment::Pending);
+    }
+
+    #[test]
+    fn aborts_and_dead_ends_are_explored_without_actions() {
+        let mut speculation = machine().speculate();
+
+        let result = speculation
+            .step_with(TransitionResult::Abort {
+                reason: "card declined".to_string(),
+                error_state: Payment::Declined,
+            })
+            .unwrap();
+        assert!(matches!(result, StepResult::Aborted { .. }));
+        assert!(matches!(
+            speculation.succeed(),
+            Err(TransitionError::NoTransition { ref from, .. }) if from == "Declined"
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+//! Transitions with unboxed actions.
+//!
+//! A [`Transition`](crate::effects::Transition) stores its action as a
+//! factory returning a `BoxedEffect`, so every step allocates the action's
+//! effect on the heap. When a machine's transition set is known at compile
+//! time, its actions can instead be variants of one type implementing
+//! [`Effect`], typically an enum with a variant per action. A
+//! [`StaticTransition`] holds such a value, and
+//! [`StateMachine::step_static`] clones and runs it without boxing.
+//!
+//! Static transitions are kept apart from the machine's own transitions,
+//! since the machine is not generic over an action type; hosts hold them
+//! next to the machine like event transitions. `benches/step.rs` compares
+//! the two paths.
+
+use crate::core::State;
+use crate::effects::machine::{StateMachine, StepEffect, StepResult};
+use crate::effects::transition::{CandidateCheck, TransitionError, TransitionResult};
+use std::marker::PhantomData;
+use stillwater::effect::Effect;
+
+/// A transition whose action is an effect value of type `E`.
+pub struct StaticTransition<S: State, Env, E> {
+    /// Source state
+    pub from: S,
+    /// Target state
+    pub to: S,
+    guard: Option<fn(&S) -> bool>,
+    action: E,
+    _env: PhantomData<fn(&Env)>,
+}
+
+impl<S: State, Env, E: Clone> Clone for StaticTransition<S, Env, E> {
+    fn clone(&self) -> Self {
+        Self {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            guard: self.guard,
+            action: self.action.clone(),
+            _env: PhantomData,
+        }
+    }
+}
+
+impl<S, Env, E> StaticTransition<S, Env, E>
+where
+    S: State,
+    Env: Clone + Send + Sync + 'static,
+    E: Effect<Output = TransitionResult<S>, Error = TransitionError, Env = Env> + Clone,
+{
+    /// Move from `from` to `to` by running a clone of `action`
+    pub fn new(from: S, to: S, action: E) -> Self {
+        Self {
+            from,
+            to,
+            guard: None,
+            action,
+            _env: PhantomData,
+        }
+    }
+
+    /// Only take the transition in states for which `guard` holds
+    pub fn when(mut self, guard: fn(&S) -> bool) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Check whether the transition can be taken in `state` (pure)
+    pub fn can_execute(&self, state: &S) -> bool {
+        &self.from == state && self.guard.is_none_or(|guard| guard(state))
+    }
+
+    /// How the transition fares in `state` during selection (pure)
+    fn check(&self, state: &S) -> CandidateCheck {
+        let state_matched = &self.from == state;
+        CandidateCheck {
+            to: self.to.display_name().into_owned(),
+            state_matched,
+            guard_passed: self
+                .guard
+                .filter(|_| state_matched)
+                .map(|guard| guard(state)),
+            guard_label: None,
+            disabled_by_flag: None,
+        }
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Execute one step with the first of `transitions` that can execute,
+    /// running its action without boxing it.
+    ///
+    /// Behaves like `step()` otherwise: pauses and signal waits are
+    /// reported without running anything, entry and exit hooks run, and
+    /// the result is applied with `apply_result()`. Fails with
+    /// [`TransitionError::UnexpectedTarget`] if the action succeeds into a
+    /// state other than the transition's `to`.
+    pub fn step_static<E>(
+        &self,
+        transitions: &[StaticTransition<S, Env, E>],
+    ) -> impl Effect<Output = (S, StepResult<S>, usize), Error = TransitionError, Env = Env>
+    where
+        E: Effect<Output = TransitionResult<S>, Error = TransitionError, Env = Env> + Clone,
+    {
+        let from = self.current_state().clone();
+        if let Some(pause) = self.pause_info() {
+            return StepEffect::Ready((
+                from,
+                StepResult::Paused {
+                    reason: pause.reason.clone(),
+                },
+                self.attempt_count(),
+            ));
+        }
+        if let Some(signal) = self.awaited_signal() {
+            return StepEffect::Ready((
+                from,
+                StepResult::AwaitingSignal {
+                    signal: signal.to_string(),
+                },
+                self.attempt_count(),
+            ));
+        }
+        match transitions.iter().find(|t| t.can_execute(&from)) {
+            Some(transition) => StepEffect::Run {
+                action: transition.action.clone(),
+                from,
+                attempt_count: self.attempt_count(),
+                target: Some(transition.to.clone()),
+                hooks: self.state_hooks(),
+            },
+            None => StepEffect::Failed(TransitionError::NoTransition {
+                candidates: transitions
+                    .iter()
+                    .filter(|t| t.from.name() == from.name())
+                    .map(|t| t.check(&from))
+                    .collect(),
+                from: from.display_name().into_owned(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_enum;
+
+    state_enum! {
+        enum Job {
+            Queued,
+            Running,
+            Done,
+        }
+        final: [Done]
+    }
+
+    #[derive(Clone)]
+    enum JobAction {
+        Start,
+        Finish { items: usize },
+    }
+
+    impl Effect for JobAction {
+        type Output = TransitionResult<Job>;
+        type Error = TransitionError;
+        type Env = ();
+
+        async fn run(self, _env: &()) -> Result<TransitionResult<Job>, TransitionError> {
+            match self {
+                Self::Start => Ok(TransitionResult::Success(Job::Running)),
+                Self::Finish { items: 0 } => Ok(TransitionResult::Retry {
+                    feedback: "nothing to do".to_string(),
+                    current_state: Job::Running,
+                }),
+                Self::Finish { .. } => Ok(TransitionResult::Success(Job::Done)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn static_transitions_drive_the_machine() {
+        let transitions = [
+            StaticTransition::new(Job::Queued, Job::Running, JobAction::Start),
+            StaticTransition::new(Job::Running, Job::Done, JobAction::Finish { items: 0 })
+                .when(|_| false),
+            StaticTransition::new(Job::Running, Job::Done, JobAction::Finish { items: 3 }),
+        ];
+        let mut machine = StateMachine::<Job, ()>::new(Job::Queued);
+
+        while !machine.is_final() {
+            let (from, result, attempt) = machine.step_static(&transitions).run(&()).await.unwrap();
+            machine.apply_result(from, result, attempt);
+        }
+
+        assert_eq!(machine.history().transitions().len(), 2);
+        let result = machine.step_static(&transitions).run(&()).await;
+        assert!(matches!(
+            result,
+            Err(TransitionError::NoTransition { ref candidates, .. }) if candidates.is_empty()
+        ));
+    }
+
+    #[tokio::test]
+    async fn static_actions_must_reach_the_declared_target() {
+        let machine = StateMachine::<Job, ()>::new(Job::Queued);
+        let wrong = [StaticTransition::new(
+            Job::Queued,
+            Job::Done,
+            JobAction::Start,
+        )];
+
+        let result = machine.step_static(&wrong).run(&()).await;
+        assert!(matches!(
+            result,
+            Err(TransitionError::UnexpectedTarget { ref expected, ref actual })
+                if expected == "Done" && actual == "Running"
+        ));
+    }
+}
@@ -0,0 +1,497 @@
+//! Durable timers backed by an external scheduler.
+//!
+//! In-process timers die with the process. A [`TimerService`] hands timers
+//! to something that outlives it instead: a database job table, a cloud
+//! scheduler. The machine keeps the handle of every pending timer in its
+//! metadata, so checkpoints record which timers are outstanding.
+//!
+//! When the scheduler calls back, the host loads the machine and calls
+//! [`StateMachine::fire_timer`] with the handle; the timer is delivered as
+//! a signal of the timer's name, so states wait for it with a
+//! `SignalWait` like for any other external event. After a restart,
+//! [`StateMachine::reconcile_timers`] fires timers whose callback was lost
+//! and reschedules those the service forgot.
+//!
+//! Hosts without an external scheduler can use a [`TimerQueue`], an
+//! in-process service backed by a binary heap: instead of polling every
+//! machine on a fixed tick, they sleep until [`TimerQueue::next_due`] and
+//! fire what [`TimerQueue::pop_due`] returns on the machines it names.
+
+use crate::checkpoint::{IdGenerator, PendingTimer, UuidIds};
+use crate::core::State;
+use crate::effects::machine::StateMachine;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// External scheduler that calls back when timers are due.
+pub trait TimerService: Send + Sync {
+    /// Schedule timer `name` for `due_at`, returning a handle the
+    /// callback identifies it with
+    fn schedule(&self, name: &str, due_at: DateTime<Utc>) -> Result<String, String>;
+
+    /// Cancel the timer with `handle`
+    fn cancel(&self, handle: &str) -> Result<(), String>;
+
+    /// Whether the timer with `handle` is still scheduled
+    fn is_scheduled(&self, handle: &str) -> bool;
+}
+
+/// A timer service call failed.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("Timer '{timer}' failed: {reason}")]
+pub struct TimerError {
+    /// Name of the timer
+    pub timer: String,
+    /// Reason reported by the timer service
+    pub reason: String,
+}
+
+/// What [`StateMachine::reconcile_timers`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimerReconciliation {
+    /// Overdue timers unknown to the service, delivered as signals
+    pub fired: Vec<String>,
+    /// Timers unknown to the service but not due yet, scheduled again
+    pub rescheduled: Vec<String>,
+}
+
+/// A timer that came due in a [`TimerQueue`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DueTimer {
+    /// Key of the machine that scheduled the timer
+    pub machine: String,
+    /// Name of the timer
+    pub name: String,
+    /// Handle to pass to the machine's [`StateMachine::fire_timer`]
+    pub handle: String,
+    /// When the timer was due
+    pub due_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Scheduled {
+    machine: String,
+    name: String,
+    due_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct Schedule {
+    /// Due times and handles, earliest first; cancelled handles are
+    /// skipped when they reach the top
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, String)>>,
+    live: HashMap<String, Scheduled>,
+}
+
+impl Schedule {
+    /// Drop cancelled entries from the top of the heap
+    fn prune(&mut self) {
+        while let Some(Reverse((_, handle))) = self.heap.peek() {
+            if self.live.contains_key(handle) {
+                break;
+            }
+            self.heap.pop();
+        }
+    }
+}
+
+/// In-process timer service keeping due timers in a binary heap.
+///
+/// Scheduling and popping are O(log n) in the number of timers, so one
+/// queue can serve the timers of many machines. Machines schedule through
+/// [`TimerQueue::for_machine`], so each [`DueTimer`] names the machine to
+/// fire it on. Handles come from an [`IdGenerator`], random UUIDs by
+/// default, so they stay unique across queues, restarts and hosts.
+///
+/// Timers are lost with the process; the handles recorded in checkpoints
+/// let [`StateMachine::reconcile_timers`] restore them into a fresh queue.
+#[derive(Debug)]
+pub struct TimerQueue {
+    schedule: Mutex<Schedule>,
+    ids: Box<dyn IdGenerator>,
+}
+
+impl Default for TimerQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimerQueue {
+    /// Create an empty queue with UUID handles
+    pub fn new() -> Self {
+        Self::with_ids(UuidIds)
+    }
+
+    /// Create an empty queue taking handles from `ids`
+    pub fn with_ids(ids: impl IdGenerator + 'static) -> Self {
+        Self {
+            schedule: Mutex::default(),
+            ids: Box::new(ids),
+        }
+    }
+
+    /// Timer service scheduling timers for the machine with key `machine`,
+    /// e.g. its checkpoint or workflow id
+    pub fn for_machine(&self, machine: impl Into<String>) -> MachineTimers<'_> {
+        MachineTimers {
+            queue: self,
+            machine: machine.into(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Schedule> {
+        self.schedule.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of scheduled timers
+    pub fn len(&self) -> usize {
+        self.lock().live.len()
+    }
+
+    /// Whether no timer is scheduled
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// When the earliest scheduled timer is due, i.e. how long the host
+    /// can sleep
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        let mut schedule = self.lock();
+        schedule.prune();
+        schedule.heap.peek().map(|Reverse((due_at, _))| *due_at)
+    }
+
+    /// Remove and return the timers due at `now`, earliest first
+    pub fn pop_due(&self, now: DateTime<Utc>) -> Vec<DueTimer> {
+        let mut schedule = self.lock();
+        let mut due = Vec::new();
+        schedule.prune();
+        while schedule
+            .heap
+            .peek()
+            .is_some_and(|Reverse((due_at, _))| *due_at <= now)
+        {
+            let Some(Reverse((_, handle))) = schedule.heap.pop() else {
+                break;
+            };
+            if let Some(timer) = schedule.live.remove(&handle) {
+                due.push(DueTimer {
+                    machine: timer.machine,
+                    name: timer.name,
+                    handle,
+                    due_at: timer.due_at,
+                });
+            }
+            schedule.prune();
+        }
+        due
+    }
+}
+
+/// [`TimerService`] view of a [`TimerQueue`] for one machine.
+#[derive(Debug)]
+pub struct MachineTimers<'a> {
+    queue: &'a TimerQueue,
+    machine: String,
+}
+
+impl TimerService for MachineTimers<'_> {
+    fn schedule(&self, name: &str, due_at: DateTime<Utc>) -> Result<String, String> {
+        let handle = self.queue.ids.generate();
+        let mut schedule = self.queue.lock();
+        schedule.live.insert(
+            handle.clone(),
+            Scheduled {
+                machine: self.machine.clone(),
+                name: name.to_string(),
+                due_at,
+            },
+        );
+        schedule.heap.push(Reverse((due_at, handle.clone())));
+        Ok(handle)
+    }
+
+    fn cancel(&self, handle: &str) -> Result<(), String> {
+        let mut schedule = self.queue.lock();
+        if schedule
+            .live
+            .get(handle)
+            .is_some_and(|timer| timer.machine == self.machine)
+        {
+            schedule.live.remove(handle);
+        }
+        Ok(())
+    }
+
+    fn is_scheduled(&self, handle: &str) -> bool {
+        self.queue
+            .lock()
+            .live
+            .get(handle)
+            .is_some_and(|timer| timer.machine == self.machine)
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Schedule timer `name` with `service`, replacing a pending timer of
+    /// the same name.
+    pub fn schedule_timer(
+        &mut self,
+        service: &dyn TimerService,
+        name: impl Into<String>,
+        due_at: DateTime<Utc>,
+    ) -> Result<(), TimerError> {
+        let name = name.into();
+        self.cancel_timer(service, &name)?;
+        let handle = service
+            .schedule(&name, due_at)
+            .map_err(|reason| TimerError {
+                timer: name.clone(),
+                reason,
+            })?;
+        let mut timers = self.pending_timers().to_vec();
+        timers.push(PendingTimer {
+            name,
+            handle,
+            due_at,
+        });
+        self.set_pending_timers(timers);
+        Ok(())
+    }
+
+    /// Cancel pending timer `name`. Returns whether one was pending.
+    pub fn cancel_timer(
+        &mut self,
+        service: &dyn TimerService,
+        name: &str,
+    ) -> Result<bool, TimerError> {
+        let Some(timer) = self.pending_timers().iter().find(|t| t.name == name) else {
+            return Ok(false);
+        };
+        service.cancel(&timer.handle).map_err(|reason| TimerError {
+            timer: name.to_string(),
+            reason,
+        })?;
+        let timers = self
+            .pending_timers()
+            .iter()
+            .filter(|t| t.name != name)
+            .cloned()
+            .collect();
+        self.set_pending_timers(timers);
+        Ok(true)
+    }
+
+    /// Deliver the timer with `handle`, as called back by the timer
+    /// service, as a signal named after the timer.
+    ///
+    /// Returns false for handles that are not pending, e.g. a repeated
+    /// callback or one for a timer that was cancelled or replaced.
+    pub fn fire_timer(&mut self, handle: &str) -> bool {
+        let Some(timer) = self
+            .pending_timers()
+            .iter()
+            .find(|t| t.handle == handle)
+            .cloned()
+        else {
+            return false;
+        };
+        let timers = self
+            .pending_timers()
+            .iter()
+            .filter(|t| t.handle != handle)
+            .cloned()
+            .collect();
+        self.set_pending_timers(timers);
+        self.signal(
+            timer.name,
+            json!({ "handle": timer.handle, "due_at": timer.due_at }),
+        );
+        true
+    }
+
+    /// Reconcile pending timers with `service` as of `now`, e.g. after
+    /// resuming from a checkpoint.
+    ///
+    /// Timers the service no longer knows are fired if they are due, since
+    /// their callback was lost, and scheduled again otherwise.
+    pub fn reconcile_timers(
+        &mut self,
+        service: &dyn TimerService,
+        now: DateTime<Utc>,
+    ) -> Result<TimerReconciliation, TimerError> {
+        let mut report = TimerReconciliation::default();
+        let lost: Vec<_> = self
+            .pending_timers()
+            .iter()
+            .filter(|t| !service.is_scheduled(&t.handle))
+            .cloned()
+            .collect();
+        for timer in lost {
+            if timer.due_at <= now {
+                self.fire_timer(&timer.handle);
+                report.fired.push(timer.name);
+            } else {
+                let handle = service
+                    .schedule(&timer.name, timer.due_at)
+                    .map_err(|reason| TimerError {
+                        timer: timer.name.clone(),
+                        reason,
+                    })?;
+                let timers = self
+                    .pending_timers()
+                    .iter()
+                    .map(|t| PendingTimer {
+                        handle: if t.handle == timer.handle {
+                            handle.clone()
+                        } else {
+                            t.handle.clone()
+                        },
+                        ..t.clone()
+                    })
+                    .collect();
+                self.set_pending_timers(timers);
+                report.rescheduled.push(timer.name);
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::SignalWait;
+    use crate::state_enum;
+    use chrono::TimeDelta;
+    use std::collections::BTreeSet;
+    use std::sync::Mutex;
+
+    state_enum! {
+        enum Invoice {
+            Sent,
+            Overdue,
+        }
+        final: [Overdue]
+    }
+
+    #[derive(Default)]
+    struct Jobs {
+        scheduled: Mutex<BTreeSet<String>>,
+        next: Mutex<usize>,
+    }
+
+    impl TimerService for Jobs {
+        fn schedule(&self, name: &str, _due_at: DateTime<Utc>) -> Result<String, String> {
+            let mut next = self.next.lock().unwrap();
+            *next += 1;
+            let handle = format!("{name}-{next}");
+            self.scheduled.lock().unwrap().insert(handle.clone());
+            Ok(handle)
+        }
+
+        fn cancel(&self, handle: &str) -> Result<(), String> {
+            self.scheduled.lock().unwrap().remove(handle);
+            Ok(())
+        }
+
+        fn is_scheduled(&self, handle: &str) -> bool {
+            self.scheduled.lock().unwrap().contains(handle)
+        }
+    }
+
+    #[test]
+    fn fired_timers_arrive_as_signals_once() {
+        let jobs = Jobs::default();
+        let mut machine = StateMachine::<Invoice, ()>::new(Invoice::Sent);
+        machine.await_signal(&Invoice::Sent, SignalWait::new("reminder"));
+        let due = Utc::now() + TimeDelta::days(30);
+
+        machine.schedule_timer(&jobs, "reminder", due).unwrap();
+        machine.schedule_timer(&jobs, "reminder", due).unwrap();
+        assert_eq!(machine.pending_timers().len(), 1);
+        assert!(!jobs.is_scheduled("reminder-1"));
+
+        assert!(!machine.fire_timer("reminder-1"));
+        assert!(machine.fire_timer("reminder-2"));
+        assert!(!machine.fire_timer("reminder-2"));
+        assert!(machine.pending_timers().is_empty());
+        assert_eq!(machine.received_signal().unwrap().name, "reminder");
+    }
+
+    #[test]
+    fn reconcile_fires_lost_due_timers_and_reschedules_the_rest() {
+        let jobs = Jobs::default();
+        let mut machine = StateMachine::<Invoice, ()>::new(Invoice::Sent);
+        let now = Utc::now();
+        machine
+            .schedule_timer(&jobs, "reminder", now - TimeDelta::hours(1))
+            .unwrap();
+        machine
+            .schedule_timer(&jobs, "escalate", now + TimeDelta::hours(1))
+            .unwrap();
+
+        let json = machine.to_json().unwrap();
+        let mut resumed = StateMachine::<Invoice, ()>::from_json(&json, vec![]).unwrap();
+        let forgetful = Jobs::default();
+        let report = resumed.reconcile_timers(&forgetful, now).unwrap();
+
+        assert_eq!(
+            report,
+            TimerReconciliation {
+                fired: vec!["reminder".to_string()],
+                rescheduled: vec!["escalate".to_string()],
+            }
+        );
+        assert_eq!(resumed.pending_timers()[0].handle, "escalate-1");
+        assert_eq!(resumed.pending_signals()[0].name, "reminder");
+    }
+
+    #[test]
+    fn queue_pops_due_timers_in_order_and_skips_cancelled_ones() {
+        let queue = TimerQueue::new();
+        let now = Utc::now();
+        let mut a = StateMachine::<Invoice, ()>::new(Invoice::Sent);
+        let mut b = StateMachine::<Invoice, ()>::new(Invoice::Sent);
+        let (a_timers, b_timers) = (queue.for_machine("a"), queue.for_machine("b"));
+        a.schedule_timer(&a_timers, "escalate", now + TimeDelta::hours(2))
+            .unwrap();
+        b.schedule_timer(&b_timers, "reminder", now + TimeDelta::hours(1))
+            .unwrap();
+        a.schedule_timer(&a_timers, "reminder", now + TimeDelta::minutes(5))
+            .unwrap();
+        assert_eq!(queue.next_due(), Some(now + TimeDelta::minutes(5)));
+
+        a.cancel_timer(&a_timers, "reminder").unwrap();
+        assert_eq!(queue.next_due(), Some(now + TimeDelta::hours(1)));
+
+        let due = queue.pop_due(now + TimeDelta::hours(3));
+        let fired: Vec<_> = due
+            .iter()
+            .map(|t| (t.machine.as_str(), t.name.as_str()))
+            .collect();
+        assert_eq!(fired, vec![("b", "reminder"), ("a", "escalate")]);
+        assert!(b.fire_timer(&due[0].handle));
+        assert!(a.fire_timer(&due[1].handle));
+        assert!(queue.is_empty());
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn queue_handles_are_unique_across_queues_and_scoped_to_machines() {
+        let now = Utc::now();
+        let (before, after) = (TimerQueue::new(), TimerQueue::new());
+        let old = before.for_machine("a").schedule("reminder", now).unwrap();
+        let new = after.for_machine("a").schedule("reminder", now).unwrap();
+        assert_ne!(old, new);
+
+        assert!(!after.for_machine("b").is_scheduled(&new));
+        after.for_machine("b").cancel(&new).unwrap();
+        assert!(after.for_machine("a").is_scheduled(&new));
+    }
+}
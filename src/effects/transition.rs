@@ -1,7 +1,12 @@
 //! State transition types with effectful actions.
 
 use crate::core::{Guard, State};
+use crate::effects::flags::FeatureFlagProvider;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::panic::Location;
 use std::sync::Arc;
+use std::time::Duration;
 use stillwater::effect::BoxedEffect;
 
 /// Result of executing a transition action.
@@ -18,17 +23,106 @@ pub enum TransitionResult<S: State> {
     Abort { reason: String, error_state: S },
 }
 
+/// Context of the transition the machine would attempt next.
+///
+/// Built by `StateMachine::preview()` so external monitors can evaluate
+/// their own policies (e.g. imminent timeouts) without re-implementing
+/// transition selection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitionContext<S: State> {
+    /// State the transition starts from
+    pub from: S,
+    /// State the transition leads to
+    pub to: S,
+    /// Attempt number the next step would run as (1-based)
+    pub attempt: usize,
+    /// When the current transition attempt started
+    pub started_at: DateTime<Utc>,
+}
+
+/// How one transition out of the current state fared during selection.
+///
+/// Reported by [`TransitionError::NoTransition`] for every transition whose
+/// source has the current state's name, so a stuck machine can be debugged
+/// without instrumenting its guards.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CandidateCheck {
+    /// Target state name
+    pub to: String,
+    /// Whether the current state equals the transition's source state
+    pub state_matched: bool,
+    /// Outcome of the guard, if the transition has one and the state matched
+    pub guard_passed: Option<bool>,
+    /// Label of the guard, if any
+    pub guard_label: Option<String>,
+    /// Feature flag that kept the transition disabled, if any
+    pub disabled_by_flag: Option<String>,
+}
+
 /// Errors that can occur during transitions
+///
+/// Variants may be added in minor releases, so matches need a wildcard arm.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum TransitionError {
     #[error("No transition available from state '{from}'")]
-    NoTransition { from: String },
+    NoTransition {
+        from: String,
+        /// Transitions out of states named like the current one
+        candidates: Vec<CandidateCheck>,
+    },
 
     #[error("Guard blocked transition from '{from}' to '{to}'")]
     GuardBlocked { from: String, to: String },
 
     #[error("Transition action failed: {0}")]
     ActionFailed(String),
+
+    #[error("Lock '{key}' unavailable: {reason}")]
+    LockUnavailable { key: String, reason: String },
+
+    #[error("Transition action timed out after {after:?}")]
+    Timeout { after: Duration },
+
+    #[error("Could not spawn child workflow '{definition}': {reason}")]
+    SpawnFailed { definition: String, reason: String },
+
+    #[error("No transition handles event '{event}' in state '{state}'")]
+    UnhandledEvent { event: String, state: String },
+
+    #[error("Transition to '{expected}' moved to '{actual}' instead")]
+    UnexpectedTarget { expected: String, actual: String },
+}
+
+/// Errors rejecting a transition definition or a change to a live
+/// machine's transitions
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum DefinitionError {
+    #[error("Transition source '{from}' is not a state of the machine")]
+    UnknownSourceState { from: String },
+
+    #[error("No transition at index {index}")]
+    NoSuchTransition { index: usize },
+
+    #[error("Removing the transition would leave current state '{state}' without a way out")]
+    OrphansCurrentState { state: String },
+
+    #[error("No action registered under {}", names.join(", "))]
+    UnknownActions { names: Vec<String> },
+}
+
+/// Lets actions use `?` on boxed errors, including `anyhow::Error` via
+/// `Into<Box<dyn Error + Send + Sync>>`.
+impl From<Box<dyn std::error::Error + Send + Sync>> for TransitionError {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Self::ActionFailed(error.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for TransitionError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        Self::ActionFailed(error.to_string())
+    }
 }
 
 /// Type alias for transition action functions.
@@ -39,16 +133,93 @@ pub type TransitionAction<S, Env> =
 /// A transition from one state to another with an effectful action.
 /// Instead of storing the effect directly, we store a factory function
 /// that creates a fresh effect on each execution.
+///
+/// Construct transitions with [`Transition::new`] or a `TransitionBuilder`
+/// and set the optional fields afterwards; fields may be added in minor
+/// releases.
+#[non_exhaustive]
 pub struct Transition<S: State, Env> {
     pub from: S,
     pub to: S,
     pub guard: Option<Guard<S>>,
+    /// Where the transition was defined. Captured via `#[track_caller]`
+    /// by the builders and `StateMachine::add_transition` when unset.
+    pub location: Option<&'static Location<'static>>,
+    /// Static operational context, e.g. owner team or docs URL. Not used
+    /// for execution; surfaced by `StateMachine::describe()`.
+    pub metadata: BTreeMap<String, String>,
+    /// Feature flag the transition is rolled out behind, if any
+    pub flag: Option<String>,
     pub action: TransitionAction<S, Env>,
 }
 
 impl<S: State, Env> Transition<S, Env> {
+    /// Create an unguarded transition from `from` to `to` running effects
+    /// created by `action`, recording the caller as its definition site
+    #[track_caller]
+    pub fn new<F>(from: S, to: S, action: F) -> Self
+    where
+        F: Fn() -> BoxedEffect<TransitionResult<S>, TransitionError, Env> + Send + Sync + 'static,
+    {
+        Self {
+            from,
+            to,
+            guard: None,
+            location: Some(Location::caller()),
+            metadata: BTreeMap::new(),
+            flag: None,
+            action: Arc::new(action),
+        }
+    }
+
     /// Check if this transition can execute from the current state (pure)
+    ///
+    /// Transitions behind a feature flag never execute here; use
+    /// [`can_execute_with`](Self::can_execute_with) to evaluate the flag.
     pub fn can_execute(&self, current: &S) -> bool {
+        self.flag.is_none() && self.matches(current)
+    }
+
+    /// Check if this transition can execute, evaluating its feature flag
+    /// against `flags` (pure)
+    pub fn can_execute_with(&self, current: &S, flags: &dyn FeatureFlagProvider) -> bool {
+        self.flag
+            .as_deref()
+            .is_none_or(|flag| flags.is_enabled(flag))
+            && self.matches(current)
+    }
+
+    /// Report how this transition fares from `current` (pure).
+    ///
+    /// Without `flags`, a flagged transition counts as disabled, like in
+    /// [`can_execute`](Self::can_execute).
+    pub(crate) fn check(
+        &self,
+        current: &S,
+        flags: Option<&dyn FeatureFlagProvider>,
+    ) -> CandidateCheck {
+        let state_matched = *current == self.from;
+        CandidateCheck {
+            to: self.to.display_name().into_owned(),
+            state_matched,
+            guard_passed: self
+                .guard
+                .as_ref()
+                .filter(|_| state_matched)
+                .map(|g| g.check(current)),
+            guard_label: self
+                .guard
+                .as_ref()
+                .and_then(|g| g.label())
+                .map(str::to_string),
+            disabled_by_flag: self
+                .flag
+                .clone()
+                .filter(|flag| !flags.is_some_and(|flags| flags.is_enabled(flag))),
+        }
+    }
+
+    fn matches(&self, current: &S) -> bool {
         // Check state match
         if *current != self.from {
             return false;
@@ -65,6 +236,9 @@ impl<S: State, Env> Clone for Transition<S, Env> {
             from: self.from.clone(),
             to: self.to.clone(),
             guard: self.guard.clone(),
+            location: self.location,
+            metadata: self.metadata.clone(),
+            flag: self.flag.clone(),
             action: Arc::clone(&self.action),
         }
     }
@@ -100,27 +274,26 @@ mod tests {
 
     #[test]
     fn can_execute_matches_from_state() {
-        let transition: Transition<TestState, ()> = Transition {
-            from: TestState::Start,
-            to: TestState::Middle,
-            guard: None,
-            action: Arc::new(|| pure(TransitionResult::Success(TestState::Middle)).boxed()),
-        };
+        let line = line!() + 2;
+        let transition: Transition<TestState, ()> =
+            Transition::new(TestState::Start, TestState::Middle, || {
+                pure(TransitionResult::Success(TestState::Middle)).boxed()
+            });
 
         assert!(transition.can_execute(&TestState::Start));
         assert!(!transition.can_execute(&TestState::Middle));
+        assert_eq!(transition.location.map(|l| l.line()), Some(line));
     }
 
     #[test]
     fn can_execute_respects_guard() {
         let guard = Guard::new(|s: &TestState| s.is_final());
 
-        let transition: Transition<TestState, ()> = Transition {
-            from: TestState::End,
-            to: TestState::Start,
-            guard: Some(guard),
-            action: Arc::new(|| pure(TransitionResult::Success(TestState::Start)).boxed()),
-        };
+        let mut transition: Transition<TestState, ()> =
+            Transition::new(TestState::End, TestState::Start, || {
+                pure(TransitionResult::Success(TestState::Start)).boxed()
+            });
+        transition.guard = Some(guard);
 
         // Should execute - End is final and guard passes
         assert!(transition.can_execute(&TestState::End));
@@ -129,10 +302,31 @@ mod tests {
             from: TestState::Start,
             to: TestState::Middle,
             guard: Some(Guard::new(|s: &TestState| s.is_final())),
+            location: None,
+            metadata: Default::default(),
+            flag: None,
             action: Arc::new(|| pure(TransitionResult::Success(TestState::Middle)).boxed()),
         };
 
         // Should not execute - Start is not final
         assert!(!transition2.can_execute(&TestState::Start));
     }
+
+    #[test]
+    fn boxed_errors_convert_to_action_failed() {
+        fn load() -> Result<(), TransitionError> {
+            let inner: Box<dyn std::error::Error + Send + Sync> = "disk full".into();
+            Err(inner)?
+        }
+
+        assert!(matches!(
+            load(),
+            Err(TransitionError::ActionFailed(ref message)) if message == "disk full"
+        ));
+        let local: Box<dyn std::error::Error> = "offline".into();
+        assert_eq!(
+            TransitionError::from(local).to_string(),
+            "Transition action failed: offline"
+        );
+    }
 }
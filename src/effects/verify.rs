@@ -0,0 +1,184 @@
+//! Side-effect verification after resume.
+//!
+//! A process can crash after a transition's action performed its external
+//! side effect (charged a card, sent an email) but before the transition
+//! was checkpointed. Resuming then repeats the action. A transition marked
+//! with [`Transition::verify_on_resume`] first asks the outside world
+//! whether the side effect already happened, and only runs the action when
+//! it did not.
+
+use crate::core::State;
+use crate::effects::context::{HasStepContext, StepContext};
+use crate::effects::transition::{Transition, TransitionError, TransitionResult};
+use std::future::Future;
+use std::sync::Arc;
+use stillwater::effect::from_async;
+use stillwater::prelude::*;
+
+/// Outcome of checking an in-flight transition's side effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The side effect happened; complete the transition without the action
+    Completed,
+    /// The side effect did not happen; run the action
+    NotDone,
+}
+
+impl<S, Env> Transition<S, Env>
+where
+    S: State + 'static,
+    Env: HasStepContext<S> + Clone + Send + Sync + 'static,
+{
+    /// Check with `verify` whether this transition's side effect already
+    /// happened when it is the first transition attempted after resuming
+    /// from a checkpoint.
+    ///
+    /// [`Verification::Completed`] moves the machine to the target state
+    /// without running the action; [`Verification::NotDone`] runs it as
+    /// usual. Outside of resumes the action runs directly. The machine must
+    /// be driven with `StateMachine::step_with_context`; without a context
+    /// the step fails with [`TransitionError::ActionFailed`].
+    pub fn verify_on_resume<F, Fut>(self, verify: F) -> Self
+    where
+        F: Fn(Env, StepContext<S>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Verification, TransitionError>> + Send + 'static,
+    {
+        let verify = Arc::new(verify);
+        let action = self.action;
+        let to = self.to.clone();
+        Self {
+            action: Arc::new(move || {
+                let verify = Arc::clone(&verify);
+                let action = Arc::clone(&action);
+                let to = to.clone();
+                from_async(move |env: &Env| {
+                    let env = env.clone();
+                    async move {
+                        let context = env.step_context().cloned().ok_or_else(|| {
+                            TransitionError::ActionFailed(
+                                "resume verification needs a step context; drive the machine with step_with_context"
+                                    .to_string(),
+                            )
+                        })?;
+                        if context.resumed
+                            && verify(env.clone(), context).await? == Verification::Completed
+                        {
+                            return Ok(TransitionResult::Success(to));
+                        }
+                        action().run(&env).await
+                    }
+                })
+                .boxed()
+            }),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult};
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Payment {
+        Pending,
+        Charged,
+    }
+
+    impl State for Payment {
+        fn name(&self) -> &str {
+            match self {
+                Self::Pending => "Pending",
+                Self::Charged => "Charged",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Charged)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Env {
+        charges: Arc<AtomicUsize>,
+        context: Option<Arc<StepContext<Payment>>>,
+    }
+
+    impl HasStepContext<Payment> for Env {
+        fn step_context(&self) -> Option<&StepContext<Payment>> {
+            self.context.as_deref()
+        }
+
+        fn with_step_context(&self, context: Arc<StepContext<Payment>>) -> Self {
+            Self {
+                context: Some(context),
+                ..self.clone()
+            }
+        }
+    }
+
+    fn charge() -> Transition<Payment, Env> {
+        Transition {
+            from: Payment::Pending,
+            to: Payment::Charged,
+            guard: None,
+            location: None,
+            metadata: Default::default(),
+            flag: None,
+            action: Arc::new(|| {
+                from_fn(|env: &Env| {
+                    env.charges.fetch_add(1, Ordering::SeqCst);
+                    Ok(TransitionResult::Success(Payment::Charged))
+                })
+                .boxed()
+            }),
+        }
+        .verify_on_resume(|env: Env, _| async move {
+            Ok(if env.charges.load(Ordering::SeqCst) > 0 {
+                Verification::Completed
+            } else {
+                Verification::NotDone
+            })
+        })
+    }
+
+    async fn drive(machine: &mut StateMachine<Payment, Env>, env: &Env) {
+        let (from, result, attempt) = machine.step_with_context().run(env).await.unwrap();
+        assert_eq!(result, StepResult::Transitioned(Payment::Charged));
+        machine.apply_result(from, result, attempt);
+    }
+
+    #[tokio::test]
+    async fn completed_side_effects_are_not_repeated_after_resume() {
+        let env = Env::default();
+        let json = StateMachine::<Payment, Env>::new(Payment::Pending)
+            .to_json()
+            .unwrap();
+
+        // The charge went through before the crash
+        env.charges.store(1, Ordering::SeqCst);
+        let mut resumed = StateMachine::from_json(&json, vec![charge()]).unwrap();
+        drive(&mut resumed, &env).await;
+        assert_eq!(env.charges.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn actions_run_when_not_resumed_or_not_done() {
+        let env = Env::default();
+        let mut fresh = StateMachine::new(Payment::Pending);
+        fresh.add_transition(charge());
+        drive(&mut fresh, &env).await;
+        assert_eq!(env.charges.load(Ordering::SeqCst), 1);
+
+        let env = Env::default();
+        let json = StateMachine::<Payment, Env>::new(Payment::Pending)
+            .to_json()
+            .unwrap();
+        let mut resumed = StateMachine::from_json(&json, vec![charge()]).unwrap();
+        drive(&mut resumed, &env).await;
+        assert_eq!(env.charges.load(Ordering::SeqCst), 1);
+    }
+}
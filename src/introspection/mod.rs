@@ -0,0 +1,233 @@
+//! Introspection of state machine definitions.
+//!
+//! A `MachineDescription` is a plain, serializable view of a machine's
+//! topology: its states and the transitions between them. Descriptions do
+//! not include actions or guard logic, only whether a guard is present.
+
+use crate::core::State;
+use crate::effects::StateMachine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Description of a single state.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StateDescription {
+    /// State name
+    pub name: String,
+    /// Whether the state is final
+    pub is_final: bool,
+    /// Whether the state is an error state
+    pub is_error: bool,
+}
+
+/// Description of a single transition edge.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TransitionDescription {
+    /// Source state name
+    pub from: String,
+    /// Target state name
+    pub to: String,
+    /// Whether the transition has a guard
+    pub guarded: bool,
+    /// Label of the guard, if any
+    pub guard_label: Option<String>,
+    /// Metadata attached to the transition
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Feature flag the transition is behind, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag: Option<String>,
+}
+
+/// Serializable view of a machine's topology.
+///
+/// States are listed in first-seen order and transitions in definition
+/// order, which matches the order `step()` evaluates them in.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineDescription {
+    /// Name of the initial state
+    pub initial: String,
+    /// All states mentioned by the definition
+    pub states: Vec<StateDescription>,
+    /// All transitions
+    pub transitions: Vec<TransitionDescription>,
+}
+
+impl MachineDescription {
+    /// Return a copy with states and transitions sorted.
+    ///
+    /// Sorted descriptions are stable across reorderings of builder calls,
+    /// which makes them suitable for snapshot comparisons.
+    pub fn sorted(&self) -> Self {
+        let mut sorted = self.clone();
+        sorted.states.sort();
+        sorted.transitions.sort();
+        sorted
+    }
+
+    /// Serialize deterministically for golden-file snapshots.
+    ///
+    /// Produces pretty-printed JSON of the sorted description, terminated
+    /// by a newline.
+    pub fn to_snapshot(&self) -> String {
+        let json = serde_json::to_string_pretty(&self.sorted())
+            .expect("MachineDescription serialization cannot fail");
+        format!("{}\n", json)
+    }
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Describe the machine's topology (pure).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use mindset::builder::{simple_transition, StateMachineBuilder};
+    /// use mindset::state_enum;
+    ///
+    /// state_enum! {
+    ///     enum Light {
+    ///         Red,
+    ///         Green,
+    ///     }
+    /// }
+    ///
+    /// let machine = StateMachineBuilder::<Light, ()>::new()
+    ///     .initial(Light::Red)
+    ///     .add_transition(simple_transition(Light::Red, Light::Green))
+    ///     .add_transition(simple_transition(Light::Green, Light::Red))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let description = machine.describe();
+    /// assert_eq!(description.initial, "Red");
+    /// assert_eq!(description.states.len(), 2);
+    /// assert_eq!(description.transitions.len(), 2);
+    /// ```
+    pub fn describe(&self) -> MachineDescription {
+        let mut states: Vec<&S> = vec![self.initial_state()];
+        for transition in self.transitions() {
+            for state in [&transition.from, &transition.to] {
+                if !states.contains(&state) {
+                    states.push(state);
+                }
+            }
+        }
+
+        MachineDescription {
+            initial: self.initial_state().name().to_string(),
+            states: states
+                .into_iter()
+                .map(|s| StateDescription {
+                    name: s.name().to_string(),
+                    is_final: s.is_final(),
+                    is_error: s.is_error(),
+                })
+                .collect(),
+            transitions: self
+                .transitions()
+                .iter()
+                .map(|t| TransitionDescription {
+                    from: t.from.name().to_string(),
+                    to: t.to.name().to_string(),
+                    guarded: t.guard.is_some(),
+                    guard_label: t.guard.as_ref().and_then(|g| g.label()).map(String::from),
+                    metadata: t.metadata.clone(),
+                    flag: t.flag.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{guarded_transition, simple_transition};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Start,
+        Middle,
+        End,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Start => "Start",
+                Self::Middle => "Middle",
+                Self::End => "End",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::End)
+        }
+    }
+
+    #[test]
+    fn describe_lists_states_and_transitions() {
+        let mut machine = StateMachine::<TestState, ()>::new(TestState::Start);
+        machine.add_transition(simple_transition(TestState::Start, TestState::Middle));
+        machine.add_transition(guarded_transition(
+            TestState::Middle,
+            TestState::End,
+            |_| true,
+        ));
+
+        let description = machine.describe();
+
+        let names: Vec<_> = description.states.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Start", "Middle", "End"]);
+        assert!(description.states[2].is_final);
+        assert!(!description.transitions[0].guarded);
+        assert!(description.transitions[1].guarded);
+    }
+
+    #[test]
+    fn snapshot_is_independent_of_definition_order() {
+        let mut first = StateMachine::<TestState, ()>::new(TestState::Start);
+        first.add_transition(simple_transition(TestState::Start, TestState::Middle));
+        first.add_transition(simple_transition(TestState::Middle, TestState::End));
+
+        let mut second = StateMachine::<TestState, ()>::new(TestState::Start);
+        second.add_transition(simple_transition(TestState::Middle, TestState::End));
+        second.add_transition(simple_transition(TestState::Start, TestState::Middle));
+
+        assert_ne!(first.describe(), second.describe());
+        assert_eq!(
+            first.describe().to_snapshot(),
+            second.describe().to_snapshot()
+        );
+    }
+
+    #[test]
+    fn describe_includes_transition_metadata() {
+        let transition = crate::builder::TransitionBuilder::<TestState, ()>::new()
+            .from(TestState::Start)
+            .to(TestState::End)
+            .metadata("owner", "payments")
+            .metadata("docs", "https://wiki.example.com/runbooks/settle")
+            .succeeds()
+            .build()
+            .unwrap();
+        let mut machine = StateMachine::<TestState, ()>::new(TestState::Start);
+        machine.add_transition(transition);
+        machine.add_transition(simple_transition(TestState::Start, TestState::Middle));
+
+        let description = machine.describe();
+        assert_eq!(
+            description.transitions[0]
+                .metadata
+                .get("owner")
+                .map(String::as_str),
+            Some("payments")
+        );
+        assert!(description.transitions[1].metadata.is_empty());
+
+        let snapshot = description.to_snapshot();
+        assert_eq!(snapshot.matches("\"metadata\"").count(), 1);
+    }
+}
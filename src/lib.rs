@@ -19,7 +19,6 @@
 //! use serde::{Deserialize, Serialize};
 //! use chrono::Utc;
 //! use stillwater::prelude::*;
-//! use std::sync::Arc;
 //!
 //! #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 //! enum WorkflowState {
@@ -46,21 +45,33 @@
 //! let mut machine: StateMachine<WorkflowState, ()> = StateMachine::new(WorkflowState::Initial);
 //!
 //! // Add a transition with an action factory
-//! machine.add_transition(Transition {
-//!     from: WorkflowState::Initial,
-//!     to: WorkflowState::Processing,
-//!     guard: None,
-//!     action: Arc::new(|| pure(TransitionResult::Success(WorkflowState::Processing)).boxed()),
-//! });
+//! machine.add_transition(Transition::new(
+//!     WorkflowState::Initial,
+//!     WorkflowState::Processing,
+//!     || pure(TransitionResult::Success(WorkflowState::Processing)).boxed(),
+//! ));
 //! ```
 
 pub mod builder;
+pub mod catalog;
 pub mod checkpoint;
+pub mod compose;
 pub mod core;
 pub mod effects;
+pub mod introspection;
+pub mod lint;
+pub mod query;
+pub mod stats;
+pub mod testing;
+pub mod timeline;
 
 // Re-export commonly used types
-pub use builder::{BuildError, StateMachineBuilder, TransitionBuilder};
-pub use checkpoint::{Checkpoint, CheckpointError, MachineMetadata, CHECKPOINT_VERSION};
-pub use core::{Guard, State, StateHistory, StateTransition};
-pub use effects::{StateMachine, StepResult, Transition, TransitionError, TransitionResult};
+pub use builder::{BuildError, FinalTransitionPolicy, StateMachineBuilder, TransitionBuilder};
+pub use checkpoint::{
+    Checkpoint, CheckpointError, MachineMetadata, MachineStats, PauseInfo, PendingTimer, Signal,
+    SyntheticStart, CHECKPOINT_VERSION,
+};
+pub use core::{ForcedTransition, Guard, HistoryEvent, State, StateHistory, StateTransition};
+pub use effects::{
+    StateMachine, StepResult, Transition, TransitionContext, TransitionError, TransitionResult,
+};
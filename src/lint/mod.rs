@@ -0,0 +1,424 @@
+//! Lint checks for state machine definitions.
+//!
+//! Linting inspects the transition graph of a machine (ignoring guards and
+//! actions) and reports definition smells such as unreachable states or
+//! states that can never reach completion. Each finding carries a stable
+//! code and a severity, so downstream CI can fail on workflow smells.
+
+use crate::core::State;
+use crate::effects::{StateMachine, Transition};
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::Location;
+
+/// Severity of a lint finding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing about, usually intentional
+    Info,
+    /// Likely a mistake in the definition
+    Warning,
+    /// Almost certainly a broken definition
+    Error,
+}
+
+/// Lint rules that can be configured individually.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// A state used by a transition cannot be reached from the initial state
+    UnreachableState,
+    /// A reachable non-final state has no path to any final state
+    NoPathToFinal,
+    /// An unguarded transition shadows later transitions from the same state
+    AmbiguousTransition,
+    /// A reachable non-final state has no transition into an error state
+    MissingErrorHandling,
+}
+
+impl LintRule {
+    /// All rules, in code order.
+    pub const ALL: [LintRule; 4] = [
+        LintRule::UnreachableState,
+        LintRule::NoPathToFinal,
+        LintRule::AmbiguousTransition,
+        LintRule::MissingErrorHandling,
+    ];
+
+    /// Stable code for this rule, suitable for CI allowlists.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnreachableState => "MS001",
+            Self::NoPathToFinal => "MS002",
+            Self::AmbiguousTransition => "MS003",
+            Self::MissingErrorHandling => "MS004",
+        }
+    }
+
+    /// Severity used when the rule is not overridden.
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Self::UnreachableState => Severity::Warning,
+            Self::NoPathToFinal => Severity::Error,
+            Self::AmbiguousTransition => Severity::Warning,
+            Self::MissingErrorHandling => Severity::Info,
+        }
+    }
+}
+
+/// A single lint finding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintWarning {
+    /// Rule that produced the finding
+    pub rule: LintRule,
+    /// Stable code of the rule
+    pub code: &'static str,
+    /// Effective severity after configuration
+    pub severity: Severity,
+    /// Name of the state the finding is about
+    pub state: String,
+    /// Human-readable description
+    pub message: String,
+    /// Source location of the transition the finding is about, if any
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {:?}: {}", self.code, self.severity, self.message)?;
+        if let Some(location) = self.location {
+            write!(f, " (defined at {location})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Lint configuration: per-rule severity overrides and disabled rules.
+#[derive(Clone, Debug, Default)]
+pub struct LintConfig {
+    overrides: HashMap<LintRule, Option<Severity>>,
+}
+
+impl LintConfig {
+    /// Create a configuration using default severities for every rule.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disable a rule entirely.
+    pub fn allow(mut self, rule: LintRule) -> Self {
+        self.overrides.insert(rule, None);
+        self
+    }
+
+    /// Override the severity reported by a rule.
+    pub fn severity(mut self, rule: LintRule, severity: Severity) -> Self {
+        self.overrides.insert(rule, Some(severity));
+        self
+    }
+
+    fn effective(&self, rule: LintRule) -> Option<Severity> {
+        match self.overrides.get(&rule) {
+            Some(overridden) => *overridden,
+            None => Some(rule.default_severity()),
+        }
+    }
+}
+
+/// States reachable from `start` following transitions, ignoring guards.
+fn reachable_from<'a, S: State, Env>(
+    start: &'a S,
+    transitions: &'a [Transition<S, Env>],
+) -> Vec<&'a S> {
+    let mut seen = vec![start];
+    let mut frontier = vec![start];
+    while let Some(state) = frontier.pop() {
+        for transition in transitions.iter().filter(|t| &t.from == state) {
+            if !seen.contains(&&transition.to) {
+                seen.push(&transition.to);
+                frontier.push(&transition.to);
+            }
+        }
+    }
+    seen
+}
+
+/// Run all lint rules over a transition graph rooted at `initial`.
+pub fn lint_transitions<S: State, Env>(
+    initial: &S,
+    transitions: &[Transition<S, Env>],
+    config: &LintConfig,
+) -> Vec<LintWarning> {
+    let mut findings = Vec::new();
+    let mut report = |rule: LintRule,
+                      state: &S,
+                      location: Option<&'static Location<'static>>,
+                      message: String| {
+        if let Some(severity) = config.effective(rule) {
+            findings.push(LintWarning {
+                rule,
+                code: rule.code(),
+                severity,
+                state: state.name().to_string(),
+                message,
+                location,
+            });
+        }
+    };
+
+    // Every state mentioned by the definition, in first-seen order
+    let mut states: Vec<&S> = vec![initial];
+    for transition in transitions {
+        for state in [&transition.from, &transition.to] {
+            if !states.contains(&state) {
+                states.push(state);
+            }
+        }
+    }
+
+    let reachable = reachable_from(initial, transitions);
+
+    for state in &states {
+        if !reachable.contains(state) {
+            report(
+                LintRule::UnreachableState,
+                state,
+                None,
+                format!(
+                    "state '{}' is not reachable from initial state '{}'",
+                    state.display(),
+                    initial.display()
+                ),
+            );
+        }
+    }
+
+    for state in reachable.iter().filter(|s| !s.is_final()) {
+        let can_finish = reachable_from(*state, transitions)
+            .iter()
+            .any(|s| s.is_final());
+        if !can_finish {
+            report(
+                LintRule::NoPathToFinal,
+                state,
+                None,
+                format!("state '{}' has no path to a final state", state.display()),
+            );
+        }
+    }
+
+    for (index, transition) in transitions.iter().enumerate() {
+        if transition.guard.is_some() {
+            continue;
+        }
+        let shadowed = transitions[index + 1..]
+            .iter()
+            .filter(|t| t.from == transition.from)
+            .count();
+        if shadowed > 0 {
+            report(
+                LintRule::AmbiguousTransition,
+                &transition.from,
+                transition.location,
+                format!(
+                    "unguarded transition '{}' -> '{}' shadows {} later transition(s) from '{}'",
+                    transition.from.display(),
+                    transition.to.display(),
+                    shadowed,
+                    transition.from.display()
+                ),
+            );
+        }
+    }
+
+    for state in reachable.iter().filter(|s| !s.is_final()) {
+        let handles_errors = transitions
+            .iter()
+            .any(|t| &t.from == *state && t.to.is_error());
+        if !handles_errors {
+            report(
+                LintRule::MissingErrorHandling,
+                state,
+                None,
+                format!(
+                    "non-final state '{}' has no transition into an error state",
+                    state.display()
+                ),
+            );
+        }
+    }
+
+    findings
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Lint the machine definition with default rule severities (pure).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use mindset::builder::{simple_transition, StateMachineBuilder};
+    /// use mindset::lint::LintRule;
+    /// use mindset::state_enum;
+    ///
+    /// state_enum! {
+    ///     enum Job {
+    ///         Queued,
+    ///         Running,
+    ///         Stuck,
+    ///         Done,
+    ///     }
+    ///     final: [Done]
+    /// }
+    ///
+    /// let machine = StateMachineBuilder::<Job, ()>::new()
+    ///     .initial(Job::Queued)
+    ///     .add_transition(simple_transition(Job::Queued, Job::Running))
+    ///     .add_transition(simple_transition(Job::Running, Job::Done))
+    ///     .add_transition(simple_transition(Job::Queued, Job::Stuck))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let warnings = machine.lint();
+    /// assert!(warnings
+    ///     .iter()
+    ///     .any(|w| w.rule == LintRule::NoPathToFinal && w.state == "Stuck"));
+    /// ```
+    pub fn lint(&self) -> Vec<LintWarning> {
+        self.lint_with(&LintConfig::default())
+    }
+
+    /// Lint the machine definition with a custom configuration (pure).
+    pub fn lint_with(&self, config: &LintConfig) -> Vec<LintWarning> {
+        lint_transitions(self.initial_state(), self.transitions(), config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::simple_transition;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Initial,
+        Processing,
+        Orphan,
+        Complete,
+        Failed,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Initial => "Initial",
+                Self::Processing => "Processing",
+                Self::Orphan => "Orphan",
+                Self::Complete => "Complete",
+                Self::Failed => "Failed",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Complete | Self::Failed)
+        }
+
+        fn is_error(&self) -> bool {
+            matches!(self, Self::Failed)
+        }
+    }
+
+    fn t(from: TestState, to: TestState) -> Transition<TestState, ()> {
+        simple_transition(from, to)
+    }
+
+    fn rules(findings: &[LintWarning]) -> Vec<(LintRule, String)> {
+        findings.iter().map(|f| (f.rule, f.state.clone())).collect()
+    }
+
+    #[test]
+    fn ambiguity_points_at_transition_definition() {
+        let transitions = vec![
+            t(TestState::Initial, TestState::Processing),
+            t(TestState::Initial, TestState::Failed),
+        ];
+
+        let findings = lint_transitions(&TestState::Initial, &transitions, &LintConfig::new());
+        let ambiguity = findings
+            .iter()
+            .find(|f| f.rule == LintRule::AmbiguousTransition)
+            .unwrap();
+
+        assert_eq!(ambiguity.location, transitions[0].location);
+        assert!(ambiguity.to_string().contains(file!()));
+    }
+
+    #[test]
+    fn clean_definition_has_no_findings() {
+        let transitions = vec![
+            t(TestState::Initial, TestState::Processing),
+            t(TestState::Initial, TestState::Failed),
+            t(TestState::Processing, TestState::Complete),
+            t(TestState::Processing, TestState::Failed),
+        ];
+        let config = LintConfig::new().allow(LintRule::AmbiguousTransition);
+
+        assert!(lint_transitions(&TestState::Initial, &transitions, &config).is_empty());
+    }
+
+    #[test]
+    fn detects_unreachable_and_dead_end_states() {
+        let transitions = vec![
+            t(TestState::Initial, TestState::Processing),
+            t(TestState::Orphan, TestState::Complete),
+        ];
+        let config = LintConfig::new().allow(LintRule::MissingErrorHandling);
+
+        let findings = lint_transitions(&TestState::Initial, &transitions, &config);
+
+        assert_eq!(
+            rules(&findings),
+            vec![
+                (LintRule::UnreachableState, "Orphan".to_string()),
+                (LintRule::UnreachableState, "Complete".to_string()),
+                (LintRule::NoPathToFinal, "Initial".to_string()),
+                (LintRule::NoPathToFinal, "Processing".to_string()),
+            ]
+        );
+        assert_eq!(findings[2].severity, Severity::Error);
+        assert_eq!(findings[2].code, "MS002");
+    }
+
+    #[test]
+    fn detects_shadowed_transitions() {
+        let transitions = vec![
+            t(TestState::Initial, TestState::Complete),
+            t(TestState::Initial, TestState::Failed),
+        ];
+        let findings = lint_transitions(&TestState::Initial, &transitions, &LintConfig::new());
+
+        assert_eq!(
+            rules(&findings),
+            vec![(LintRule::AmbiguousTransition, "Initial".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_missing_error_handling_as_info() {
+        let transitions = vec![t(TestState::Initial, TestState::Complete)];
+        let findings = lint_transitions(&TestState::Initial, &transitions, &LintConfig::new());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, LintRule::MissingErrorHandling);
+        assert_eq!(findings[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn config_overrides_severity() {
+        let transitions = vec![t(TestState::Initial, TestState::Complete)];
+        let config = LintConfig::new().severity(LintRule::MissingErrorHandling, Severity::Error);
+        let findings = lint_transitions(&TestState::Initial, &transitions, &config);
+
+        assert_eq!(findings[0].severity, Severity::Error);
+    }
+}
@@ -0,0 +1,294 @@
+//! Queries over the checkpoints of many runs.
+//!
+//! Simple operational questions ("how many refunds failed in Review last
+//! week, per team?") should not require exporting checkpoints to a
+//! database. A [`Query`] filters runs by visited state, time range,
+//! outcome and labels, and yields either the matching runs or their
+//! matching transitions as iterators. Queries take any iterator over
+//! checkpoint references, so checkpoints held in memory and checkpoints
+//! streamed from storage are queried the same way; projections are plain
+//! `map` calls on the results.
+
+use crate::checkpoint::Checkpoint;
+use crate::core::{State, StateTransition};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How a run stands, judged by its current state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Outcome {
+    /// Not in a final state yet
+    Running,
+    /// In a final state that is not an error state
+    Completed,
+    /// In an error state
+    Failed,
+}
+
+impl Outcome {
+    /// Outcome of the run a checkpoint captured (pure)
+    pub fn of<S: State>(checkpoint: &Checkpoint<S>) -> Self {
+        let state = &checkpoint.current_state;
+        if state.is_error() {
+            Self::Failed
+        } else if state.is_final() {
+            Self::Completed
+        } else {
+            Self::Running
+        }
+    }
+}
+
+/// A transition together with the run it belongs to.
+#[derive(Debug)]
+pub struct TransitionRecord<'a, S: State> {
+    /// Checkpoint of the run
+    pub run: &'a Checkpoint<S>,
+    /// The transition
+    pub transition: &'a StateTransition<S>,
+}
+
+impl<S: State> Clone for TransitionRecord<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: State> Copy for TransitionRecord<'_, S> {}
+
+/// Filters over runs and their transitions.
+///
+/// All filters must match; an empty query matches everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    state: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    outcome: Option<Outcome>,
+    labels: BTreeMap<String, String>,
+}
+
+impl Query {
+    /// A query matching everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs that visited the state named `state`; transitions into it
+    pub fn state(mut self, state: impl Into<String>) -> Self {
+        self.state = Some(state.into());
+        self
+    }
+
+    /// Transitions at or after `since`; runs with such a transition
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Transitions before `until`; runs with such a transition
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Runs with the given outcome
+    pub fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    /// Runs labelled `key=value`
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Runs matching the query (lazy)
+    pub fn runs<'a, S, I>(&'a self, runs: I) -> impl Iterator<Item = &'a Checkpoint<S>> + 'a
+    where
+        S: State + 'a,
+        I: IntoIterator<Item = &'a Checkpoint<S>>,
+        I::IntoIter: 'a,
+    {
+        runs.into_iter().filter(move |run| self.matches_run(run))
+    }
+
+    /// Transitions matching the query, run by run in history order (lazy)
+    pub fn transitions<'a, S, I>(
+        &'a self,
+        runs: I,
+    ) -> impl Iterator<Item = TransitionRecord<'a, S>> + 'a
+    where
+        S: State + 'a,
+        I: IntoIterator<Item = &'a Checkpoint<S>>,
+        I::IntoIter: 'a,
+    {
+        runs.into_iter()
+            .filter(move |run| self.matches_run_filters(run))
+            .flat_map(|run| {
+                run.history
+                    .transitions()
+                    .iter()
+                    .map(move |transition| TransitionRecord { run, transition })
+            })
+            .filter(move |record| self.matches_transition(record.transition))
+    }
+
+    /// Number of matching transitions per `key` (e.g. target state or a
+    /// label)
+    pub fn count_by<'a, S, I, K, F>(&'a self, runs: I, key: F) -> BTreeMap<K, usize>
+    where
+        S: State + 'a,
+        I: IntoIterator<Item = &'a Checkpoint<S>>,
+        I::IntoIter: 'a,
+        K: Ord,
+        F: Fn(&TransitionRecord<'a, S>) -> K,
+    {
+        let mut counts = BTreeMap::new();
+        for record in self.transitions(runs) {
+            *counts.entry(key(&record)).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn matches_run<S: State>(&self, run: &Checkpoint<S>) -> bool {
+        if !self.matches_run_filters(run) {
+            return false;
+        }
+        let transition_filtered =
+            self.state.is_some() || self.since.is_some() || self.until.is_some();
+        if !transition_filtered {
+            return true;
+        }
+        let starts_in_state = self.since.is_none()
+            && self.until.is_none()
+            && self.state.as_deref() == Some(run.initial_state.name());
+        starts_in_state
+            || run
+                .history
+                .transitions()
+                .iter()
+                .any(|transition| self.matches_transition(transition))
+    }
+
+    /// Filters on the run as a whole: outcome and labels
+    fn matches_run_filters<S: State>(&self, run: &Checkpoint<S>) -> bool {
+        self.outcome
+            .is_none_or(|outcome| Outcome::of(run) == outcome)
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| run.metadata.labels.get(key) == Some(value))
+    }
+
+    fn matches_transition<S: State>(&self, transition: &StateTransition<S>) -> bool {
+        self.state
+            .as_deref()
+            .is_none_or(|state| transition.to.name() == state)
+            && self.since.is_none_or(|since| transition.timestamp >= since)
+            && self.until.is_none_or(|until| transition.timestamp < until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{StateMachine, StepResult};
+    use chrono::TimeZone;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Refund {
+        Requested,
+        Review,
+        Refunded,
+        Rejected,
+    }
+
+    impl State for Refund {
+        fn name(&self) -> &str {
+            match self {
+                Self::Requested => "Requested",
+                Self::Review => "Review",
+                Self::Refunded => "Refunded",
+                Self::Rejected => "Rejected",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Refunded | Self::Rejected)
+        }
+
+        fn is_error(&self) -> bool {
+            matches!(self, Self::Rejected)
+        }
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, day, 12, 0, 0).unwrap()
+    }
+
+    fn run(team: &str, path: &[(Refund, u32)]) -> Checkpoint<Refund> {
+        let mut machine = StateMachine::<Refund, ()>::new(Refund::Requested);
+        machine.set_label("team", team);
+        let mut from = Refund::Requested;
+        for (to, on) in path {
+            machine.apply_result_at(
+                from,
+                StepResult::Transitioned(to.clone()),
+                1,
+                Default::default(),
+                day(*on),
+            );
+            from = to.clone();
+        }
+        machine.checkpoint()
+    }
+
+    fn runs() -> Vec<Checkpoint<Refund>> {
+        vec![
+            run("billing", &[(Refund::Review, 2), (Refund::Rejected, 3)]),
+            run("billing", &[(Refund::Review, 9), (Refund::Refunded, 10)]),
+            run("support", &[(Refund::Review, 3), (Refund::Rejected, 4)]),
+            run("support", &[]),
+        ]
+    }
+
+    #[test]
+    fn runs_filter_by_outcome_label_and_time() {
+        let runs = runs();
+
+        let failed = Query::new().outcome(Outcome::Failed);
+        assert_eq!(failed.runs(&runs).count(), 2);
+        let billing_failed = failed.clone().label("team", "billing");
+        assert_eq!(billing_failed.runs(&runs).count(), 1);
+
+        let first_week = Query::new().state("Review").until(day(8));
+        assert_eq!(first_week.runs(&runs).count(), 2);
+        assert_eq!(Query::new().state("Requested").runs(&runs).count(), 4);
+    }
+
+    #[test]
+    fn transitions_project_and_aggregate() {
+        let runs = runs();
+        let query = Query::new().since(day(3));
+
+        let targets: Vec<&str> = query
+            .transitions(&runs)
+            .map(|record| record.transition.to.name())
+            .collect();
+        assert_eq!(
+            targets,
+            vec!["Rejected", "Review", "Refunded", "Review", "Rejected"]
+        );
+
+        let per_team = Query::new()
+            .state("Rejected")
+            .count_by(&runs, |record| record.run.metadata.labels["team"].clone());
+        assert_eq!(
+            per_team,
+            BTreeMap::from([("billing".to_string(), 1), ("support".to_string(), 1)])
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Early warnings from deviating transitions.
+//!
+//! An [`AnomalyDetector`] learns a baseline per `from -> to` edge, an
+//! exponentially weighted moving average (EWMA) of the time spent in `from`
+//! and of the retries needed, and reports transitions that exceed the
+//! baseline by a configurable factor. A dependency that slowly degrades
+//! then shows up as a stream of anomalies before runs start failing.
+
+use crate::core::{State, StateTransition};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Factor over the baseline that counts as anomalous, by default.
+pub const DEFAULT_ANOMALY_FACTOR: f64 = 3.0;
+
+/// Weight of each new observation in the baseline, by default.
+pub const DEFAULT_SMOOTHING: f64 = 0.2;
+
+/// Observations per edge before anomalies are reported, by default.
+pub const DEFAULT_WARMUP: usize = 5;
+
+/// A transition that deviated from its edge's baseline.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Anomaly {
+    /// The machine stayed in `from` much longer than usual
+    SlowTransition {
+        from: String,
+        to: String,
+        duration_ms: u64,
+        baseline_ms: f64,
+    },
+    /// The transition needed many more retries than usual
+    RetrySpike {
+        from: String,
+        to: String,
+        retries: usize,
+        baseline: f64,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+struct Baseline {
+    samples: usize,
+    duration_ms: f64,
+    retries: f64,
+}
+
+/// Learns per-edge baselines and flags deviating transitions.
+#[derive(Clone, Debug)]
+pub struct AnomalyDetector {
+    factor: f64,
+    smoothing: f64,
+    warmup: usize,
+    baselines: BTreeMap<(String, String), Baseline>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self {
+            factor: DEFAULT_ANOMALY_FACTOR,
+            smoothing: DEFAULT_SMOOTHING,
+            warmup: DEFAULT_WARMUP,
+            baselines: BTreeMap::new(),
+        }
+    }
+}
+
+impl AnomalyDetector {
+    /// Create a detector with default factor, smoothing and warm-up
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report transitions exceeding `factor` times the baseline
+    pub fn with_factor(mut self, factor: f64) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Weight of each new observation in the baseline, in `(0, 1]`
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Observations per edge before anomalies are reported
+    pub fn with_warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Check `transition` against its edge's baseline, then fold it in.
+    ///
+    /// `time_in_from` is how long the machine spent in the source state,
+    /// e.g. the gap to the previous transition. Retries are taken from the
+    /// transition's attempt number. A retry spike needs more than `factor`
+    /// retries even when the baseline is below one.
+    pub fn observe<S: State>(
+        &mut self,
+        transition: &StateTransition<S>,
+        time_in_from: Duration,
+    ) -> Vec<Anomaly> {
+        let from = transition.from.name().to_string();
+        let to = transition.to.name().to_string();
+        let duration_ms = time_in_from.as_millis() as f64;
+        let retries = transition.attempt.saturating_sub(1);
+        let baseline = self
+            .baselines
+            .entry((from.clone(), to.clone()))
+            .or_default();
+
+        let mut found = Vec::new();
+        if baseline.samples >= self.warmup {
+            if baseline.duration_ms > 0.0 && duration_ms > self.factor * baseline.duration_ms {
+                found.push(Anomaly::SlowTransition {
+                    from: from.clone(),
+                    to: to.clone(),
+                    duration_ms: duration_ms as u64,
+                    baseline_ms: baseline.duration_ms,
+                });
+            }
+            if retries as f64 > self.factor * baseline.retries.max(1.0) {
+                found.push(Anomaly::RetrySpike {
+                    from,
+                    to,
+                    retries,
+                    baseline: baseline.retries,
+                });
+            }
+        }
+
+        if baseline.samples == 0 {
+            baseline.duration_ms = duration_ms;
+            baseline.retries = retries as f64;
+        } else {
+            let alpha = self.smoothing;
+            baseline.duration_ms += alpha * (duration_ms - baseline.duration_ms);
+            baseline.retries += alpha * (retries as f64 - baseline.retries);
+        }
+        baseline.samples += 1;
+        found
+    }
+
+    /// Learned baseline duration of an edge in milliseconds, if observed
+    pub fn baseline_ms(&self, from: &str, to: &str) -> Option<f64> {
+        self.baselines
+            .get(&(from.to_string(), to.to_string()))
+            .map(|baseline| baseline.duration_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Feed {
+        Fetching,
+        Stored,
+    }
+
+    impl State for Feed {
+        fn name(&self) -> &str {
+            match self {
+                Self::Fetching => "Fetching",
+                Self::Stored => "Stored",
+            }
+        }
+    }
+
+    fn fetched(attempt: usize) -> StateTransition<Feed> {
+        StateTransition {
+            from: Feed::Fetching,
+            to: Feed::Stored,
+            timestamp: Utc::now(),
+            attempt,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
+        }
+    }
+
+    #[test]
+    fn deviations_are_reported_after_warmup() {
+        let mut detector = AnomalyDetector::new().with_warmup(3);
+        let usual = Duration::from_millis(100);
+        for _ in 0..3 {
+            assert!(detector.observe(&fetched(1), usual).is_empty());
+        }
+        assert_eq!(detector.baseline_ms("Fetching", "Stored"), Some(100.0));
+
+        assert!(detector
+            .observe(&fetched(2), Duration::from_millis(250))
+            .is_empty());
+        let found = detector.observe(&fetched(6), Duration::from_secs(1));
+        assert!(matches!(
+            found.as_slice(),
+            [
+                Anomaly::SlowTransition {
+                    duration_ms: 1000,
+                    ..
+                },
+                Anomaly::RetrySpike { retries: 5, .. }
+            ]
+        ));
+    }
+}
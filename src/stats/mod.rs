@@ -0,0 +1,318 @@
+//! Aggregate statistics across many runs.
+//!
+//! A [`StatsCollector`] consumes the histories or checkpoints of many runs
+//! of one definition and produces a serializable [`StatsReport`]: duration
+//! percentiles per transition, abort rate, most common paths and retry hot
+//! spots. Runs of different definitions should be fed to separate
+//! collectors, since transitions are keyed by state names only.
+//!
+//! An [`AnomalyDetector`] watches transitions as they happen instead and
+//! flags those that deviate from the learned per-edge baseline.
+
+mod anomaly;
+
+pub use anomaly::{
+    Anomaly, AnomalyDetector, DEFAULT_ANOMALY_FACTOR, DEFAULT_SMOOTHING, DEFAULT_WARMUP,
+};
+
+use crate::checkpoint::Checkpoint;
+use crate::core::{State, StateHistory};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Statistics for one `from -> to` edge.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransitionStats {
+    /// Source state name
+    pub from: String,
+    /// Target state name
+    pub to: String,
+    /// Number of times the transition happened
+    pub count: usize,
+    /// Median time spent in `from` before the transition, in milliseconds
+    pub median_ms: Option<i64>,
+    /// 95th percentile of the time spent in `from`, in milliseconds
+    pub p95_ms: Option<i64>,
+    /// Retries before success, summed over all occurrences
+    pub retries: usize,
+}
+
+/// A sequence of states and how many runs followed it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathCount {
+    /// State names in visiting order
+    pub path: Vec<String>,
+    /// Number of runs that followed the path
+    pub runs: usize,
+}
+
+/// Serializable summary of many runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatsReport {
+    /// Number of runs consumed
+    pub runs: usize,
+    /// Runs that ended in an error state
+    pub aborted: usize,
+    /// `aborted / runs`, or 0 without runs
+    pub abort_rate: f64,
+    /// Per-transition statistics, ordered by source then target
+    pub transitions: Vec<TransitionStats>,
+    /// Paths, most common first
+    pub paths: Vec<PathCount>,
+}
+
+impl StatsReport {
+    /// Transitions that needed retries, most retried first.
+    pub fn retry_hot_spots(&self) -> Vec<&TransitionStats> {
+        let mut hot: Vec<_> = self.transitions.iter().filter(|t| t.retries > 0).collect();
+        hot.sort_by(|a, b| b.retries.cmp(&a.retries));
+        hot
+    }
+}
+
+#[derive(Default)]
+struct Edge {
+    durations_ms: Vec<i64>,
+    count: usize,
+    retries: usize,
+}
+
+/// Accumulates runs for a [`StatsReport`].
+#[derive(Default)]
+pub struct StatsCollector {
+    runs: usize,
+    aborted: usize,
+    edges: BTreeMap<(String, String), Edge>,
+    paths: BTreeMap<Vec<String>, usize>,
+}
+
+impl StatsCollector {
+    /// Create an empty collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a run from its history alone.
+    ///
+    /// Without the run's start time the first transition has no duration,
+    /// and aborts are only detected when the last transition
+    /// entered an error state. Prefer [`StatsCollector::add_checkpoint`]
+    /// when checkpoints are available.
+    pub fn add_history<S: State>(&mut self, history: &StateHistory<S>) -> &mut Self {
+        let aborted = history
+            .transitions()
+            .last()
+            .is_some_and(|t| t.to.is_error());
+        self.add_run(history, None, None, aborted)
+    }
+
+    /// Add a run from a checkpoint.
+    pub fn add_checkpoint<S: State>(&mut self, checkpoint: &Checkpoint<S>) -> &mut Self {
+        self.add_run(
+            &checkpoint.history,
+            Some(&checkpoint.initial_state),
+            Some(checkpoint.metadata.created_at),
+            checkpoint.current_state.is_error(),
+        )
+    }
+
+    fn add_run<S: State>(
+        &mut self,
+        history: &StateHistory<S>,
+        initial: Option<&S>,
+        started_at: Option<DateTime<Utc>>,
+        aborted: bool,
+    ) -> &mut Self {
+        self.runs += 1;
+        self.aborted += usize::from(aborted);
+
+        let transitions = history.transitions();
+        let mut path: Vec<String> = transitions
+            .first()
+            .map(|t| &t.from)
+            .or(initial)
+            .map(|s| vec![s.name().to_string()])
+            .unwrap_or_default();
+        let mut entered = started_at;
+        for transition in transitions {
+            let edge = self
+                .edges
+                .entry((
+                    transition.from.name().to_string(),
+                    transition.to.name().to_string(),
+                ))
+                .or_default();
+            edge.count += 1;
+            edge.retries += transition.attempt.saturating_sub(1);
+            if let Some(entered) = entered {
+                edge.durations_ms
+                    .push((transition.timestamp - entered).num_milliseconds());
+            }
+            entered = Some(transition.timestamp);
+            path.push(transition.to.name().to_string());
+        }
+        if !path.is_empty() {
+            *self.paths.entry(path).or_default() += 1;
+        }
+        self
+    }
+
+    /// Summarize the runs consumed so far (pure).
+    pub fn report(&self) -> StatsReport {
+        let transitions = self
+            .edges
+            .iter()
+            .map(|((from, to), edge)| {
+                let mut durations = edge.durations_ms.clone();
+                durations.sort_unstable();
+                TransitionStats {
+                    from: from.clone(),
+                    to: to.clone(),
+                    count: edge.count,
+                    median_ms: percentile(&durations, 50),
+                    p95_ms: percentile(&durations, 95),
+                    retries: edge.retries,
+                }
+            })
+            .collect();
+
+        let mut paths: Vec<PathCount> = self
+            .paths
+            .iter()
+            .map(|(path, runs)| PathCount {
+                path: path.clone(),
+                runs: *runs,
+            })
+            .collect();
+        // Stable sort keeps equally common paths in lexical order
+        paths.sort_by(|a, b| b.runs.cmp(&a.runs));
+
+        StatsReport {
+            runs: self.runs,
+            aborted: self.aborted,
+            abort_rate: if self.runs == 0 {
+                0.0
+            } else {
+                self.aborted as f64 / self.runs as f64
+            },
+            transitions,
+            paths,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[i64], pct: usize) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StateTransition;
+    use chrono::Duration;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Start,
+        Charge,
+        Done,
+        Failed,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Start => "Start",
+                Self::Charge => "Charge",
+                Self::Done => "Done",
+                Self::Failed => "Failed",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done | Self::Failed)
+        }
+
+        fn is_error(&self) -> bool {
+            matches!(self, Self::Failed)
+        }
+    }
+
+    fn history(steps: &[(TestState, TestState, i64, usize)]) -> StateHistory<TestState> {
+        let start = Utc::now();
+        steps
+            .iter()
+            .fold(StateHistory::new(), |history, (from, to, at, attempt)| {
+                history.record(StateTransition {
+                    from: from.clone(),
+                    to: to.clone(),
+                    timestamp: start + Duration::milliseconds(*at),
+                    attempt: *attempt,
+                    sequence: 0,
+                    forced: None,
+                    correlation: Default::default(),
+                })
+            })
+    }
+
+    #[test]
+    fn report_aggregates_durations_paths_and_aborts() {
+        use TestState::*;
+        let mut collector = StatsCollector::new();
+        for charge_ms in [100, 200, 300] {
+            collector.add_history(&history(&[
+                (Start.clone(), Charge.clone(), 0, 1),
+                (Charge.clone(), Done.clone(), charge_ms, 1),
+            ]));
+        }
+        collector.add_history(&history(&[
+            (Start.clone(), Charge.clone(), 0, 1),
+            (Charge.clone(), Failed.clone(), 50, 4),
+        ]));
+
+        let report = collector.report();
+        assert_eq!(report.runs, 4);
+        assert_eq!(report.aborted, 1);
+        assert_eq!(report.abort_rate, 0.25);
+
+        let charge_done = report
+            .transitions
+            .iter()
+            .find(|t| t.from == "Charge" && t.to == "Done")
+            .unwrap();
+        assert_eq!(charge_done.count, 3);
+        assert_eq!(charge_done.median_ms, Some(200));
+        assert_eq!(charge_done.p95_ms, Some(300));
+
+        assert_eq!(report.paths[0].path, vec!["Start", "Charge", "Done"]);
+        assert_eq!(report.paths[0].runs, 3);
+
+        let hot = report.retry_hot_spots();
+        assert_eq!(hot.len(), 1);
+        assert_eq!((hot[0].to.as_str(), hot[0].retries), ("Failed", 3));
+    }
+
+    #[test]
+    fn checkpoint_runs_time_the_first_transition() {
+        let mut machine = crate::effects::StateMachine::<TestState, ()>::new(TestState::Start);
+        machine.apply_result(
+            TestState::Start,
+            crate::effects::StepResult::Transitioned(TestState::Charge),
+            1,
+        );
+
+        let report = StatsCollector::new()
+            .add_checkpoint(&machine.checkpoint())
+            .report();
+
+        assert!(report.transitions[0].median_ms.is_some());
+        assert_eq!(report.paths[0].path, vec!["Start", "Charge"]);
+        assert_eq!(report.aborted, 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! Test utilities for state machine definitions.
+//!
+//! These helpers are meant for downstream test suites: they make it easy to
+//! catch unintended changes to workflow definitions during code review.
+
+mod coverage;
+mod mutation;
+mod trace;
+
+pub use coverage::{edge_coverage, CoveragePlan};
+pub use mutation::{mutants, mutation_test, Mutation, MutationReport};
+pub use trace::{assert_trace, TraceMismatch, TracePattern, TraceStep};
+
+use crate::introspection::MachineDescription;
+use std::path::Path;
+
+/// Environment variable that makes snapshot assertions (re)write files.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "MINDSET_UPDATE_SNAPSHOTS";
+
+/// Assert that a description matches a golden snapshot file.
+///
+/// The description is serialized with [`MachineDescription::to_snapshot`]
+/// and compared with the file at `path`. When the environment variable
+/// `MINDSET_UPDATE_SNAPSHOTS` is set, the file is written instead, which is
+/// how snapshots are created and updated.
+///
+/// # Panics
+///
+/// Panics if the file is missing or its contents differ from the snapshot.
+pub fn assert_snapshot(description: &MachineDescription, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let actual = description.to_snapshot();
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create snapshot directory");
+        }
+        std::fs::write(path, &actual).expect("Failed to write snapshot");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|_| {
+        panic!(
+            "Snapshot {} does not exist. Run with {}=1 to create it",
+            path.display(),
+            UPDATE_SNAPSHOTS_ENV
+        )
+    });
+
+    if expected != actual {
+        panic!(
+            "Machine definition does not match snapshot {}\n--- expected\n{}\n--- actual\n{}\nRun with {}=1 to update it",
+            path.display(),
+            expected,
+            actual,
+            UPDATE_SNAPSHOTS_ENV
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::introspection::StateDescription;
+
+    fn description() -> MachineDescription {
+        MachineDescription {
+            initial: "Start".to_string(),
+            states: vec![StateDescription {
+                name: "Start".to_string(),
+                is_final: false,
+                is_error: false,
+            }],
+            transitions: vec![],
+        }
+    }
+
+    fn snapshot_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mindset-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn matching_snapshot_passes() {
+        let path = snapshot_path("match");
+        std::fs::write(&path, description().to_snapshot()).unwrap();
+
+        assert_snapshot(&description(), &path);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn differing_snapshot_panics() {
+        let path = snapshot_path("differ");
+        std::fs::write(&path, "{}\n").unwrap();
+
+        let result = std::panic::catch_unwind(|| assert_snapshot(&description(), &path));
+        std::fs::remove_file(path).unwrap();
+
+        assert!(result.is_err());
+    }
+}
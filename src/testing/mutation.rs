@@ -0,0 +1,238 @@
+//! Mutation testing of workflow definitions.
+//!
+//! [`mutation_test`] applies small faults to a [`Definition`] (dropping a
+//! transition, negating a guard, swapping the targets of two transitions
+//! leaving the same state) and runs the caller's tests against every
+//! mutant. Tests are expected to fail on a mutant; mutants they still pass
+//! on *survive* and point at behaviour the tests do not constrain.
+
+use crate::catalog::Definition;
+use crate::core::{GuardExpr, State};
+use std::fmt;
+
+/// A single fault applied to a definition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    /// The transition at `index` was removed
+    DropTransition {
+        index: usize,
+        from: String,
+        to: String,
+    },
+    /// The guard of the transition at `index` was negated
+    FlipGuard {
+        index: usize,
+        from: String,
+        to: String,
+    },
+    /// Two transitions leaving `from` had their targets swapped
+    SwapTargets {
+        first: usize,
+        second: usize,
+        from: String,
+    },
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DropTransition { index, from, to } => {
+                write!(f, "drop transition #{index} ({from} -> {to})")
+            }
+            Self::FlipGuard { index, from, to } => {
+                write!(f, "flip guard of transition #{index} ({from} -> {to})")
+            }
+            Self::SwapTargets {
+                first,
+                second,
+                from,
+            } => write!(
+                f,
+                "swap targets of transitions #{first} and #{second} (from {from})"
+            ),
+        }
+    }
+}
+
+/// Outcome of a mutation test run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MutationReport {
+    /// Mutants the tests failed on
+    pub killed: Vec<Mutation>,
+    /// Mutants the tests passed on
+    pub survived: Vec<Mutation>,
+}
+
+impl MutationReport {
+    /// Fraction of mutants killed, `1.0` when there were none
+    pub fn score(&self) -> f64 {
+        let total = self.killed.len() + self.survived.len();
+        if total == 0 {
+            1.0
+        } else {
+            self.killed.len() as f64 / total as f64
+        }
+    }
+}
+
+/// All single-fault mutants of `definition`, in transition order (pure)
+pub fn mutants<S: State>(definition: &Definition<S>) -> Vec<(Mutation, Definition<S>)> {
+    let transitions = &definition.transitions;
+    let edge = |index: usize| {
+        (
+            transitions[index].from.name().to_string(),
+            transitions[index].to.name().to_string(),
+        )
+    };
+    let mut mutants = Vec::new();
+
+    for index in 0..transitions.len() {
+        let (from, to) = edge(index);
+        let mut mutant = definition.clone();
+        mutant.transitions.remove(index);
+        mutants.push((Mutation::DropTransition { index, from, to }, mutant));
+    }
+
+    for (index, spec) in transitions.iter().enumerate() {
+        let Some(guard) = &spec.guard else { continue };
+        let (from, to) = edge(index);
+        let mut mutant = definition.clone();
+        mutant.transitions[index].guard = Some(match guard {
+            GuardExpr::Not { expr } => (**expr).clone(),
+            guard => GuardExpr::negate(guard.clone()),
+        });
+        mutants.push((Mutation::FlipGuard { index, from, to }, mutant));
+    }
+
+    for first in 0..transitions.len() {
+        for second in first + 1..transitions.len() {
+            let (a, b) = (&transitions[first], &transitions[second]);
+            if a.from.name() != b.from.name() || a.to.name() == b.to.name() {
+                continue;
+            }
+            let mut mutant = definition.clone();
+            mutant.transitions[first].to = b.to.clone();
+            mutant.transitions[second].to = a.to.clone();
+            let from = a.from.name().to_string();
+            mutants.push((
+                Mutation::SwapTargets {
+                    first,
+                    second,
+                    from,
+                },
+                mutant,
+            ));
+        }
+    }
+
+    mutants
+}
+
+/// Run `tests` against every mutant of `definition`.
+///
+/// `tests` returns whether the tests pass on the given definition; it
+/// typically instantiates the definition against the real action registry
+/// and runs property tests or simulations on the resulting machines.
+pub fn mutation_test<S: State>(
+    definition: &Definition<S>,
+    mut tests: impl FnMut(&Definition<S>) -> bool,
+) -> MutationReport {
+    let mut report = MutationReport::default();
+    for (mutation, mutant) in mutants(definition) {
+        if tests(&mutant) {
+            report.survived.push(mutation);
+        } else {
+            report.killed.push(mutation);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::TransitionSpec;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Claim {
+        Filed { covered: bool },
+        Paid,
+        Denied,
+    }
+
+    impl State for Claim {
+        fn name(&self) -> &str {
+            match self {
+                Self::Filed { .. } => "Filed",
+                Self::Paid => "Paid",
+                Self::Denied => "Denied",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Paid | Self::Denied)
+        }
+    }
+
+    fn definition() -> Definition<Claim> {
+        let filed = Claim::Filed { covered: false };
+        Definition::new("claims", 1, filed.clone())
+            .transition(
+                TransitionSpec::new(filed.clone(), Claim::Paid, "pay")
+                    .when_expr(GuardExpr::eq("Filed.covered", json!(true))),
+            )
+            .transition(TransitionSpec::new(filed, Claim::Denied, "deny"))
+    }
+
+    /// Target of the first transition whose guard is absent or holds for
+    /// a claim filed with `covered`
+    fn outcome(definition: &Definition<Claim>, covered: bool) -> Option<Claim> {
+        let state = Claim::Filed { covered };
+        definition
+            .transitions
+            .iter()
+            .find(|t| t.guard.as_ref().is_none_or(|g| g.eval(&state)))
+            .map(|t| t.to.clone())
+    }
+
+    #[test]
+    fn generates_each_kind_of_mutant() {
+        let mutations: Vec<String> = mutants(&definition())
+            .into_iter()
+            .map(|(mutation, _)| mutation.to_string())
+            .collect();
+
+        assert_eq!(
+            mutations,
+            vec![
+                "drop transition #0 (Filed -> Paid)",
+                "drop transition #1 (Filed -> Denied)",
+                "flip guard of transition #0 (Filed -> Paid)",
+                "swap targets of transitions #0 and #1 (from Filed)",
+            ]
+        );
+    }
+
+    #[test]
+    fn weak_tests_let_mutants_survive() {
+        let thorough = mutation_test(&definition(), |d| {
+            outcome(d, true) == Some(Claim::Paid) && outcome(d, false) == Some(Claim::Denied)
+        });
+        assert!(thorough.survived.is_empty());
+        assert_eq!(thorough.score(), 1.0);
+
+        let happy_path_only =
+            mutation_test(&definition(), |d| outcome(d, true) == Some(Claim::Paid));
+        assert_eq!(
+            happy_path_only.survived,
+            vec![Mutation::DropTransition {
+                index: 1,
+                from: "Filed".to_string(),
+                to: "Denied".to_string(),
+            }]
+        );
+        assert_eq!(happy_path_only.score(), 0.75);
+    }
+}
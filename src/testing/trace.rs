@@ -0,0 +1,264 @@
+//! Pattern assertions over executed paths.
+//!
+//! Retries and loops make exact path vectors brittle to assert on. A
+//! [`TracePattern`] describes the acceptable paths instead, with
+//! repetition and wildcards, and [`assert_trace`] reports where a history
+//! diverges from it. Patterns are usually written with
+//! [`expect_trace!`](crate::expect_trace).
+
+use crate::core::{State, StateHistory};
+use std::fmt;
+
+/// One element of a [`TracePattern`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceStep {
+    /// Exactly one visit of the named state (`"Draft"`)
+    State(String),
+    /// Zero or more consecutive visits of the named state (`"Review"*`)
+    ZeroOrMore(String),
+    /// One or more consecutive visits of the named state (`"Review"+`)
+    OneOrMore(String),
+    /// Any single state (`_`)
+    Any,
+    /// Any number of states, including none (`..`)
+    Rest,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::State(name) => write!(f, "{name:?}"),
+            Self::ZeroOrMore(name) => write!(f, "{name:?}*"),
+            Self::OneOrMore(name) => write!(f, "{name:?}+"),
+            Self::Any => write!(f, "_"),
+            Self::Rest => write!(f, ".."),
+        }
+    }
+}
+
+/// Pattern over the sequence of state names a machine visited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracePattern {
+    steps: Vec<TraceStep>,
+}
+
+/// Where a path stopped matching a [`TracePattern`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceMismatch {
+    /// The pattern that did not match
+    pub pattern: TracePattern,
+    /// The path that was checked
+    pub path: Vec<String>,
+    /// Index into `path` of the first state no match attempt got past
+    pub position: usize,
+    /// What the pattern expected at `position`, `None` for the end of the path
+    pub expected: Option<TraceStep>,
+}
+
+impl fmt::Display for TracePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<String> = self.steps.iter().map(ToString::to_string).collect();
+        write!(f, "[{}]", steps.join(", "))
+    }
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Path does not match pattern")?;
+        writeln!(f, "  pattern: {}", self.pattern)?;
+        writeln!(f, "  path:    {}", self.path.join(" -> "))?;
+        let found = self
+            .path
+            .get(self.position)
+            .map_or("end of path".to_string(), |name| format!("{name:?}"));
+        match &self.expected {
+            Some(step) => write!(f, "  at {}: expected {step}, found {found}", self.position),
+            None => write!(
+                f,
+                "  at {}: expected end of path, found {found}",
+                self.position
+            ),
+        }
+    }
+}
+
+impl TracePattern {
+    /// Create a pattern from its steps
+    pub fn new(steps: Vec<TraceStep>) -> Self {
+        Self { steps }
+    }
+
+    /// The pattern's steps
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// Whether `path` matches the pattern (pure)
+    pub fn matches<N: AsRef<str>>(&self, path: &[N]) -> bool {
+        self.check(path).is_ok()
+    }
+
+    /// Match `path` against the pattern, describing the divergence that
+    /// got furthest into the path on failure (pure)
+    pub fn check<N: AsRef<str>>(&self, path: &[N]) -> Result<(), TraceMismatch> {
+        let names: Vec<&str> = path.iter().map(AsRef::as_ref).collect();
+        let mut furthest = None;
+        if match_from(&self.steps, &names, 0, &mut furthest) {
+            return Ok(());
+        }
+        let (position, expected) = furthest.unwrap_or((0, None));
+        Err(TraceMismatch {
+            pattern: self.clone(),
+            path: names.iter().map(|name| name.to_string()).collect(),
+            position,
+            expected: expected.cloned(),
+        })
+    }
+}
+
+/// Furthest position a match attempt failed at, with the step expected
+/// there (`None` for the end of the path).
+type Furthest<'p> = Option<(usize, Option<&'p TraceStep>)>;
+
+/// Backtracking match of `steps` against `path[at..]`.
+fn match_from<'p>(
+    steps: &'p [TraceStep],
+    path: &[&str],
+    at: usize,
+    furthest: &mut Furthest<'p>,
+) -> bool {
+    let mut fail = |expected: Option<&'p TraceStep>| {
+        if furthest.is_none_or(|(position, _)| at > position) {
+            *furthest = Some((at, expected));
+        }
+        false
+    };
+    let Some((step, rest)) = steps.split_first() else {
+        return at == path.len() || fail(None);
+    };
+    let is = |name: &str| path.get(at) == Some(&name);
+
+    match step {
+        TraceStep::State(name) if is(name) => match_from(rest, path, at + 1, furthest),
+        TraceStep::Any if at < path.len() => match_from(rest, path, at + 1, furthest),
+        TraceStep::State(_) | TraceStep::Any => fail(Some(step)),
+        TraceStep::Rest => (at..=path.len()).any(|next| match_from(rest, path, next, furthest)),
+        TraceStep::ZeroOrMore(name) | TraceStep::OneOrMore(name) => {
+            let min = usize::from(matches!(step, TraceStep::OneOrMore(_)));
+            let run = path[at.min(path.len())..]
+                .iter()
+                .take_while(|visited| *visited == name)
+                .count();
+            if run < min {
+                return fail(Some(step));
+            }
+            (min..=run)
+                .rev()
+                .any(|count| match_from(rest, path, at + count, furthest))
+        }
+    }
+}
+
+/// Assert that the path of `history` matches `pattern`.
+///
+/// # Panics
+///
+/// Panics with the pattern, the path and the first divergence if the path
+/// does not match.
+pub fn assert_trace<S: State>(history: &StateHistory<S>, pattern: &TracePattern) {
+    let path: Vec<&str> = history.get_path().into_iter().map(State::name).collect();
+    if let Err(mismatch) = pattern.check(&path) {
+        panic!("{mismatch}");
+    }
+}
+
+/// Build a [`TracePattern`](crate::testing::TracePattern) from state names,
+/// `"Name"*` and `"Name"+` repetitions, `_` and `..` wildcards.
+///
+/// # Example
+///
+/// ```
+/// use mindset::expect_trace;
+///
+/// let pattern = expect_trace!["Draft", "Review"+, _, ..];
+/// assert!(pattern.matches(&["Draft", "Review", "Review", "Approved"]));
+/// assert!(!pattern.matches(&["Draft", "Approved"]));
+/// ```
+#[macro_export]
+macro_rules! expect_trace {
+    ($($pattern:tt)*) => {
+        $crate::testing::TracePattern::new($crate::__trace_steps!([] $($pattern)*))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_steps {
+    ([$($done:expr,)*]) => {
+        vec![$($done),*]
+    };
+    ([$($done:expr,)*] .. $(, $($rest:tt)*)?) => {
+        $crate::__trace_steps!([$($done,)* $crate::testing::TraceStep::Rest,] $($($rest)*)?)
+    };
+    ([$($done:expr,)*] _ $(, $($rest:tt)*)?) => {
+        $crate::__trace_steps!([$($done,)* $crate::testing::TraceStep::Any,] $($($rest)*)?)
+    };
+    ([$($done:expr,)*] $name:literal * $(, $($rest:tt)*)?) => {
+        $crate::__trace_steps!(
+            [$($done,)* $crate::testing::TraceStep::ZeroOrMore($name.to_string()),]
+            $($($rest)*)?
+        )
+    };
+    ([$($done:expr,)*] $name:literal + $(, $($rest:tt)*)?) => {
+        $crate::__trace_steps!(
+            [$($done,)* $crate::testing::TraceStep::OneOrMore($name.to_string()),]
+            $($($rest)*)?
+        )
+    };
+    ([$($done:expr,)*] $name:literal $(, $($rest:tt)*)?) => {
+        $crate::__trace_steps!(
+            [$($done,)* $crate::testing::TraceStep::State($name.to_string()),]
+            $($($rest)*)?
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repetition_and_wildcards() {
+        let pattern = expect_trace!["Draft", "Review"*, "Approved", ..];
+
+        assert!(pattern.matches(&["Draft", "Approved"]));
+        assert!(pattern.matches(&["Draft", "Review", "Review", "Approved", "Archived"]));
+        assert!(!pattern.matches(&["Draft", "Review", "Rejected"]));
+        assert!(expect_trace![_, "Review"+].matches(&["Draft", "Review"]));
+        assert!(!expect_trace![_, "Review"+].matches(&["Draft"]));
+        assert_eq!(
+            pattern.to_string(),
+            r#"["Draft", "Review"*, "Approved", ..]"#
+        );
+    }
+
+    #[test]
+    fn mismatch_points_at_the_divergence() {
+        let pattern = expect_trace!["Draft", "Review"*, "Approved"];
+        let mismatch = pattern
+            .check(&["Draft", "Review", "Review", "Rejected"])
+            .unwrap_err();
+
+        assert_eq!(mismatch.position, 3);
+        assert_eq!(
+            mismatch.expected,
+            Some(TraceStep::State("Approved".to_string()))
+        );
+        assert!(mismatch
+            .to_string()
+            .ends_with(r#"at 3: expected "Approved", found "Rejected""#));
+
+        let too_long = pattern.check(&["Draft", "Approved", "Draft"]).unwrap_err();
+        assert_eq!((too_long.position, too_long.expected), (2, None));
+    }
+}
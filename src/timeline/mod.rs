@@ -0,0 +1,225 @@
+//! Gantt-style timelines of a run.
+//!
+//! A [`Timeline`] turns a machine's history into one bar per state
+//! occupancy: when the machine entered the state, when it left, and how
+//! many attempts the outgoing transition took. Timelines serialize to JSON
+//! and render as a Mermaid `gantt` chart, which makes slow steps easy to
+//! spot in postmortems, and export to OpenTelemetry trace backends such as
+//! Jaeger or Tempo.
+
+use crate::core::State;
+use crate::effects::StateMachine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+mod otlp;
+
+/// One period the machine spent in a state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimelineBar {
+    /// Name of the occupied state
+    pub state: String,
+    /// When the machine entered the state
+    pub start: DateTime<Utc>,
+    /// When the machine left the state; the export time for an ongoing
+    /// state and the entry time for a final one
+    pub end: DateTime<Utc>,
+    /// Length of the occupancy in milliseconds
+    pub duration_ms: i64,
+    /// Attempts made at the transition out of the state
+    pub attempts: usize,
+    /// Whether the machine is still in the state
+    pub ongoing: bool,
+}
+
+/// Bars of a run, oldest first.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    /// State occupancies in the order they happened
+    pub bars: Vec<TimelineBar>,
+}
+
+fn bar(state: &str, start: DateTime<Utc>, end: DateTime<Utc>, attempts: usize) -> TimelineBar {
+    TimelineBar {
+        state: state.to_string(),
+        start,
+        end,
+        duration_ms: (end - start).num_milliseconds(),
+        attempts,
+        ongoing: false,
+    }
+}
+
+impl Timeline {
+    /// Render as a Mermaid `gantt` chart.
+    ///
+    /// Bars are labelled with the state name, annotated with the attempt
+    /// count when the transition out of the state was retried. The
+    /// ongoing bar is marked `active`.
+    pub fn to_mermaid(&self, title: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "gantt");
+        let _ = writeln!(out, "    title {}", sanitize(title));
+        let _ = writeln!(out, "    dateFormat x");
+        let _ = writeln!(out, "    axisFormat %H:%M:%S");
+        for (index, bar) in self.bars.iter().enumerate() {
+            let mut label = sanitize(&bar.state);
+            if bar.attempts > 1 {
+                let _ = write!(label, " ({} attempts)", bar.attempts);
+            }
+            let tag = if bar.ongoing { "active" } else { "done" };
+            let _ = writeln!(
+                out,
+                "    {label} :{tag}, s{index}, {}, {}",
+                bar.start.timestamp_millis(),
+                bar.end.timestamp_millis()
+            );
+        }
+        out
+    }
+}
+
+/// Mermaid uses `:`, `;` and `#` as syntax inside task lines.
+fn sanitize(text: &str) -> String {
+    text.replace([':', ';', '#'], " ")
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Build a timeline of the run as of now (pure apart from reading the clock).
+    pub fn timeline(&self) -> Timeline {
+        self.timeline_at(Utc::now())
+    }
+
+    /// Build a timeline of the run as of `now` (pure).
+    ///
+    /// The first bar starts when the machine was created. An abort into an
+    /// error state is not recorded as a transition; it closes the last bar
+    /// when the current state was entered.
+    pub fn timeline_at(&self, now: DateTime<Utc>) -> Timeline {
+        let mut bars = Vec::new();
+        let mut state = self.initial_state();
+        let mut entered = self.created_at();
+        for transition in self.history().transitions() {
+            bars.push(bar(
+                transition.from.name(),
+                entered,
+                transition.timestamp,
+                transition.attempt,
+            ));
+            state = &transition.to;
+            entered = transition.timestamp;
+        }
+
+        let current = self.current_state();
+        let mut attempts = self.attempt_count();
+        if current != state {
+            let left = self.current_transition_started_at();
+            bars.push(bar(state.name(), entered, left, attempts));
+            entered = left;
+            attempts = 0;
+        }
+        // A completed run ends when the final state is entered
+        let end = if current.is_final() {
+            entered
+        } else {
+            now.max(entered)
+        };
+        bars.push(TimelineBar {
+            ongoing: !current.is_final(),
+            ..bar(current.name(), entered, end, attempts)
+        });
+
+        Timeline { bars }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::StepResult;
+    use chrono::Duration;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum TestState {
+        Queued,
+        Running,
+        Done,
+    }
+
+    impl State for TestState {
+        fn name(&self) -> &str {
+            match self {
+                Self::Queued => "Queued",
+                Self::Running => "Running",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    fn machine() -> StateMachine<TestState, ()> {
+        let mut machine = StateMachine::new(TestState::Queued);
+        let start = machine.created_at();
+        machine.apply_result_at(
+            TestState::Queued,
+            StepResult::Transitioned(TestState::Running),
+            1,
+            Default::default(),
+            start + Duration::seconds(2),
+        );
+        machine.apply_result_at(
+            TestState::Running,
+            StepResult::Transitioned(TestState::Done),
+            3,
+            Default::default(),
+            start + Duration::seconds(12),
+        );
+        machine
+    }
+
+    #[test]
+    fn bars_cover_each_state_occupancy() {
+        let machine = machine();
+        let timeline = machine.timeline_at(machine.created_at() + Duration::seconds(20));
+
+        let summary: Vec<_> = timeline
+            .bars
+            .iter()
+            .map(|b| (b.state.as_str(), b.duration_ms, b.attempts, b.ongoing))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Queued", 2_000, 1, false),
+                ("Running", 10_000, 3, false),
+                ("Done", 0, 0, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn ongoing_state_is_marked_active() {
+        let machine = StateMachine::<TestState, ()>::new(TestState::Queued);
+        let timeline = machine.timeline_at(machine.created_at() + Duration::seconds(5));
+
+        assert_eq!(timeline.bars.len(), 1);
+        assert!(timeline.bars[0].ongoing);
+        assert!(timeline.to_mermaid("Job").contains("Queued :active, s0, "));
+    }
+
+    #[test]
+    fn mermaid_annotates_retries() {
+        let machine = machine();
+        let chart = machine
+            .timeline_at(machine.created_at() + Duration::seconds(20))
+            .to_mermaid("Job: nightly");
+
+        assert!(chart.starts_with("gantt\n    title Job  nightly\n    dateFormat x\n"));
+        assert!(chart.contains("    Running (3 attempts) :done, s1, "));
+        assert_eq!(chart.lines().filter(|l| l.contains(":done")).count(), 3);
+    }
+}
@@ -0,0 +1,275 @@
+//! OpenTelemetry export of a run.
+//!
+//! [`StateMachine::otlp_trace_at`] encodes a run's [`Timeline`] as an OTLP
+//! trace in the protocol's JSON mapping, ready to POST to a collector's
+//! `/v1/traces` endpoint. The run is a root span with one child span per
+//! state occupancy; retried transitions and administrative history events
+//! (pauses, resumes, ...) become span events.
+
+use crate::core::{HistoryEvent, State};
+use crate::effects::StateMachine;
+use crate::timeline::{Timeline, TimelineBar};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Instrumentation scope reported for exported spans
+const SCOPE: &str = "mindset";
+
+/// OTLP `SPAN_KIND_INTERNAL`
+const KIND_INTERNAL: u8 = 1;
+
+/// OTLP `STATUS_CODE_ERROR`
+const STATUS_ERROR: u8 = 2;
+
+fn nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt()
+        .unwrap_or_default()
+        .max(0)
+        .to_string()
+}
+
+fn span_id(index: usize) -> String {
+    format!("{:016x}", index + 1)
+}
+
+fn string_attr(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn int_attr(key: &str, value: usize) -> Value {
+    json!({ "key": key, "value": { "intValue": value.to_string() } })
+}
+
+fn bool_attr(key: &str, value: bool) -> Value {
+    json!({ "key": key, "value": { "boolValue": value } })
+}
+
+fn event(name: &str, time: DateTime<Utc>, attributes: Vec<Value>) -> Value {
+    json!({ "name": name, "timeUnixNano": nanos(time), "attributes": attributes })
+}
+
+/// Span event for an administrative history event
+fn history_event(recorded: &HistoryEvent) -> (DateTime<Utc>, Value) {
+    let (name, timestamp, attributes) = match recorded {
+        HistoryEvent::Paused { reason, timestamp } => {
+            ("paused", *timestamp, vec![string_attr("reason", reason)])
+        }
+        HistoryEvent::Resumed { timestamp } => ("resumed", *timestamp, vec![]),
+        HistoryEvent::InvariantViolated {
+            invariant,
+            state,
+            timestamp,
+        } => (
+            "invariant_violated",
+            *timestamp,
+            vec![
+                string_attr("invariant", invariant),
+                string_attr("state", state),
+            ],
+        ),
+        HistoryEvent::TransitionAdded {
+            from,
+            to,
+            timestamp,
+        } => (
+            "transition_added",
+            *timestamp,
+            vec![string_attr("from", from), string_attr("to", to)],
+        ),
+        HistoryEvent::TransitionRemoved {
+            from,
+            to,
+            timestamp,
+        } => (
+            "transition_removed",
+            *timestamp,
+            vec![string_attr("from", from), string_attr("to", to)],
+        ),
+        HistoryEvent::LockAcquired { key, timestamp } => {
+            ("lock_acquired", *timestamp, vec![string_attr("key", key)])
+        }
+        HistoryEvent::LockReleased { key, timestamp } => {
+            ("lock_released", *timestamp, vec![string_attr("key", key)])
+        }
+    };
+    (timestamp, event(name, timestamp, attributes))
+}
+
+fn state_span(trace_id: &str, index: usize, bar: &TimelineBar, mut events: Vec<Value>) -> Value {
+    if bar.attempts > 1 {
+        events.push(event(
+            "retried",
+            bar.end,
+            vec![int_attr("mindset.attempts", bar.attempts)],
+        ));
+    }
+    json!({
+        "traceId": trace_id,
+        "spanId": span_id(index + 1),
+        "parentSpanId": span_id(0),
+        "name": bar.state,
+        "kind": KIND_INTERNAL,
+        "startTimeUnixNano": nanos(bar.start),
+        "endTimeUnixNano": nanos(bar.end),
+        "attributes": [
+            string_attr("mindset.state", &bar.state),
+            int_attr("mindset.attempts", bar.attempts),
+            bool_attr("mindset.ongoing", bar.ongoing),
+        ],
+        "events": events,
+    })
+}
+
+impl<S: State + 'static, Env: Clone + Send + Sync + 'static> StateMachine<S, Env> {
+    /// Export the run as an OTLP trace as of now, under a trace id derived
+    /// from a new id of the machine's id generator, so traces get random
+    /// ids by default and reproducible ones with deterministic generators.
+    pub fn otlp_trace(&self, service_name: &str) -> Value {
+        let digest = Sha256::digest(self.generate_id().as_bytes());
+        let mut trace_id = [0; 16];
+        trace_id.copy_from_slice(&digest[..16]);
+        self.otlp_trace_at(service_name, Uuid::from_bytes(trace_id), Utc::now())
+    }
+
+    /// Export the run as of `now` as an OTLP trace with `trace_id` (pure).
+    ///
+    /// The root span is named after `service_name` and covers the whole
+    /// run. A run that ended in an error state has an error status on the
+    /// root span and on the error state's span.
+    pub fn otlp_trace_at(&self, service_name: &str, trace_id: Uuid, now: DateTime<Utc>) -> Value {
+        let Timeline { bars } = self.timeline_at(now);
+        let trace_id = trace_id.simple().to_string();
+
+        let mut events: Vec<_> = self.history().events().iter().map(history_event).collect();
+        events.sort_by_key(|(timestamp, _)| *timestamp);
+        let mut events = events.into_iter().peekable();
+
+        let failed = self.current_state().is_error();
+        let mut spans = Vec::with_capacity(bars.len() + 1);
+        for (index, bar) in bars.iter().enumerate() {
+            // Events belong to the occupancy they happened in; the last one
+            // takes everything left over
+            let last = index + 1 == bars.len();
+            let mut own = Vec::new();
+            while let Some((_, event)) = events.next_if(|(at, _)| last || *at < bar.end) {
+                own.push(event);
+            }
+            let mut span = state_span(&trace_id, index, bar, own);
+            if last && failed {
+                span["status"] = json!({ "code": STATUS_ERROR });
+            }
+            spans.push(span);
+        }
+
+        let start = bars.first().map_or(now, |bar| bar.start);
+        let end = bars.last().map_or(now, |bar| bar.end);
+        let mut root = json!({
+            "traceId": trace_id,
+            "spanId": span_id(0),
+            "name": service_name,
+            "kind": KIND_INTERNAL,
+            "startTimeUnixNano": nanos(start),
+            "endTimeUnixNano": nanos(end),
+            "attributes": [
+                string_attr("mindset.machine_id", self.id()),
+                string_attr("mindset.initial_state", self.initial_state().name()),
+                string_attr("mindset.current_state", self.current_state().name()),
+                int_attr("mindset.transitions", self.history().transitions().len()),
+            ],
+        });
+        if failed {
+            root["status"] = json!({ "code": STATUS_ERROR });
+        }
+        spans.insert(0, root);
+
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [string_attr("service.name", service_name)] },
+                "scopeSpans": [{
+                    "scope": { "name": SCOPE, "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::StepResult;
+    use chrono::Duration;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    enum Job {
+        Queued,
+        Running,
+        Done,
+    }
+
+    impl State for Job {
+        fn name(&self) -> &str {
+            match self {
+                Self::Queued => "Queued",
+                Self::Running => "Running",
+                Self::Done => "Done",
+            }
+        }
+
+        fn is_final(&self) -> bool {
+            matches!(self, Self::Done)
+        }
+    }
+
+    #[test]
+    fn occupancies_become_child_spans_with_retry_events() {
+        let mut machine = StateMachine::<Job, ()>::new(Job::Queued);
+        let start = machine.created_at();
+        machine.apply_result_at(
+            Job::Queued,
+            StepResult::Transitioned(Job::Running),
+            1,
+            Default::default(),
+            start + Duration::seconds(1),
+        );
+        machine.apply_result_at(
+            Job::Running,
+            StepResult::Transitioned(Job::Done),
+            3,
+            Default::default(),
+            start + Duration::seconds(4),
+        );
+
+        let trace = machine.otlp_trace_at("nightly", Uuid::nil(), start + Duration::seconds(9));
+        let spans = trace["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+
+        let names: Vec<_> = spans.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["nightly", "Queued", "Running", "Done"]);
+        assert!(spans[1..]
+            .iter()
+            .all(|s| s["parentSpanId"] == spans[0]["spanId"]));
+        assert_eq!(spans[0]["traceId"], "00000000000000000000000000000000");
+        assert_eq!(spans[2]["events"][0]["name"], "retried");
+        assert!(spans[1]["events"].as_array().unwrap().is_empty());
+        assert_eq!(spans[0]["endTimeUnixNano"], spans[3]["endTimeUnixNano"]);
+    }
+
+    #[test]
+    fn trace_ids_come_from_the_id_generator() {
+        let trace_id = |prefix: &str| {
+            let mut machine = StateMachine::<Job, ()>::new(Job::Queued);
+            machine.set_id_generator(crate::checkpoint::SequentialIds::new(prefix));
+            let trace = machine.otlp_trace("nightly");
+            let root = &trace["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+            assert_eq!(root["attributes"][0]["value"]["stringValue"], "job-1");
+            root["traceId"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(trace_id("job"), trace_id("job"));
+    }
+}
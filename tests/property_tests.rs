@@ -95,6 +95,9 @@ proptest! {
                 to: to_state.clone(),
                 timestamp: Utc::now(),
                 attempt: 1,
+                correlation: Default::default(),
+                sequence: 0,
+                forced: None,
             };
 
             history = history.record(transition);
@@ -118,6 +121,9 @@ proptest! {
             to: state2,
             timestamp: Utc::now(),
             attempt: 1,
+            correlation: Default::default(),
+            sequence: 0,
+            forced: None,
         };
 
         let new_history = history.record(transition);
@@ -147,6 +153,9 @@ proptest! {
                 to: to_state.clone(),
                 timestamp: base_time,
                 attempt: 1,
+                correlation: Default::default(),
+                sequence: 0,
+                forced: None,
             };
 
             history = history.record(transition);
@@ -182,6 +191,9 @@ proptest! {
                 to: to_state.clone(),
                 timestamp: Utc::now(),
                 attempt: 1,
+                correlation: Default::default(),
+                sequence: 0,
+                forced: None,
             };
 
             history = history.record(transition);